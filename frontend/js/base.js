@@ -1,9 +1,9 @@
-const [base_url_ui, base_url_api, base_ext_ui, base_ext_api] = [
-  "http://localhost:3000",
-  "http://localhost:8080",
-  "/viz/",
-  "/api/",
-];
+// server.js serves the UI on port 3000 next to the API on port 8080, while the Rust server serves
+// both from the same origin.
+const [base_url_ui, base_url_api, base_ext_ui, base_ext_api] =
+  window.location.port === "3000"
+    ? ["http://localhost:3000", "http://localhost:8080", "/viz/", "/api/"]
+    : [window.location.origin, window.location.origin, "/viz/", "/api/"];
 
 const capitalizeWords = (str) => {
   return str
@@ -0,0 +1,27 @@
+use std::{env, time::Instant};
+
+use anyhow::{bail, Context, Result};
+use neuronav::data::NeuronStore;
+
+const USAGE: &str = "Usage: compile_store <model_name>...";
+
+/// Compiles the JSON neuron stores of models into binary stores the server can open directly.
+pub fn main() -> Result<()> {
+    let model_names: Vec<_> = env::args().skip(1).collect();
+    if model_names.is_empty() {
+        bail!("{USAGE}");
+    }
+    for model_name in model_names {
+        let start = Instant::now();
+        let path = NeuronStore::write(&model_name)
+            .with_context(|| format!("Failed to compile neuron store of model '{model_name}'."))?;
+        let neuron_store = NeuronStore::open(&path)?;
+        println!(
+            "Compiled neuron store of model '{model_name}' to '{}' ({} MiB) in {:.1} s.",
+            path.display(),
+            neuron_store.mapped_size() >> 20,
+            start.elapsed().as_secs_f64()
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,55 @@
+use std::{
+    env,
+    io::{self, Write},
+    pin::pin,
+    sync::Arc,
+};
+
+use anyhow::{bail, Context, Result};
+use futures::StreamExt;
+use neuronav::server::{self, ExportScope, State};
+use serde_json::json;
+use tokio::runtime::Runtime;
+
+const USAGE: &str = "Usage: export <model_name> [layer_index] [--services service1,service2,...]";
+
+pub fn main() -> Result<()> {
+    let mut model_name = None;
+    let mut layer_index = None;
+    let mut service_names = None;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--services" {
+            let services = args.next().context(USAGE)?;
+            service_names = Some(services.split(',').map(str::to_owned).collect());
+        } else if model_name.is_none() {
+            model_name = Some(arg);
+        } else if layer_index.is_none() {
+            layer_index = Some(
+                arg.parse::<u32>()
+                    .with_context(|| format!("Layer index '{arg}' not a valid integer."))?,
+            );
+        } else {
+            bail!("Unexpected argument '{arg}'.\n{USAGE}");
+        }
+    }
+    let model_name = model_name.context(USAGE)?;
+    let scope = match layer_index {
+        Some(layer_index) => ExportScope::Layer(layer_index),
+        None => ExportScope::Model,
+    };
+
+    let state = Arc::new(State::default());
+    Runtime::new()
+        .context("Failed to start async runtime to export data.")?
+        .block_on(async {
+            let lines = server::export_stream(state, json!({}), &model_name, scope, service_names)?;
+            let mut lines = pin!(lines);
+            let mut stdout = io::stdout().lock();
+            while let Some(line) = lines.next().await {
+                stdout.write_all(line?.as_bytes())?;
+            }
+            stdout.flush()?;
+            Ok(())
+        })
+}
@@ -0,0 +1,25 @@
+use std::env;
+
+use anyhow::{bail, Context, Result};
+use neuronav::data::retrieve::explanation::import_explanations;
+
+const USAGE: &str = "Usage: import_explanations <model_name> <explanations_path>";
+
+/// Imports explanations from OpenAI's automated interpretability project, laid out as
+/// `{layer_index}/{neuron_index}.jsonl` in a local directory.
+pub fn main() -> Result<()> {
+    let data_path = "data";
+    let mut args = env::args().skip(1);
+    let model_name = args.next().context(USAGE)?;
+    let source_path = args.next().context(USAGE)?;
+    if let Some(arg) = args.next() {
+        bail!("Unexpected argument '{arg}'.\n{USAGE}");
+    }
+
+    let num_imported =
+        import_explanations(&source_path, data_path, &model_name).with_context(|| {
+            format!("Failed to import explanations from '{source_path}' for model '{model_name}'.")
+        })?;
+    println!("Imported explanations of {num_imported} neurons.");
+    Ok(())
+}
@@ -0,0 +1,91 @@
+use std::env;
+
+use anyhow::{bail, Context, Result};
+use neuronav::data::{
+    retrieve::activations::{self, ActivationArray},
+    LayerMetadata, ModelMetadata,
+};
+
+const USAGE: &str = "Usage: ingest <model_name> <tokens.json> <layer_0_activations> [<layer_1_activations> ...] [--tensor TENSOR_NAME] [--texts-per-neuron COUNT] [--activation-function NAME] [--dataset NAME] [--num-parameters COUNT]";
+
+const DEFAULT_NUM_TEXTS_PER_NEURON: usize = 20;
+
+/// Creates neuroscope pages for a model from its activations on a set of texts. Activations are
+/// given per layer as `.npy` or `.safetensors` files of shape `[n_texts, n_tokens, n_neurons]`,
+/// and the tokens of the texts as a JSON list of lists of token strings.
+pub fn main() -> Result<()> {
+    let data_path = "data";
+    let mut positional_args = Vec::new();
+    let mut tensor_name = None;
+    let mut num_texts_per_neuron = DEFAULT_NUM_TEXTS_PER_NEURON;
+    let mut activation_function = "unknown".to_owned();
+    let mut dataset = "unknown".to_owned();
+    let mut num_total_parameters = 0;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--tensor" => tensor_name = Some(args.next().context(USAGE)?),
+            "--texts-per-neuron" => {
+                let value = args.next().context(USAGE)?;
+                num_texts_per_neuron = value
+                    .parse()
+                    .with_context(|| format!("Text count '{value}' not a valid integer."))?;
+            }
+            "--activation-function" => activation_function = args.next().context(USAGE)?,
+            "--dataset" => dataset = args.next().context(USAGE)?,
+            "--num-parameters" => {
+                let value = args.next().context(USAGE)?;
+                num_total_parameters = value
+                    .parse()
+                    .with_context(|| format!("Parameter count '{value}' not a valid integer."))?;
+            }
+            _ if arg.starts_with("--") => bail!("Unknown option '{arg}'.\n{USAGE}"),
+            _ => positional_args.push(arg),
+        }
+    }
+    if positional_args.len() < 3 {
+        bail!(USAGE);
+    }
+    let model_name = positional_args.remove(0);
+    let tokens_path = positional_args.remove(0);
+
+    let tokens = activations::load_tokens(&tokens_path)?;
+    let layer_activations = positional_args
+        .iter()
+        .map(|path| ActivationArray::open(path, tensor_name.as_deref()))
+        .collect::<Result<Vec<_>>>()?;
+    let layers = layer_activations
+        .iter()
+        .map(|activations| LayerMetadata {
+            num_neurons: activations.num_neurons() as u32,
+        })
+        .collect::<Vec<_>>();
+    let model_metadata = ModelMetadata {
+        name: model_name,
+        num_total_neurons: layers.iter().map(|layer| layer.num_neurons).sum(),
+        layers,
+        activation_function,
+        num_total_parameters,
+        dataset,
+    };
+
+    activations::ingest_model_to_files(
+        data_path,
+        &model_metadata,
+        &tokens,
+        &layer_activations,
+        num_texts_per_neuron,
+    )
+    .with_context(|| {
+        format!(
+            "Failed to ingest activations of model '{}'.",
+            model_metadata.name
+        )
+    })?;
+    println!(
+        "Ingested {} neurons in {} layers.",
+        model_metadata.num_total_neurons,
+        model_metadata.layers.len()
+    );
+    Ok(())
+}
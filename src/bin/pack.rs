@@ -0,0 +1,63 @@
+use std::{env, fs};
+
+use anyhow::{bail, ensure, Context, Result};
+use neuronav::data::{
+    retrieve::neuroscope::neuron_data_path, ModelMetadata, NeuroscopeLayerArchive,
+    NeuroscopeNeuronPage,
+};
+
+const USAGE: &str = "Usage: pack <model_name> [--remove-pages]";
+
+/// Packs the neuroscope neuron pages of a model into one archive per layer.
+pub fn main() -> Result<()> {
+    let data_path = "data";
+    let mut model_name = None;
+    let mut remove_pages = false;
+    for arg in env::args().skip(1) {
+        if arg == "--remove-pages" {
+            remove_pages = true;
+        } else if model_name.is_none() {
+            model_name = Some(arg);
+        } else {
+            bail!("Unexpected argument '{arg}'.\n{USAGE}");
+        }
+    }
+    let model_name = model_name.context(USAGE)?;
+
+    let model_metadata = ModelMetadata::from_file(data_path, &model_name)?;
+    for (layer_index, layer_metadata) in model_metadata.layers.iter().enumerate() {
+        let layer_index = layer_index as u32;
+        let num_neurons = layer_metadata.num_neurons;
+        let num_packed = NeuroscopeLayerArchive::pack_page_files(
+            data_path,
+            &model_name,
+            layer_index,
+            num_neurons,
+        )
+        .with_context(|| format!("Failed to pack layer {layer_index} of model '{model_name}'."))?;
+        println!("Packed {num_packed}/{num_neurons} pages of layer {layer_index}.");
+
+        if remove_pages {
+            let archive = NeuroscopeLayerArchive::open(NeuroscopeLayerArchive::path(
+                data_path,
+                &model_name,
+                layer_index,
+            ))?;
+            for neuron_index in layer_metadata.neuron_indices(layer_index) {
+                let page_path = neuron_data_path(data_path, &model_name, neuron_index);
+                if page_path.exists() {
+                    // Only remove pages that can be read back from the archive unchanged.
+                    let page = NeuroscopeNeuronPage::from_file(&page_path)?;
+                    let packed_page = archive.page(neuron_index)?;
+                    ensure!(
+                        packed_page == page,
+                        "Packed page of neuron {neuron_index} differs from '{page_path:?}'."
+                    );
+                    fs::remove_file(&page_path)
+                        .with_context(|| format!("Failed to remove page file '{page_path:?}'."))?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
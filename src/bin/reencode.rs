@@ -0,0 +1,311 @@
+use std::{
+    collections::HashSet,
+    env, fs,
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+use anyhow::{bail, Context, Result};
+use neuronav::data::{
+    retrieve::neuroscope::neuron_data_path,
+    storage::{self, Codec, Dictionary, Encoding, StorageConfig},
+    ModelMetadata, NeuronIndex, NeuroscopeLayerArchive, NeuroscopeLayerPage, NeuroscopeModelPage,
+    NeuroscopeNeuronPage,
+};
+
+const USAGE: &str = "Usage: reencode <model_name> [--codec none|deflate|zstd] [--level LEVEL] [--dictionary] [--dictionary-size BYTES] [--samples COUNT]";
+
+const DEFAULT_DICTIONARY_SIZE: usize = 112 * 1024;
+const DEFAULT_NUM_SAMPLES: usize = 256;
+/// Every sample is decoded this many times to get stable timings.
+const DECODE_ROUNDS: u32 = 5;
+
+/// Benchmarks the available codecs on a sample of a model's neuroscope neuron pages and, if a
+/// codec is given, re-encodes all neuroscope pages of the model with it.
+pub fn main() -> Result<()> {
+    let data_path = Path::new("data");
+    let mut model_name = None;
+    let mut codec = None;
+    let mut level = None;
+    let mut use_dictionary = false;
+    let mut dictionary_size = DEFAULT_DICTIONARY_SIZE;
+    let mut num_samples = DEFAULT_NUM_SAMPLES;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--codec" => codec = Some(args.next().context(USAGE)?.parse::<Codec>()?),
+            "--level" => {
+                let value = args.next().context(USAGE)?;
+                level = Some(
+                    value
+                        .parse::<i32>()
+                        .with_context(|| format!("Level '{value}' not a valid integer."))?,
+                );
+            }
+            "--dictionary" => use_dictionary = true,
+            "--dictionary-size" => {
+                let value = args.next().context(USAGE)?;
+                dictionary_size = value
+                    .parse()
+                    .with_context(|| format!("Dictionary size '{value}' not a valid integer."))?;
+                use_dictionary = true;
+            }
+            "--samples" => {
+                let value = args.next().context(USAGE)?;
+                num_samples = value
+                    .parse()
+                    .with_context(|| format!("Sample count '{value}' not a valid integer."))?;
+            }
+            _ if model_name.is_none() => model_name = Some(arg),
+            _ => bail!("Unexpected argument '{arg}'.\n{USAGE}"),
+        }
+    }
+    let model_name = model_name.context(USAGE)?;
+    if use_dictionary && codec.is_some_and(|codec| codec != Codec::Zstd) {
+        bail!("Dictionaries are only supported by the zstd codec.");
+    }
+
+    let model_metadata = ModelMetadata::from_file(data_path, &model_name)?;
+    let neuroscope_path = data_path.join(&model_name).join("neuroscope");
+
+    let samples = sample_pages(data_path, &model_metadata, num_samples)?;
+    if samples.is_empty() {
+        bail!("Model '{model_name}' has no neuroscope neuron pages.");
+    }
+    let serialized_samples = samples
+        .iter()
+        .map(postcard::to_allocvec)
+        .collect::<Result<Vec<_>, _>>()
+        .context("Failed to serialize sample pages.")?;
+    // Training fails if there are too few samples, which only matters if a dictionary is wanted.
+    let dictionary = match Dictionary::train(&serialized_samples, dictionary_size) {
+        Ok(dictionary) => Some(dictionary),
+        Err(error) if use_dictionary => return Err(error),
+        Err(error) => {
+            println!("Skipping dictionary: {error:#}");
+            None
+        }
+    };
+
+    println!(
+        "Benchmarking on {} pages ({} bytes uncompressed).",
+        samples.len(),
+        serialized_samples.iter().map(Vec::len).sum::<usize>()
+    );
+    println!(
+        "{:<28} {:>12} {:>16}",
+        "encoding", "bytes", "decode µs/page"
+    );
+    // The benchmark decodes pages with the trained dictionary before it is part of the dataset,
+    // so it is stored in a scratch directory in the meantime.
+    let scratch_path = env::temp_dir().join(format!("reencode-{}", std::process::id()));
+    fs::create_dir_all(&scratch_path)
+        .with_context(|| format!("Failed to create directory '{scratch_path:?}'."))?;
+    if let Some(dictionary) = &dictionary {
+        dictionary.save(&scratch_path)?;
+    }
+    let benchmark_result = Codec::ALL
+        .into_iter()
+        .map(|codec| (codec.to_string(), Encoding::new(codec, level, None)))
+        .chain(dictionary.iter().map(|dictionary| {
+            (
+                format!("zstd + {} byte dictionary", dictionary.len()),
+                Encoding::new(Codec::Zstd, level, Some(dictionary)),
+            )
+        }))
+        .try_for_each(|(name, encoding)| {
+            // The level may not be valid for every codec.
+            let Ok(encoding) = encoding else {
+                return Ok(());
+            };
+            let (size, decode_micros) = benchmark(&samples, &encoding, &scratch_path)?;
+            println!("{name:<28} {size:>12} {decode_micros:>16.1}");
+            anyhow::Ok(())
+        });
+    // Failing to clean up the scratch directory does not affect the data.
+    let _ = fs::remove_dir_all(&scratch_path);
+    benchmark_result?;
+
+    let Some(codec) = codec else {
+        return Ok(());
+    };
+    let dictionary = dictionary.filter(|_| codec == Codec::Zstd && use_dictionary);
+    let encoding = Encoding::new(codec, level, dictionary.as_ref())?;
+    if let Some(dictionary) = &dictionary {
+        dictionary.save(&neuroscope_path)?;
+    }
+    reencode(data_path, &model_metadata, &encoding)?;
+    let storage_config = StorageConfig {
+        codec,
+        level,
+        dictionary_id: dictionary.as_ref().map(Dictionary::id),
+    };
+    storage_config.to_dataset(&neuroscope_path)?;
+    remove_unused_dictionaries(&neuroscope_path, storage_config.dictionary_id)?;
+    println!("Re-encoded neuroscope pages of model '{model_name}' with {codec}.");
+    Ok(())
+}
+
+/// Loads up to `num_samples` neuron pages spread evenly over the model.
+fn sample_pages(
+    data_path: &Path,
+    model_metadata: &ModelMetadata,
+    num_samples: usize,
+) -> Result<Vec<NeuroscopeNeuronPage>> {
+    let neuron_indices = model_metadata.neuron_indices().collect::<Vec<_>>();
+    let step = (neuron_indices.len() / num_samples.max(1)).max(1);
+    let mut samples = Vec::new();
+    for neuron_index in neuron_indices.into_iter().step_by(step).take(num_samples) {
+        if let Some(page) = load_neuron_page(data_path, &model_metadata.name, neuron_index)? {
+            samples.push(page);
+        }
+    }
+    Ok(samples)
+}
+
+fn load_neuron_page(
+    data_path: &Path,
+    model_name: &str,
+    neuron_index: NeuronIndex,
+) -> Result<Option<NeuroscopeNeuronPage>> {
+    let archive_path = NeuroscopeLayerArchive::path(data_path, model_name, neuron_index.layer);
+    if archive_path.exists() {
+        let archive = NeuroscopeLayerArchive::open(&archive_path)?;
+        if archive.record(neuron_index.neuron)?.is_none() {
+            return Ok(None);
+        }
+        return archive.page(neuron_index).map(Some);
+    }
+    let page_path = neuron_data_path(data_path, model_name, neuron_index);
+    if !page_path.exists() {
+        return Ok(None);
+    }
+    NeuroscopeNeuronPage::from_file(page_path).map(Some)
+}
+
+/// Returns the total encoded size of the pages and the mean time to decode one of them.
+fn benchmark(
+    pages: &[NeuroscopeNeuronPage],
+    encoding: &Encoding,
+    dataset_path: &Path,
+) -> Result<(usize, f64)> {
+    let encoded = pages
+        .iter()
+        .map(|page| page.to_bytes(encoding))
+        .collect::<Result<Vec<_>>>()?;
+    let size = encoded.iter().map(Vec::len).sum();
+
+    let start = Instant::now();
+    for _ in 0..DECODE_ROUNDS {
+        for bytes in &encoded {
+            NeuroscopeNeuronPage::from_bytes(bytes, dataset_path)?;
+        }
+    }
+    let decode_micros =
+        start.elapsed().as_secs_f64() * 1e6 / (DECODE_ROUNDS as f64 * encoded.len() as f64);
+    Ok((size, decode_micros))
+}
+
+fn reencode(data_path: &Path, model_metadata: &ModelMetadata, encoding: &Encoding) -> Result<()> {
+    let model_name = model_metadata.name.as_str();
+    let neuroscope_path = data_path.join(model_name).join("neuroscope");
+
+    for (layer_index, layer_metadata) in model_metadata.layers.iter().enumerate() {
+        let layer_index = layer_index as u32;
+        let archive_path = NeuroscopeLayerArchive::path(data_path, model_name, layer_index);
+        if archive_path.exists() {
+            let archive = NeuroscopeLayerArchive::open(&archive_path)?;
+            let pages = layer_metadata
+                .neuron_indices(layer_index)
+                .filter_map(|neuron_index| match archive.record(neuron_index.neuron) {
+                    Ok(Some(_)) => Some(archive.page(neuron_index)),
+                    Ok(None) => None,
+                    Err(error) => Some(Err(error)),
+                });
+            // The archive is replaced by renaming, so reading from the old mapping is fine.
+            NeuroscopeLayerArchive::write(
+                &archive_path,
+                layer_index,
+                archive.num_neurons(),
+                pages,
+                encoding,
+            )?;
+        } else {
+            for neuron_index in layer_metadata.neuron_indices(layer_index) {
+                let page_path = neuron_data_path(data_path, model_name, neuron_index);
+                if page_path.exists() {
+                    NeuroscopeNeuronPage::from_file(&page_path)?
+                        .to_file_with_encoding(&page_path, encoding)?;
+                }
+            }
+        }
+
+        let layer_page_path = neuroscope_path.join(format!("l{layer_index}.postcard"));
+        if layer_page_path.exists() {
+            NeuroscopeLayerPage::from_file(&layer_page_path)?
+                .to_file_with_encoding(&layer_page_path, encoding)?;
+        }
+        println!("Re-encoded layer {layer_index}.");
+    }
+
+    let model_page_path = neuroscope_path.join("model.postcard");
+    if model_page_path.exists() {
+        NeuroscopeModelPage::from_file(&model_page_path)?
+            .to_file_with_encoding(&model_page_path, encoding)?;
+    }
+    Ok(())
+}
+
+/// Removes dictionaries that no page or archive record of the dataset refers to. Pages that were
+/// not re-encoded, such as page files shadowed by an archive, may still refer to old dictionaries,
+/// so every file is scanned. If any of them cannot be read, all dictionaries are kept.
+fn remove_unused_dictionaries(neuroscope_path: &Path, dictionary_id: Option<u32>) -> Result<()> {
+    let paths = fs::read_dir(neuroscope_path)
+        .with_context(|| format!("Failed to read directory '{neuroscope_path:?}'."))?
+        .map(|entry| Ok(entry?.path()))
+        .collect::<Result<Vec<PathBuf>>>()?;
+    let (dictionaries, pages): (Vec<_>, Vec<_>) = paths
+        .into_iter()
+        .map(|path| {
+            let id = path
+                .file_name()
+                .and_then(|file_name| file_name.to_str())
+                .and_then(Dictionary::id_of_file_name);
+            (path, id)
+        })
+        .partition(|(_, id)| id.is_some());
+    let used_ids = match used_dictionary_ids(pages.into_iter().map(|(path, _)| path)) {
+        Ok(used_ids) => used_ids,
+        Err(error) => {
+            println!("Keeping old dictionaries: {error:#}");
+            return Ok(());
+        }
+    };
+    for (path, id) in dictionaries {
+        if id != dictionary_id && !id.is_some_and(|id| used_ids.contains(&id)) {
+            fs::remove_file(&path)
+                .with_context(|| format!("Failed to remove dictionary '{path:?}'."))?;
+        }
+    }
+    Ok(())
+}
+
+/// Returns the ids of the dictionaries that the pages and archive records in the files refer to.
+fn used_dictionary_ids(paths: impl Iterator<Item = PathBuf>) -> Result<HashSet<u32>> {
+    let mut used_ids = HashSet::new();
+    for path in paths {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("archive") => {
+                let archive = NeuroscopeLayerArchive::open(&path)?;
+                for neuron_index in 0..archive.num_neurons() {
+                    if let Some(record) = archive.record(neuron_index)? {
+                        used_ids.extend(storage::dictionary_id(record));
+                    }
+                }
+            }
+            Some("postcard") => used_ids.extend(storage::dictionary_id_of_file(&path)?),
+            _ => {}
+        }
+    }
+    Ok(used_ids)
+}
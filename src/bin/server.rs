@@ -1,8 +1,21 @@
-use anyhow::{Context, Result};
-use neuronav::server;
+use std::env;
+
+use anyhow::{bail, Context, Result};
+use neuronav::server::{self, Config};
+
+const USAGE: &str = "Usage: server [config.json]";
 
 pub fn main() -> Result<()> {
     env_logger::init();
 
-    server::start_server().context("Failed to start server.")
+    let mut args = env::args().skip(1);
+    let config = match args.next() {
+        Some(config_path) => Config::from_file(config_path)?,
+        None => Config::default(),
+    };
+    if let Some(arg) = args.next() {
+        bail!("Unexpected argument '{arg}'.\n{USAGE}");
+    }
+
+    server::start_server(config).context("Failed to start server.")
 }
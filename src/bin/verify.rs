@@ -0,0 +1,31 @@
+use std::{env, process::ExitCode};
+
+use anyhow::{Context, Result};
+use neuronav::data::verify::{self, DataReport};
+
+/// Verifies the data directory, or only the given models, and prints a JSON report of missing,
+/// corrupt and orphaned files. Exits with a failure code if any issues are found.
+pub fn main() -> Result<ExitCode> {
+    let data_path = "data";
+    let model_names: Vec<String> = env::args().skip(1).collect();
+    let report = if model_names.is_empty() {
+        verify::verify_data(data_path)?
+    } else {
+        DataReport {
+            models: model_names
+                .iter()
+                .map(|model_name| verify::verify_model(data_path, model_name))
+                .collect(),
+        }
+    };
+
+    let report_json =
+        serde_json::to_string_pretty(&report).context("Failed to serialize report.")?;
+    println!("{report_json}");
+
+    Ok(if report.is_ok() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    })
+}
@@ -0,0 +1,186 @@
+use anyhow::{bail, Context, Result};
+use itertools::Itertools;
+use serde::{de::DeserializeOwned, Deserialize};
+
+use crate::data::{
+    ExplanationLayerPage, ExplanationNeuronPage, LayerMetadata, ModelMetadata, Neuron2GraphPage,
+    NeuronIndex, NeuroscopeLayerPage, NeuroscopeModelPage, NeuroscopeNeuronPage, TokenSearch,
+};
+
+/// Pages of services other than metadata are wrapped together with the metadata of the page.
+#[derive(Deserialize)]
+struct ServiceResponse<T> {
+    data: T,
+}
+
+/// Typed client for the API of a neuronav server.
+///
+/// The typed page methods assume the services are registered under their default names. Services
+/// registered under other names can be requested with [`Client::model_page`],
+/// [`Client::layer_page`] and [`Client::neuron_page`].
+#[derive(Clone, Debug)]
+pub struct Client {
+    http: reqwest::Client,
+    base_url: String,
+}
+
+impl Client {
+    /// Creates a client for the server at `base_url`, e.g. `http://127.0.0.1:8080`.
+    pub fn new<S: AsRef<str>>(base_url: S) -> Self {
+        Self::with_http_client(reqwest::Client::new(), base_url)
+    }
+
+    pub fn with_http_client<S: AsRef<str>>(http: reqwest::Client, base_url: S) -> Self {
+        Self {
+            http,
+            base_url: base_url.as_ref().trim_end_matches('/').to_string(),
+        }
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    async fn get<T: DeserializeOwned>(&self, path: &str, query: &[(&str, &str)]) -> Result<T> {
+        let url = format!("{}/api/{path}", self.base_url);
+        let response = self
+            .http
+            .get(&url)
+            .query(query)
+            .send()
+            .await
+            .with_context(|| format!("Failed to send request to '{url}'."))?;
+        let status = response.status();
+        let text = response
+            .text()
+            .await
+            .with_context(|| format!("Failed to read response from '{url}'."))?;
+        if !status.is_success() {
+            bail!("Request to '{url}' failed with status {status}: {text}");
+        }
+        serde_json::from_str(&text)
+            .with_context(|| format!("Failed to parse response from '{url}'."))
+    }
+
+    async fn service_page<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        query: &[(&str, &str)],
+    ) -> Result<T> {
+        self.get::<ServiceResponse<T>>(path, query)
+            .await
+            .map(|response| response.data)
+    }
+
+    pub async fn model_metadata(&self, model_name: &str) -> Result<ModelMetadata> {
+        self.get(&format!("{model_name}/metadata"), &[]).await
+    }
+
+    pub async fn layer_metadata(
+        &self,
+        model_name: &str,
+        layer_index: u32,
+    ) -> Result<LayerMetadata> {
+        self.get(&format!("{model_name}/metadata/{layer_index}"), &[])
+            .await
+    }
+
+    /// Returns the model page of an arbitrary service.
+    pub async fn model_page<T: DeserializeOwned>(
+        &self,
+        model_name: &str,
+        service_name: &str,
+        query: &[(&str, &str)],
+    ) -> Result<T> {
+        self.service_page(&format!("{model_name}/{service_name}"), query)
+            .await
+    }
+
+    /// Returns the layer page of an arbitrary service.
+    pub async fn layer_page<T: DeserializeOwned>(
+        &self,
+        model_name: &str,
+        service_name: &str,
+        layer_index: u32,
+        query: &[(&str, &str)],
+    ) -> Result<T> {
+        self.service_page(&format!("{model_name}/{service_name}/{layer_index}"), query)
+            .await
+    }
+
+    /// Returns the neuron page of an arbitrary service.
+    pub async fn neuron_page<T: DeserializeOwned>(
+        &self,
+        model_name: &str,
+        service_name: &str,
+        neuron_index: NeuronIndex,
+        query: &[(&str, &str)],
+    ) -> Result<T> {
+        let NeuronIndex { layer, neuron } = neuron_index;
+        self.service_page(
+            &format!("{model_name}/{service_name}/{layer}/{neuron}"),
+            query,
+        )
+        .await
+    }
+
+    pub async fn neuroscope_model_page(&self, model_name: &str) -> Result<NeuroscopeModelPage> {
+        self.model_page(model_name, "neuroscope", &[]).await
+    }
+
+    pub async fn neuroscope_layer_page(
+        &self,
+        model_name: &str,
+        layer_index: u32,
+    ) -> Result<NeuroscopeLayerPage> {
+        self.layer_page(model_name, "neuroscope", layer_index, &[])
+            .await
+    }
+
+    pub async fn neuroscope_neuron_page(
+        &self,
+        model_name: &str,
+        neuron_index: NeuronIndex,
+    ) -> Result<NeuroscopeNeuronPage> {
+        self.neuron_page(model_name, "neuroscope", neuron_index, &[])
+            .await
+    }
+
+    pub async fn neuron2graph_neuron_page(
+        &self,
+        model_name: &str,
+        neuron_index: NeuronIndex,
+    ) -> Result<Neuron2GraphPage> {
+        self.neuron_page(model_name, "neuron2graph", neuron_index, &[])
+            .await
+    }
+
+    pub async fn explanation_layer_page(
+        &self,
+        model_name: &str,
+        layer_index: u32,
+    ) -> Result<ExplanationLayerPage> {
+        self.layer_page(model_name, "explanation", layer_index, &[])
+            .await
+    }
+
+    pub async fn explanation_neuron_page(
+        &self,
+        model_name: &str,
+        neuron_index: NeuronIndex,
+    ) -> Result<ExplanationNeuronPage> {
+        self.neuron_page(model_name, "explanation", neuron_index, &[])
+            .await
+    }
+
+    /// Returns the neurons matching all of the token searches, sorted by index.
+    pub async fn search(
+        &self,
+        model_name: &str,
+        token_searches: &[TokenSearch],
+    ) -> Result<Vec<NeuronIndex>> {
+        let query = token_searches.iter().join(",");
+        self.model_page(model_name, "neuron2graph-search", &[("query", &query)])
+            .await
+    }
+}
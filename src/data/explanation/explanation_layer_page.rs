@@ -0,0 +1,100 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::data::{
+    storage::{self, Encoding, PageKind, StoredPage, LEGACY_SCHEMA_VERSION},
+    NeuronIndex,
+};
+
+use super::{ExplanationNeuronPage, ScoredExplanation};
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct RankedNeuron {
+    pub neuron_index: NeuronIndex,
+    pub explanation: String,
+    pub score: Option<f32>,
+}
+
+/// The neurons of a layer ranked by the score of their best explanation.
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct ExplanationLayerPage {
+    num_neurons: u32,
+    /// Sorted from best to worst score, with unscored neurons last.
+    ranked_neurons: Vec<RankedNeuron>,
+}
+
+impl RankedNeuron {
+    /// Returns `None` if the page has no explanations.
+    pub fn from_page(page: &ExplanationNeuronPage) -> Option<Self> {
+        page.best_explanation().map(|explanation| Self {
+            neuron_index: page.neuron_index(),
+            explanation: explanation.explanation.clone(),
+            score: explanation.ev_correlation_score,
+        })
+    }
+}
+
+impl ExplanationLayerPage {
+    pub fn new(mut ranked_neurons: Vec<RankedNeuron>) -> Self {
+        ranked_neurons.sort_by(|a, b| ScoredExplanation::compare_scores(a.score, b.score));
+        Self {
+            num_neurons: ranked_neurons.len() as u32,
+            ranked_neurons,
+        }
+    }
+
+    pub fn path<P: AsRef<Path>, S: AsRef<str>>(
+        data_path: P,
+        model_name: S,
+        layer_index: u32,
+    ) -> PathBuf {
+        data_path
+            .as_ref()
+            .join(model_name.as_ref())
+            .join("explanation")
+            .join(format!("l{layer_index}"))
+            .with_extension("postcard")
+    }
+
+    pub fn num_neurons(&self) -> u32 {
+        self.num_neurons
+    }
+
+    pub fn ranked_neurons(&self) -> &[RankedNeuron] {
+        self.ranked_neurons.as_slice()
+    }
+
+    pub fn to_file<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        storage::to_file(self, path)
+    }
+
+    pub fn to_file_with_encoding<P: AsRef<Path>>(
+        &self,
+        path: P,
+        encoding: &Encoding,
+    ) -> Result<()> {
+        storage::to_file_with_encoding(self, path, encoding)
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        storage::from_file(path)
+    }
+}
+
+impl StoredPage for ExplanationLayerPage {
+    const KIND: PageKind = PageKind::ExplanationLayer;
+    const SCHEMA_VERSION: u16 = 1;
+
+    fn deserialize_payload(version: u16, payload: &[u8]) -> Result<Self> {
+        match version {
+            LEGACY_SCHEMA_VERSION => {
+                bail!("Explanation pages have always been written with a header.")
+            }
+            1 => Ok(postcard::from_bytes(payload)?),
+            _ => unreachable!("Unsupported versions are rejected before deserializing."),
+        }
+    }
+}
@@ -0,0 +1,149 @@
+use std::{
+    cmp::Ordering,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, ensure, Result};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::data::{
+    storage::{self, Encoding, PageKind, StoredPage, LEGACY_SCHEMA_VERSION},
+    NeuronIndex,
+};
+
+/// An activation record that the explanation was scored on, with the activations the simulator
+/// expected from the explanation next to the true activations.
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct ScoredActivationRecord {
+    pub tokens: Vec<String>,
+    pub true_activations: Vec<f32>,
+    pub expected_activations: Vec<f32>,
+    pub ev_correlation_score: Option<f32>,
+}
+
+impl ScoredActivationRecord {
+    pub fn new(
+        tokens: Vec<String>,
+        true_activations: Vec<f32>,
+        expected_activations: Vec<f32>,
+        ev_correlation_score: Option<f32>,
+    ) -> Result<Self> {
+        ensure!(
+            tokens.len() == true_activations.len() && tokens.len() == expected_activations.len(),
+            "Activation record has {} tokens, but {} true and {} expected activations.",
+            tokens.len(),
+            true_activations.len(),
+            expected_activations.len()
+        );
+        Ok(Self {
+            tokens,
+            true_activations,
+            expected_activations,
+            ev_correlation_score,
+        })
+    }
+}
+
+/// An explanation of a neuron with the scores the simulator achieved with it. Scores are missing
+/// if they could not be computed, e.g. for neurons that never activate.
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct ScoredExplanation {
+    pub explanation: String,
+    /// Correlation between simulated and true activations, which explanations are ranked by.
+    pub ev_correlation_score: Option<f32>,
+    pub rsquared_score: Option<f32>,
+    pub absolute_dev_explained_score: Option<f32>,
+    pub records: Vec<ScoredActivationRecord>,
+}
+
+impl ScoredExplanation {
+    /// Orders explanations from best to worst score, with unscored explanations last.
+    pub(crate) fn compare_scores(a: Option<f32>, b: Option<f32>) -> Ordering {
+        match (a, b) {
+            (Some(a), Some(b)) => b.total_cmp(&a),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct ExplanationNeuronPage {
+    neuron_index: NeuronIndex,
+    /// Sorted from best to worst score.
+    explanations: Vec<ScoredExplanation>,
+}
+
+impl ExplanationNeuronPage {
+    pub fn new(neuron_index: NeuronIndex, mut explanations: Vec<ScoredExplanation>) -> Self {
+        explanations.sort_by(|a, b| {
+            ScoredExplanation::compare_scores(a.ev_correlation_score, b.ev_correlation_score)
+        });
+        Self {
+            neuron_index,
+            explanations,
+        }
+    }
+
+    pub fn path<P: AsRef<Path>, S: AsRef<str>>(
+        data_path: P,
+        model_name: S,
+        neuron_index: NeuronIndex,
+    ) -> PathBuf {
+        let NeuronIndex {
+            layer: layer_index,
+            neuron: neuron_index,
+        } = neuron_index;
+        data_path
+            .as_ref()
+            .join(model_name.as_ref())
+            .join("explanation")
+            .join(format!("l{layer_index}n{neuron_index}"))
+            .with_extension("postcard")
+    }
+
+    pub fn neuron_index(&self) -> NeuronIndex {
+        self.neuron_index
+    }
+
+    pub fn explanations(&self) -> &[ScoredExplanation] {
+        self.explanations.as_slice()
+    }
+
+    pub fn best_explanation(&self) -> Option<&ScoredExplanation> {
+        self.explanations.first()
+    }
+
+    pub fn to_file<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        storage::to_file(self, path)
+    }
+
+    pub fn to_file_with_encoding<P: AsRef<Path>>(
+        &self,
+        path: P,
+        encoding: &Encoding,
+    ) -> Result<()> {
+        storage::to_file_with_encoding(self, path, encoding)
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        storage::from_file(path)
+    }
+}
+
+impl StoredPage for ExplanationNeuronPage {
+    const KIND: PageKind = PageKind::ExplanationNeuron;
+    const SCHEMA_VERSION: u16 = 1;
+
+    fn deserialize_payload(version: u16, payload: &[u8]) -> Result<Self> {
+        match version {
+            LEGACY_SCHEMA_VERSION => {
+                bail!("Explanation pages have always been written with a header.")
+            }
+            1 => Ok(postcard::from_bytes(payload)?),
+            _ => unreachable!("Unsupported versions are rejected before deserializing."),
+        }
+    }
+}
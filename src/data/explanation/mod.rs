@@ -0,0 +1,4 @@
+mod explanation_page;
+pub use explanation_page::{ExplanationNeuronPage, ScoredActivationRecord, ScoredExplanation};
+mod explanation_layer_page;
+pub use explanation_layer_page::{ExplanationLayerPage, RankedNeuron};
@@ -4,10 +4,13 @@ use std::{
 };
 
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 
 use anyhow::{Context, Result};
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+use super::NeuronIndex;
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
 pub struct ModelMetadata {
     pub name: String,
     pub layers: Vec<LayerMetadata>,
@@ -31,9 +34,37 @@ impl ModelMetadata {
         serde_json::to_writer(model_metadata_file, self)?;
         Ok(())
     }
+
+    pub fn from_file<P: AsRef<Path>, S: AsRef<str>>(data_path: P, model_name: S) -> Result<Self> {
+        let model_name = model_name.as_ref();
+        let model_metadata_path = data_path.as_ref().join(model_name).join("metadata.json");
+        let text = fs::read_to_string(&model_metadata_path).with_context(|| {
+            format!(
+                "Failed to read metadata for model '{model_name}' from '{model_metadata_path:?}'."
+            )
+        })?;
+        serde_json::from_str(&text)
+            .with_context(|| format!("Failed to parse metadata for model '{model_name}'."))
+    }
+
+    pub fn neuron_indices(&self) -> impl Iterator<Item = NeuronIndex> + '_ {
+        self.layers
+            .iter()
+            .enumerate()
+            .flat_map(|(layer_index, layer)| layer.neuron_indices(layer_index as u32))
+    }
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
 pub struct LayerMetadata {
     pub num_neurons: u32,
 }
+
+impl LayerMetadata {
+    pub fn neuron_indices(&self, layer_index: u32) -> impl Iterator<Item = NeuronIndex> {
+        (0..self.num_neurons).map(move |neuron_index| NeuronIndex {
+            layer: layer_index,
+            neuron: neuron_index,
+        })
+    }
+}
@@ -1,13 +1,24 @@
+mod explanation;
+pub use explanation::{
+    ExplanationLayerPage, ExplanationNeuronPage, RankedNeuron, ScoredActivationRecord,
+    ScoredExplanation,
+};
 mod neuron_index;
 pub use neuron_index::NeuronIndex;
 mod neuron_viewer_object;
 pub use neuron_viewer_object::NeuronViewerObject;
 mod neuroscope;
-pub use neuroscope::{NeuroscopeLayerPage, NeuroscopeModelPage, NeuroscopeNeuronPage};
+pub use neuroscope::{
+    NeuroscopeLayerArchive, NeuroscopeLayerPage, NeuroscopeModelPage, NeuroscopeNeuronPage, Text,
+};
+mod neuron2graph_page;
+pub use neuron2graph_page::{Neuron2GraphPage, SimilarNeuron};
 mod neuron_store;
-pub use neuron_store::{NeuronStore, TokenSearch, TokenSearchType};
+pub use neuron_store::{NeuronStore, NeuronStoreRaw, TokenSearch, TokenSearchType};
 mod metadata;
 pub mod retrieve;
+pub mod storage;
+pub mod verify;
 pub use metadata::{LayerMetadata, ModelMetadata};
 
 mod payload;
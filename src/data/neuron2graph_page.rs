@@ -0,0 +1,48 @@
+use std::{fs, path::Path};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use super::NeuronIndex;
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, ToSchema)]
+pub struct SimilarNeuron {
+    pub layer: u32,
+    pub neuron: u32,
+    pub similarity: f32,
+}
+
+impl SimilarNeuron {
+    pub fn new(neuron_index: NeuronIndex, similarity: f32) -> Self {
+        Self {
+            layer: neuron_index.layer,
+            neuron: neuron_index.neuron,
+            similarity,
+        }
+    }
+
+    pub fn neuron_index(&self) -> NeuronIndex {
+        NeuronIndex {
+            layer: self.layer,
+            neuron: self.neuron,
+        }
+    }
+}
+
+/// A neuron2graph graph in dot format, with the neurons whose graphs share the most tokens with it.
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct Neuron2GraphPage {
+    pub graph: String,
+    /// Sorted from most to least similar.
+    pub similar: Vec<SimilarNeuron>,
+}
+
+impl Neuron2GraphPage {
+    /// Reads the graph of a neuron. Graphs are sent to clients as they are stored.
+    pub fn read_graph<P: AsRef<Path>>(path: P) -> Result<String> {
+        let path = path.as_ref();
+        fs::read_to_string(path)
+            .with_context(|| format!("Failed to read graph '{path:?}' as UTF-8 text."))
+    }
+}
@@ -1,12 +1,16 @@
 use std::cmp::Ordering;
-use std::collections::{HashMap, HashSet};
-use std::fs;
-use std::path::Path;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::mem;
+use std::ops::{Deref, Range};
+use std::path::{Path, PathBuf};
 use std::{fmt::Display, str::FromStr};
 
-use anyhow::{bail, Context, Result};
+use anyhow::{bail, ensure, Context, Result};
 use itertools::Itertools;
-use ndarray::{s, Array2};
+use memmap2::Mmap;
+use ndarray::Array2;
 use serde::Deserialize;
 use serde::Serialize;
 
@@ -64,6 +68,16 @@ impl FromStr for TokenSearch {
     }
 }
 
+impl Display for TokenSearch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let search_type_str = match self.search_types.as_slice() {
+            [search_type] => search_type.to_str(),
+            _ => "any",
+        };
+        write!(f, "{search_type_str}:{}", self.token)
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NeuronStoreRaw {
     activating: HashMap<String, HashSet<String>>,
@@ -71,11 +85,16 @@ pub struct NeuronStoreRaw {
 }
 
 impl NeuronStoreRaw {
-    pub fn load(model: &str) -> Result<Self> {
-        let neuron_store_path = Path::new("data")
+    pub fn path<P: AsRef<Path>>(data_path: P, model: &str) -> PathBuf {
+        data_path
+            .as_ref()
             .join(model)
             .join("neuron2graph-search")
-            .join("neuron_store.json");
+            .join("neuron_store.json")
+    }
+
+    pub fn load(model: &str) -> Result<Self> {
+        let neuron_store_path = Self::path("data", model);
         let neuron_store_path = neuron_store_path.as_path();
         let neuron_store_string = fs::read_to_string(neuron_store_path)
             .with_context(|| format!("Could not find neuron store file for model '{model}'."))?;
@@ -83,109 +102,542 @@ impl NeuronStoreRaw {
         serde_json::from_str(&neuron_store_string)
             .with_context(|| format!("Failed to parse neuron store for model '{model}'."))
     }
+
+    /// Parses the neuron indices of a token index.
+    fn token_index(
+        token_index: HashMap<String, HashSet<String>>,
+    ) -> Result<BTreeMap<String, Vec<NeuronIndex>>> {
+        token_index
+            .into_iter()
+            .map(|(token, neuron_indices)| {
+                let mut neuron_indices = neuron_indices
+                    .iter()
+                    .map(String::as_str)
+                    .map(NeuronIndex::from_str)
+                    .collect::<Result<Vec<_>>>()?;
+                neuron_indices.sort_unstable();
+                Ok((token, neuron_indices))
+            })
+            .collect()
+    }
+
+    /// Compiles the store into the format of [`NeuronStore`]. The co-occurrence counts of the
+    /// neurons are computed here, so they need not be computed when the store is opened.
+    pub fn compile(self, layer_size: u32, num_layers: u32) -> Result<Vec<u8>> {
+        let activating = Self::token_index(self.activating)?;
+        let important = Self::token_index(self.important)?;
+        let num_neurons = layer_size
+            .checked_mul(num_layers)
+            .context("Neuron store has too many neurons.")? as usize;
+
+        let mut rows: Vec<HashMap<u32, u32>> = vec![HashMap::new(); num_neurons];
+        for neuron_indices in activating.values().chain(important.values()) {
+            for &neuron_index in neuron_indices {
+                ensure!(
+                    neuron_index.layer < num_layers && neuron_index.neuron < layer_size,
+                    "Neuron {neuron_index} does not fit in a store of {num_layers} layers of {layer_size} neurons."
+                );
+                let row = &mut rows[neuron_index.flat_index(layer_size)];
+                for &other_neuron_index in neuron_indices {
+                    *row.entry(other_neuron_index.flat_index(layer_size) as u32)
+                        .or_default() += 1;
+                }
+            }
+        }
+
+        // Tokens of either index, in the byte order used to look them up.
+        let tokens: Vec<&str> = activating
+            .keys()
+            .merge(important.keys())
+            .dedup()
+            .map(String::as_str)
+            .collect();
+        let num_postings = activating
+            .values()
+            .chain(important.values())
+            .map(Vec::len)
+            .sum();
+        let num_entries = rows.iter().map(HashMap::len).sum();
+        let layout = Layout::new(Header {
+            layer_size,
+            num_layers,
+            num_tokens: tokens.len(),
+            num_token_bytes: tokens.iter().map(|token| token.len()).sum(),
+            num_postings,
+            num_entries,
+        })?;
+
+        let mut bytes = Vec::with_capacity(layout.end);
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&VERSION.to_le_bytes());
+        bytes.extend_from_slice(&layer_size.to_le_bytes());
+        bytes.extend_from_slice(&num_layers.to_le_bytes());
+        for count in [
+            tokens.len(),
+            layout.header.num_token_bytes,
+            num_postings,
+            num_entries,
+        ] {
+            bytes.extend_from_slice(&(count as u64).to_le_bytes());
+        }
+
+        let mut extend_offsets = |lengths: &mut dyn Iterator<Item = usize>| {
+            let mut offset = 0u64;
+            bytes.extend_from_slice(&offset.to_le_bytes());
+            for length in lengths {
+                offset += length as u64;
+                bytes.extend_from_slice(&offset.to_le_bytes());
+            }
+        };
+        let postings_length = |token_index: &BTreeMap<String, Vec<NeuronIndex>>, token: &str| {
+            token_index.get(token).map_or(0, Vec::len)
+        };
+        extend_offsets(&mut tokens.iter().map(|token| token.len()));
+        extend_offsets(
+            &mut tokens
+                .iter()
+                .map(|token| postings_length(&activating, token)),
+        );
+        extend_offsets(
+            &mut tokens
+                .iter()
+                .map(|token| postings_length(&important, token)),
+        );
+        extend_offsets(&mut rows.iter().map(HashMap::len));
+
+        for (flat_index, row) in rows.iter().enumerate() {
+            let self_count = row.get(&(flat_index as u32)).copied().unwrap_or(0);
+            bytes.extend_from_slice(&self_count.to_le_bytes());
+        }
+        for token_index in [&activating, &important] {
+            for token in &tokens {
+                for neuron_index in token_index.get(*token).into_iter().flatten() {
+                    let flat_index = neuron_index.flat_index(layer_size) as u32;
+                    bytes.extend_from_slice(&flat_index.to_le_bytes());
+                }
+            }
+        }
+        for row in &rows {
+            for (column, count) in row.iter().sorted_unstable() {
+                bytes.extend_from_slice(&column.to_le_bytes());
+                bytes.extend_from_slice(&count.to_le_bytes());
+            }
+        }
+        for token in &tokens {
+            bytes.extend_from_slice(token.as_bytes());
+        }
+        assert_eq!(
+            bytes.len(),
+            layout.end,
+            "Compiled neuron store has wrong size."
+        );
+        Ok(bytes)
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct NeuronStore {
+const MAGIC: &[u8; 4] = b"NSTO";
+const VERSION: u32 = 1;
+/// Magic, version, layer size, number of layers, and the numbers of tokens, token bytes, postings
+/// and co-occurrence entries.
+const HEADER_SIZE: usize = 48;
+/// Column and count of a co-occurrence entry.
+const ENTRY_SIZE: usize = 8;
+
+struct Header {
     layer_size: u32,
     num_layers: u32,
-    activating: HashMap<String, HashSet<NeuronIndex>>,
-    important: HashMap<String, HashSet<NeuronIndex>>,
-    related_neurons: Array2<u32>,
+    num_tokens: usize,
+    num_token_bytes: usize,
+    num_postings: usize,
+    num_entries: usize,
+}
+
+/// Byte offsets of the sections of a compiled neuron store.
+struct Layout {
+    header: Header,
+    token_offsets: usize,
+    activating_offsets: usize,
+    important_offsets: usize,
+    row_offsets: usize,
+    self_counts: usize,
+    postings: usize,
+    entries: usize,
+    token_bytes: usize,
+    end: usize,
+}
+
+impl Layout {
+    /// Computes the offsets of the sections, failing if they do not fit in the address space.
+    fn new(header: Header) -> Result<Self> {
+        Self::offsets(header).context("Neuron store sections do not fit in memory.")
+    }
+
+    fn offsets(header: Header) -> Option<Self> {
+        // Flat neuron indices are stored as `u32`.
+        let num_neurons = header.layer_size.checked_mul(header.num_layers)? as usize;
+        let offset_table_size = |num_ranges: usize| num_ranges.checked_add(1)?.checked_mul(8);
+        let token_offsets = HEADER_SIZE;
+        let activating_offsets =
+            token_offsets.checked_add(offset_table_size(header.num_tokens)?)?;
+        let important_offsets =
+            activating_offsets.checked_add(offset_table_size(header.num_tokens)?)?;
+        let row_offsets = important_offsets.checked_add(offset_table_size(header.num_tokens)?)?;
+        let self_counts = row_offsets.checked_add(offset_table_size(num_neurons)?)?;
+        let postings = self_counts.checked_add(num_neurons.checked_mul(4)?)?;
+        let entries = postings.checked_add(header.num_postings.checked_mul(4)?)?;
+        let token_bytes = entries.checked_add(header.num_entries.checked_mul(ENTRY_SIZE)?)?;
+        let end = token_bytes.checked_add(header.num_token_bytes)?;
+        Some(Self {
+            header,
+            token_offsets,
+            activating_offsets,
+            important_offsets,
+            row_offsets,
+            self_counts,
+            postings,
+            entries,
+            token_bytes,
+            end,
+        })
+    }
+}
+
+enum Bytes {
+    Mapped(Mmap),
+    Owned(Vec<u8>),
+}
+
+impl Deref for Bytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            Bytes::Mapped(mmap) => mmap,
+            Bytes::Owned(bytes) => bytes,
+        }
+    }
+}
+
+/// Index of the tokens that neurons activate on or are important for, together with how often
+/// neurons share tokens.
+///
+/// The store is compiled from the JSON file produced by neuron2graph into a binary file with a
+/// header, a sorted token table, the posting lists of the tokens and the sparse co-occurrence
+/// counts of the neurons. The binary file is memory mapped, so opening it takes no time. Stores
+/// without a binary file are compiled in memory from the JSON file when loaded.
+pub struct NeuronStore {
+    bytes: Bytes,
+    layout: Layout,
 }
 
 impl NeuronStore {
+    pub fn path<P: AsRef<Path>>(data_path: P, model: &str) -> PathBuf {
+        NeuronStoreRaw::path(data_path, model).with_extension("bin")
+    }
+
+    /// The file the store of the model is loaded from: the compiled store if there is one that is
+    /// at least as new as the JSON store, and the JSON store otherwise.
+    pub fn source_path<P: AsRef<Path>>(data_path: P, model: &str) -> PathBuf {
+        let path = Self::path(&data_path, model);
+        let json_path = NeuronStoreRaw::path(data_path, model);
+        if path.exists() && !is_newer(&json_path, &path) {
+            path
+        } else {
+            json_path
+        }
+    }
+
+    /// Opens the compiled store of the model, or compiles it from JSON if there is none or the
+    /// JSON store changed since it was compiled.
     pub fn load(model: &str) -> Result<Self> {
-        let NeuronStoreRaw {
-            activating,
-            important,
-        } = NeuronStoreRaw::load(model)?;
-        let activating = activating
-            .into_iter()
-            .map(|(key, value)| {
-                Ok((
-                    key,
-                    value
-                        .iter()
-                        .map(String::as_str)
-                        .map(NeuronIndex::from_str)
-                        .collect::<Result<HashSet<_>>>()?,
-                ))
-            })
-            .collect::<Result<HashMap<_, _>>>()?;
-        let important = important
-            .into_iter()
-            .map(|(key, value)| {
-                Ok((
-                    key,
-                    value
-                        .iter()
-                        .map(String::as_str)
-                        .map(NeuronIndex::from_str)
-                        .collect::<Result<HashSet<_>>>()?,
-                ))
-            })
-            .collect::<Result<HashMap<_, _>>>()?;
+        let path = Self::path("data", model);
+        if path.exists() {
+            if !is_newer(NeuronStoreRaw::path("data", model), &path) {
+                return Self::open(&path);
+            }
+            log::warn!(
+                "Compiled neuron store '{path:?}' is older than the JSON store of model \
+                 '{model}', compiling from JSON. Run compile_store to update it."
+            );
+        } else {
+            log::info!("No compiled neuron store for model '{model}', compiling from JSON");
+        }
+        let bytes = Self::compile(model)?;
+        Self::from_bytes(Bytes::Owned(bytes))
+            .with_context(|| format!("Failed to compile neuron store for model '{model}'."))
+    }
+
+    /// Compiles the JSON store of the model.
+    pub fn compile(model: &str) -> Result<Vec<u8>> {
         let layer_size = 3072;
         let num_layers = 6;
-        let num_neurons = (layer_size * num_layers) as usize;
-        let mut related_neurons: Array2<u32> = Array2::zeros((num_neurons, num_neurons));
-        for (_token, neuron_indices) in activating.iter().chain(important.iter()) {
-            for &neuron_index in neuron_indices {
-                let index1 = neuron_index.flat_index(layer_size);
-                for &other_neuron_index in neuron_indices {
-                    let index2 = other_neuron_index.flat_index(layer_size);
-                    related_neurons[[index1, index2]] += 1;
-                }
+        NeuronStoreRaw::load(model)?.compile(layer_size, num_layers)
+    }
+
+    /// Compiles the JSON store of the model and writes it next to the JSON file.
+    pub fn write(model: &str) -> Result<PathBuf> {
+        let bytes = Self::compile(model)?;
+        // Check the store before replacing one that may be mapped by a running server. Opening it
+        // only checks the offset tables, so check the neuron indices here once.
+        Self::from_bytes(Bytes::Owned(bytes.clone()))?.check_neurons()?;
+        let path = Self::path("data", model);
+        let temporary_path = path.with_extension("bin.tmp");
+        File::create(&temporary_path)
+            .and_then(|file| {
+                let mut writer = BufWriter::new(file);
+                writer.write_all(&bytes)?;
+                writer.flush()
+            })
+            .with_context(|| format!("Failed to write file '{temporary_path:?}'."))?;
+        fs::rename(&temporary_path, &path)
+            .with_context(|| format!("Failed to move '{temporary_path:?}' to '{path:?}'."))?;
+        Ok(path)
+    }
+
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let file = File::open(path).with_context(|| format!("Failed to open file '{path:?}'."))?;
+        // SAFETY: Compiled stores are written once and replaced rather than modified, so the
+        // mapped memory does not change under us.
+        let mmap = unsafe { Mmap::map(&file) }
+            .with_context(|| format!("Failed to memory map file '{path:?}'."))?;
+        Self::from_bytes(Bytes::Mapped(mmap))
+            .with_context(|| format!("Failed to open neuron store '{path:?}'."))
+    }
+
+    fn from_bytes(bytes: Bytes) -> Result<Self> {
+        ensure!(
+            bytes.len() >= HEADER_SIZE && &bytes[..4] == MAGIC,
+            "Not a compiled neuron store."
+        );
+        let version = read_u32(&bytes, 4);
+        if version != VERSION {
+            bail!("Compiled neuron store has unsupported version {version}.");
+        }
+        let layout = Layout::new(Header {
+            layer_size: read_u32(&bytes, 8),
+            num_layers: read_u32(&bytes, 12),
+            num_tokens: read_u64(&bytes, 16) as usize,
+            num_token_bytes: read_u64(&bytes, 24) as usize,
+            num_postings: read_u64(&bytes, 32) as usize,
+            num_entries: read_u64(&bytes, 40) as usize,
+        })?;
+        ensure!(
+            bytes.len() == layout.end,
+            "Compiled neuron store should be {} bytes, but is {}.",
+            layout.end,
+            bytes.len()
+        );
+        let neuron_store = Self { bytes, layout };
+        neuron_store.check_offset_tables()?;
+        Ok(neuron_store)
+    }
+
+    /// Checks that the ranges of the offset tables lie within their sections, so lookups cannot
+    /// read out of bounds. This reads the offset tables but not the sections they index.
+    fn check_offset_tables(&self) -> Result<()> {
+        let Layout { header, .. } = &self.layout;
+        let num_activating_postings = self.check_offsets(
+            "activating token",
+            self.layout.activating_offsets,
+            header.num_tokens,
+            header.num_postings,
+        )?;
+        // Important postings follow all activating postings.
+        let offset_tables = [
+            (
+                "important token",
+                self.layout.important_offsets,
+                header.num_tokens,
+                header.num_postings - num_activating_postings,
+            ),
+            (
+                "token",
+                self.layout.token_offsets,
+                header.num_tokens,
+                header.num_token_bytes,
+            ),
+            (
+                "row",
+                self.layout.row_offsets,
+                self.num_neurons(),
+                header.num_entries,
+            ),
+        ];
+        for (name, offsets, num_ranges, section_size) in offset_tables {
+            let end = self.check_offsets(name, offsets, num_ranges, section_size)?;
+            ensure!(
+                end == section_size,
+                "Compiled neuron store is corrupted: {name} offsets end at {end}, but should end \
+                 at {section_size}."
+            );
+        }
+        Ok(())
+    }
+
+    /// Checks that the postings and co-occurrence entries only hold neurons of the store. This
+    /// reads the whole store, so it is done when compiling rather than when opening it. Lookups
+    /// skip neurons that are out of range.
+    fn check_neurons(&self) -> Result<()> {
+        let Layout { header, .. } = &self.layout;
+        let num_neurons = self.num_neurons();
+        let postings = (0..header.num_postings).map(|index| self.layout.postings + index * 4);
+        let columns = (0..header.num_entries).map(|index| self.layout.entries + index * ENTRY_SIZE);
+        for offset in postings.chain(columns) {
+            let flat_index = read_u32(&self.bytes, offset) as usize;
+            ensure!(
+                flat_index < num_neurons,
+                "Compiled neuron store is corrupted: neuron {flat_index} is out of range."
+            );
+        }
+        Ok(())
+    }
+
+    /// Checks that the offsets never decrease and stay within `limit`. Returns the last offset.
+    fn check_offsets(
+        &self,
+        name: &str,
+        offsets: usize,
+        num_ranges: usize,
+        limit: usize,
+    ) -> Result<usize> {
+        let mut previous = 0;
+        for index in 0..=num_ranges {
+            let offset = read_u64(&self.bytes, offsets + index * 8);
+            ensure!(
+                offset >= previous && offset <= limit as u64,
+                "Compiled neuron store is corrupted: {name} offset {index} is {offset}, which is \
+                 below the previous offset {previous} or beyond {limit}."
+            );
+            previous = offset;
+        }
+        Ok(previous as usize)
+    }
+
+    pub fn layer_size(&self) -> u32 {
+        self.layout.header.layer_size
+    }
+
+    pub fn num_layers(&self) -> u32 {
+        self.layout.header.num_layers
+    }
+
+    fn num_neurons(&self) -> usize {
+        self.layer_size() as usize * self.num_layers() as usize
+    }
+
+    /// Approximate number of bytes the store holds on the heap, which includes its token index
+    /// and co-occurrence counts if it was compiled from JSON when loaded.
+    pub fn heap_size(&self) -> usize {
+        mem::size_of::<Self>()
+            + match &self.bytes {
+                Bytes::Mapped(_) => 0,
+                Bytes::Owned(bytes) => bytes.len(),
             }
+    }
+
+    /// Number of bytes of the compiled store file that are memory mapped. The operating system
+    /// pages them in and out as needed.
+    pub fn mapped_size(&self) -> usize {
+        match &self.bytes {
+            Bytes::Mapped(mmap) => mmap.len(),
+            Bytes::Owned(_) => 0,
         }
+    }
 
-        Ok(Self {
-            layer_size,
-            num_layers,
-            activating,
-            important,
-            related_neurons,
-        })
+    pub fn contains(&self, neuron_index: NeuronIndex) -> bool {
+        neuron_index.layer < self.num_layers() && neuron_index.neuron < self.layer_size()
+    }
+
+    /// The range between the `index`th and next offset of an offset table.
+    fn range(&self, offsets: usize, index: usize) -> Range<usize> {
+        let start = read_u64(&self.bytes, offsets + index * 8) as usize;
+        let end = read_u64(&self.bytes, offsets + (index + 1) * 8) as usize;
+        start..end
+    }
+
+    fn token(&self, token_index: usize) -> &[u8] {
+        let range = self.range(self.layout.token_offsets, token_index);
+        &self.bytes[self.layout.token_bytes + range.start..self.layout.token_bytes + range.end]
+    }
+
+    /// The flat neuron index at `offset`, or `None` if it is not in the store.
+    fn flat_index(&self, offset: usize) -> Option<usize> {
+        let flat_index = read_u32(&self.bytes, offset) as usize;
+        (flat_index < self.num_neurons()).then_some(flat_index)
+    }
+
+    fn self_count(&self, flat_index: usize) -> u32 {
+        read_u32(&self.bytes, self.layout.self_counts + flat_index * 4)
+    }
+
+    /// The neurons sharing tokens with the neuron and how many tokens they share, by flat index.
+    fn row(&self, flat_index: usize) -> impl Iterator<Item = (usize, u32)> + '_ {
+        self.range(self.layout.row_offsets, flat_index)
+            .filter_map(move |entry_index| {
+                let offset = self.layout.entries + entry_index * ENTRY_SIZE;
+                Some((self.flat_index(offset)?, read_u32(&self.bytes, offset + 4)))
+            })
+    }
+
+    fn common_count(&self, flat_index1: usize, flat_index2: usize) -> u32 {
+        let entries = self.range(self.layout.row_offsets, flat_index1);
+        let column = |entry_index: usize| {
+            read_u32(&self.bytes, self.layout.entries + entry_index * ENTRY_SIZE) as usize
+        };
+        let (mut low, mut high) = (entries.start, entries.end);
+        while low < high {
+            let middle = low + (high - low) / 2;
+            match column(middle).cmp(&flat_index2) {
+                Ordering::Less => low = middle + 1,
+                Ordering::Greater => high = middle,
+                Ordering::Equal => {
+                    return read_u32(&self.bytes, self.layout.entries + middle * ENTRY_SIZE + 4)
+                }
+            }
+        }
+        0
     }
 
     pub fn similarity(&self, neuron_index1: NeuronIndex, neuron_index2: NeuronIndex) -> f32 {
-        let index1 = neuron_index1.flat_index(self.layer_size);
-        let self_count1 = self.related_neurons[[index1, index1]];
-        let index2 = neuron_index2.flat_index(self.layer_size);
-        let self_count2 = self.related_neurons[[index2, index2]];
-        (self.related_neurons[[index1, index2]] as f32)
-            / (self_count1.max(self_count2).max(1) as f32)
+        let index1 = neuron_index1.flat_index(self.layer_size());
+        let index2 = neuron_index2.flat_index(self.layer_size());
+        let self_count1 = self.self_count(index1);
+        let self_count2 = self.self_count(index2);
+        (self.common_count(index1, index2) as f32) / (self_count1.max(self_count2).max(1) as f32)
     }
 
     pub fn similarity_matrix(&self) -> Array2<f32> {
-        let num_neurons = (self.layer_size * self.num_layers) as usize;
+        let num_neurons = self.num_neurons();
         let mut matrix = Array2::zeros((num_neurons, num_neurons));
-        for i in 0..(self.layer_size * 6) as usize {
-            let self_count1 = self.related_neurons[[i, i]];
-            for j in 0..(self.layer_size * 6) as usize {
-                let self_count2 = self.related_neurons[[j, j]];
-                matrix[[i, j]] = (self.related_neurons[[i, j]] as f32)
-                    / (self_count1.max(self_count2).max(1) as f32);
+        for i in 0..num_neurons {
+            let self_count1 = self.self_count(i);
+            for (j, common_count) in self.row(i) {
+                let self_count2 = self.self_count(j);
+                matrix[[i, j]] =
+                    (common_count as f32) / (self_count1.max(self_count2).max(1) as f32);
             }
         }
         matrix
     }
 
+    /// Returns the neurons sharing tokens with the neuron with a similarity of at least
+    /// `threshold`, from most to least similar.
     pub fn similar_neurons(
         &self,
         neuron_index: NeuronIndex,
         threshold: f32,
     ) -> Result<Vec<(NeuronIndex, f32)>> {
-        let index = neuron_index.flat_index(self.layer_size);
-        let related_neurons = self.related_neurons.slice(s![index, ..]);
-        let self_count1 = *self.related_neurons.get([index, index]).unwrap();
-        let mut similar_neurons: Vec<_> = related_neurons
-            .iter()
-            .copied()
-            .enumerate()
+        ensure!(
+            self.contains(neuron_index),
+            "Neuron {neuron_index} is not in the neuron store."
+        );
+        let index = neuron_index.flat_index(self.layer_size());
+        let self_count1 = self.self_count(index);
+        let mut similar_neurons: Vec<_> = self
+            .row(index)
             .map(|(index2, common_token_count)| {
-                let self_count2 = self.related_neurons[[index2, index2]];
+                let self_count2 = self.self_count(index2);
                 (
                     index2,
                     (common_token_count as f32) / (self_count1.max(self_count2) as f32),
@@ -194,7 +646,7 @@ impl NeuronStore {
             .filter(|&(index2, similarity)| index2 != index && similarity >= threshold)
             .map(|(index2, similarity)| {
                 (
-                    NeuronIndex::from_flat_index(self.layer_size, index2),
+                    NeuronIndex::from_flat_index(self.layer_size(), index2),
                     similarity,
                 )
             })
@@ -207,10 +659,237 @@ impl NeuronStore {
         Ok(similar_neurons)
     }
 
-    pub fn get(&self, search_type: TokenSearchType, token: &str) -> Option<&HashSet<NeuronIndex>> {
-        match search_type {
-            TokenSearchType::Activating => self.activating.get(token),
-            TokenSearchType::Important => self.important.get(token),
+    /// Returns the neurons matching the token for the search type, sorted by index, or `None` if
+    /// no neuron does.
+    pub fn get(
+        &self,
+        search_type: TokenSearchType,
+        token: &str,
+    ) -> Option<impl Iterator<Item = NeuronIndex> + '_> {
+        let (mut low, mut high) = (0, self.layout.header.num_tokens);
+        let token_index = loop {
+            if low >= high {
+                return None;
+            }
+            let middle = low + (high - low) / 2;
+            match self.token(middle).cmp(token.as_bytes()) {
+                Ordering::Less => low = middle + 1,
+                Ordering::Greater => high = middle,
+                Ordering::Equal => break middle,
+            }
+        };
+        let (offsets, start) = match search_type {
+            TokenSearchType::Activating => (self.layout.activating_offsets, 0),
+            // Important postings follow all activating postings.
+            TokenSearchType::Important => (
+                self.layout.important_offsets,
+                read_u64(
+                    &self.bytes,
+                    self.layout.activating_offsets + self.layout.header.num_tokens * 8,
+                ) as usize,
+            ),
+        };
+        let postings = self.range(offsets, token_index);
+        if postings.is_empty() {
+            return None;
+        }
+        Some(postings.filter_map(move |posting_index| {
+            let offset = self.layout.postings + (start + posting_index) * 4;
+            let flat_index = self.flat_index(offset)?;
+            Some(NeuronIndex::from_flat_index(self.layer_size(), flat_index))
+        }))
+    }
+
+    /// Returns the neurons matching all of the token searches, sorted by index. A neuron matches a
+    /// token search if it matches the token for any of its search types.
+    pub fn search(&self, token_searches: &[TokenSearch]) -> Result<Vec<NeuronIndex>> {
+        let mut results = token_searches
+            .iter()
+            .map(|token_search| {
+                token_search
+                    .search_types
+                    .iter()
+                    .flat_map(|&search_type| {
+                        self.get(search_type, token_search.token.as_str())
+                            .into_iter()
+                            .flatten()
+                    })
+                    .collect::<HashSet<_>>()
+            })
+            .reduce(|a, b| a.intersection(&b).copied().collect::<HashSet<_>>())
+            .context("At least one token search should be provided.")?
+            .into_iter()
+            .collect::<Vec<_>>();
+        results.sort_unstable();
+        Ok(results)
+    }
+}
+
+/// Whether the file at `path` was modified after the one at `other_path`. Files whose
+/// modification time cannot be read are not newer.
+fn is_newer<P: AsRef<Path>, Q: AsRef<Path>>(path: P, other_path: Q) -> bool {
+    let modified = |path: &Path| {
+        fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+    };
+    match (modified(path.as_ref()), modified(other_path.as_ref())) {
+        (Some(modified), Some(other_modified)) => modified > other_modified,
+        _ => false,
+    }
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LAYER_SIZE: u32 = 3;
+    const NUM_LAYERS: u32 = 2;
+
+    fn raw_store() -> NeuronStoreRaw {
+        serde_json::from_value(serde_json::json!({
+            "activating": {
+                "a": ["0_0", "0_1", "1_2"],
+                "b": ["0_1"],
+                "the": ["1_1", "0_2", "0_0"],
+            },
+            "important": {
+                "a": ["1_0"],
+                "c": ["0_0", "0_1", "1_2"],
+            },
+        }))
+        .unwrap()
+    }
+
+    fn compiled_bytes() -> Vec<u8> {
+        raw_store().compile(LAYER_SIZE, NUM_LAYERS).unwrap()
+    }
+
+    fn compiled_store() -> NeuronStore {
+        NeuronStore::from_bytes(Bytes::Owned(compiled_bytes())).unwrap()
+    }
+
+    fn neuron_indices(neuron_indices: &HashSet<String>) -> HashSet<NeuronIndex> {
+        neuron_indices
+            .iter()
+            .map(|neuron_index| neuron_index.parse().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn gets_the_neurons_of_the_json_store() {
+        let raw = raw_store();
+        let neuron_store = compiled_store();
+        for (search_type, token_index) in [
+            (TokenSearchType::Activating, &raw.activating),
+            (TokenSearchType::Important, &raw.important),
+        ] {
+            for token in ["a", "b", "c", "the", "missing"] {
+                let expected = token_index.get(token).map(neuron_indices);
+                let found = neuron_store
+                    .get(search_type, token)
+                    .map(|neuron_indices| neuron_indices.collect::<Vec<_>>());
+                if let Some(found) = &found {
+                    assert!(found.windows(2).all(|pair| pair[0] < pair[1]));
+                }
+                assert_eq!(
+                    found.map(|found| found.into_iter().collect::<HashSet<_>>()),
+                    expected,
+                    "{search_type}:{token}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn finds_the_similar_neurons_of_the_json_store() {
+        let raw = raw_store();
+        let neuron_store = compiled_store();
+        // Neurons are similar if they activate on or are important for the same tokens.
+        let lists: Vec<_> = raw
+            .activating
+            .values()
+            .chain(raw.important.values())
+            .map(neuron_indices)
+            .collect();
+        let count = |neurons: &[NeuronIndex]| {
+            lists
+                .iter()
+                .filter(|list| neurons.iter().all(|neuron| list.contains(neuron)))
+                .count() as f32
+        };
+        for flat_index in 0..(LAYER_SIZE * NUM_LAYERS) as usize {
+            let neuron = NeuronIndex::from_flat_index(LAYER_SIZE, flat_index);
+            let expected: BTreeMap<_, _> = (0..(LAYER_SIZE * NUM_LAYERS) as usize)
+                .map(|other_flat_index| NeuronIndex::from_flat_index(LAYER_SIZE, other_flat_index))
+                .filter(|&other| other != neuron && count(&[neuron, other]) > 0.)
+                .map(|other| {
+                    let similarity =
+                        count(&[neuron, other]) / count(&[neuron]).max(count(&[other]));
+                    (other, similarity)
+                })
+                .collect();
+            let found = neuron_store.similar_neurons(neuron, 0.).unwrap();
+            assert!(found.windows(2).all(|pair| pair[0].1 >= pair[1].1));
+            assert_eq!(
+                found.into_iter().collect::<BTreeMap<_, _>>(),
+                expected,
+                "{neuron}"
+            );
+        }
+        assert!(neuron_store
+            .similar_neurons(
+                NeuronIndex {
+                    layer: 2,
+                    neuron: 0
+                },
+                0.
+            )
+            .is_err());
+    }
+
+    #[test]
+    fn rejects_corrupted_stores() {
+        let bytes = compiled_bytes();
+        let layout = compiled_store().layout;
+        let open = |bytes: Vec<u8>| NeuronStore::from_bytes(Bytes::Owned(bytes));
+
+        assert!(open(bytes[..bytes.len() - 1].to_vec()).is_err());
+
+        // A number of tokens that overflows the size of the offset tables.
+        let mut overflowing = bytes.clone();
+        overflowing[16..24].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert!(open(overflowing).is_err());
+
+        // A row that ends before it starts.
+        let mut decreasing = bytes.clone();
+        let offset = layout.row_offsets + 8;
+        let num_entries = layout.header.num_entries as u64;
+        decreasing[offset..offset + 8].copy_from_slice(&num_entries.to_le_bytes());
+        let error = open(decreasing).err().unwrap().to_string();
+        assert!(error.contains("row offset 2"), "{error}");
+
+        // A co-occurrence entry with a neuron beyond the store is only found when compiling, and
+        // skipped by lookups.
+        let mut out_of_range = bytes;
+        let offset = layout.entries;
+        out_of_range[offset..offset + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+        let neuron_store = open(out_of_range).unwrap();
+        assert!(neuron_store.check_neurons().is_err());
+        for flat_index in 0..neuron_store.num_neurons() {
+            let neuron = NeuronIndex::from_flat_index(LAYER_SIZE, flat_index);
+            let similar_neurons = neuron_store.similar_neurons(neuron, 0.).unwrap();
+            assert!(similar_neurons
+                .iter()
+                .all(|&(other, _)| neuron_store.contains(other)));
         }
     }
 }
@@ -1,13 +1,77 @@
-use std::{fs, path::Path};
+use std::path::Path;
 
-use anyhow::{Context, Result};
+use anyhow::{ensure, Context, Result};
 
 use half::f16;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
+use crate::data::{
+    storage::{self, Encoding, PageKind, StoredPage, LEGACY_SCHEMA_VERSION},
+    NeuronIndex,
+};
+
 #[derive(Clone, Serialize, Deserialize)]
 pub struct NeuronViewerObject {
+    neuron_id: NeuronId,
+    random_sample: Vec<ActivationRecord>,
+    random_sample_by_quantile: Vec<Vec<ActivationRecord>>,
+    /// Boundaries between the quantiles. There is one less boundary than there are quantiles.
+    quantile_boundaries: Vec<f16>,
+    mean: f32,
+    variance: f32,
+    skewness: f32,
+    kurtosis: f32,
+    most_positive_activation_records: Vec<ActivationRecord>,
+}
+
+/// Layout of the neuron viewer JSON files of the automated interpretability project.
+#[derive(Deserialize)]
+struct NeuronViewerJson {
+    neuron_id: NeuronId,
+    random_sample: Vec<ActivationRecordJson>,
+    random_sample_by_quantile: Vec<Vec<ActivationRecordJson>>,
+    quantile_boundaries: Vec<f32>,
+    mean: f32,
+    variance: f32,
+    skewness: f32,
+    kurtosis: f32,
+    most_positive_activation_records: Vec<ActivationRecordJson>,
+}
+
+#[derive(Deserialize)]
+struct ActivationRecordJson {
+    tokens: Vec<String>,
+    activations: Vec<f32>,
+}
+
+impl ActivationRecordJson {
+    fn into_record(self) -> Result<ActivationRecord> {
+        ActivationRecord::new(
+            self.tokens
+                .into_iter()
+                .map(|token_id| Token { token_id })
+                .collect(),
+            self.activations.into_iter().map(f16::from_f32).collect(),
+        )
+    }
+
+    fn into_records(records: Vec<Self>) -> Result<Vec<ActivationRecord>> {
+        records
+            .into_iter()
+            .enumerate()
+            .map(|(index, record)| {
+                record
+                    .into_record()
+                    .with_context(|| format!("Invalid activation record {index}."))
+            })
+            .collect()
+    }
+}
+
+/// Layout of files written before they had a header, which only supported 5 quantiles.
+#[derive(Deserialize)]
+struct LegacyNeuronViewerObject {
     neuron_id: NeuronId,
     random_sample: Vec<ActivationRecord>,
     random_sample_by_quantile: [Vec<ActivationRecord>; 5],
@@ -21,48 +85,64 @@ pub struct NeuronViewerObject {
 
 impl NeuronViewerObject {
     pub fn from_json(json: &Value) -> Result<Self> {
-        let json = json.as_object().unwrap();
-        let neuron_id = NeuronId::from_json(&json["neuron_id"]);
-        let random_sample = json["random_sample"]
-            .as_array()
-            .context("object 'random_sample' not an array")?
-            .iter()
-            .map(ActivationRecord::from_json)
-            .collect::<Result<Vec<_>>>()?;
-        let random_sample_by_quantile: Vec<Vec<ActivationRecord>> = json
-            ["random_sample_by_quantile"]
-            .as_array()
-            .context("object 'random_sample_by_quantile' not an array")?
-            .iter()
-            .map(|quantile_samples| {
-                quantile_samples
-                    .as_array()
-                    .context("quantile sample not an array")?
-                    .iter()
-                    .map(ActivationRecord::from_json)
-                    .collect::<Result<Vec<_>>>()
+        let json =
+            NeuronViewerJson::deserialize(json).context("Failed to parse neuron viewer object.")?;
+        Self::from_typed_json(json)
+    }
+
+    pub fn from_json_str(json: &str) -> Result<Self> {
+        let json = serde_json::from_str(json).context("Failed to parse neuron viewer object.")?;
+        Self::from_typed_json(json)
+    }
+
+    fn from_typed_json(json: NeuronViewerJson) -> Result<Self> {
+        let NeuronViewerJson {
+            neuron_id,
+            random_sample,
+            random_sample_by_quantile,
+            quantile_boundaries,
+            mean,
+            variance,
+            skewness,
+            kurtosis,
+            most_positive_activation_records,
+        } = json;
+        let random_sample_by_quantile = random_sample_by_quantile
+            .into_iter()
+            .enumerate()
+            .map(|(index, records)| {
+                ActivationRecordJson::into_records(records)
+                    .with_context(|| format!("Invalid sample of quantile {index}."))
             })
             .collect::<Result<Vec<_>>>()?;
-        let random_sample_by_quantile: [Vec<ActivationRecord>; 5] =
-            random_sample_by_quantile.try_into().unwrap();
-        let quantile_boundaries = json["quantile_boundaries"]
-            .as_array()
-            .unwrap()
-            .iter()
-            .filter_map(|f| f.as_f64().map(f16::from_f64))
-            .collect::<Vec<_>>()
-            .try_into()
-            .expect("wrong number of quantile boundaries. Should be 4");
-        let mean = json["mean"].as_f64().unwrap() as f32;
-        let variance = json["variance"].as_f64().unwrap() as f32;
-        let skewness = json["skewness"].as_f64().unwrap() as f32;
-        let kurtosis = json["kurtosis"].as_f64().unwrap() as f32;
-        let most_positive_activation_records = json["most_positive_activation_records"]
-            .as_array()
-            .unwrap()
-            .iter()
-            .map(ActivationRecord::from_json)
-            .collect::<Result<Vec<_>>>()?;
+        Self::new(
+            neuron_id,
+            ActivationRecordJson::into_records(random_sample).context("Invalid random sample.")?,
+            random_sample_by_quantile,
+            quantile_boundaries.into_iter().map(f16::from_f32).collect(),
+            [mean, variance, skewness, kurtosis],
+            ActivationRecordJson::into_records(most_positive_activation_records)
+                .context("Invalid most positive activation records.")?,
+        )
+    }
+
+    /// `moments` are the mean, variance, skewness and kurtosis of the activations.
+    fn new(
+        neuron_id: NeuronId,
+        random_sample: Vec<ActivationRecord>,
+        random_sample_by_quantile: Vec<Vec<ActivationRecord>>,
+        quantile_boundaries: Vec<f16>,
+        moments: [f32; 4],
+        most_positive_activation_records: Vec<ActivationRecord>,
+    ) -> Result<Self> {
+        ensure!(
+            random_sample_by_quantile.len() == quantile_boundaries.len() + 1,
+            "Expected {} quantile boundaries for {} quantiles, but got {}.",
+            random_sample_by_quantile.len().saturating_sub(1),
+            random_sample_by_quantile.len(),
+            quantile_boundaries.len()
+        );
+        let [mean, variance, skewness, kurtosis] = moments;
         Ok(Self {
             neuron_id,
             random_sample,
@@ -76,15 +156,78 @@ impl NeuronViewerObject {
         })
     }
 
-    pub fn to_file<P: AsRef<Path>>(&self, path: P) {
-        let path = path.as_ref();
-        fs::create_dir_all(path.parent().unwrap()).unwrap();
-        let data = postcard::to_allocvec(&self).unwrap();
-        std::fs::write(path, data).unwrap();
+    pub fn neuron_index(&self) -> NeuronIndex {
+        NeuronIndex {
+            layer: self.neuron_id.layer_index,
+            neuron: self.neuron_id.neuron_index,
+        }
+    }
+
+    pub fn random_sample_by_quantile(&self) -> &[Vec<ActivationRecord>] {
+        self.random_sample_by_quantile.as_slice()
+    }
+
+    pub fn quantile_boundaries(&self) -> &[f16] {
+        self.quantile_boundaries.as_slice()
+    }
+
+    pub fn to_file<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        storage::to_file(self, path)
+    }
+
+    pub fn to_file_with_encoding<P: AsRef<Path>>(
+        &self,
+        path: P,
+        encoding: &Encoding,
+    ) -> Result<()> {
+        storage::to_file_with_encoding(self, path, encoding)
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        storage::from_file(path)
+    }
+}
+
+impl StoredPage for NeuronViewerObject {
+    const KIND: PageKind = PageKind::NeuronViewer;
+    const SCHEMA_VERSION: u16 = 1;
+
+    fn deserialize_payload(version: u16, payload: &[u8]) -> Result<Self> {
+        match version {
+            LEGACY_SCHEMA_VERSION => {
+                let LegacyNeuronViewerObject {
+                    neuron_id,
+                    random_sample,
+                    random_sample_by_quantile,
+                    quantile_boundaries,
+                    mean,
+                    variance,
+                    skewness,
+                    kurtosis,
+                    most_positive_activation_records,
+                } = postcard::from_bytes(payload)?;
+                Self::new(
+                    neuron_id,
+                    random_sample,
+                    random_sample_by_quantile.into(),
+                    quantile_boundaries.into(),
+                    [mean, variance, skewness, kurtosis],
+                    most_positive_activation_records,
+                )
+            }
+            1 => Ok(postcard::from_bytes(payload)?),
+            _ => unreachable!("Unsupported versions are rejected before deserializing."),
+        }
+    }
+
+    /// Files without a header were written uncompressed.
+    fn from_legacy_bytes(bytes: &[u8]) -> Result<Self> {
+        Self::deserialize_payload(LEGACY_SCHEMA_VERSION, bytes)
     }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(transparent)]
 pub struct Token {
     token_id: String,
 }
@@ -95,18 +238,6 @@ pub struct NeuronId {
     neuron_index: u32,
 }
 
-impl NeuronId {
-    pub fn from_json(json: &Value) -> Self {
-        let json = json.as_object().unwrap();
-        let layer_index = json["layer_index"].as_u64().unwrap() as u32;
-        let neuron_index = json["neuron_index"].as_u64().unwrap() as u32;
-        Self {
-            layer_index,
-            neuron_index,
-        }
-    }
-}
-
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct ActivationRecord {
     tokens: Vec<Token>,
@@ -114,40 +245,16 @@ pub struct ActivationRecord {
 }
 
 impl ActivationRecord {
-    pub fn new(tokens: Vec<Token>, activations: Vec<f16>) -> Self {
-        assert_eq!(tokens.len(), activations.len());
-        Self {
+    pub fn new(tokens: Vec<Token>, activations: Vec<f16>) -> Result<Self> {
+        ensure!(
+            tokens.len() == activations.len(),
+            "Activation record has {} tokens, but {} activations.",
+            tokens.len(),
+            activations.len()
+        );
+        Ok(Self {
             tokens,
             activations,
-        }
-    }
-
-    pub fn from_json(json: &Value) -> Result<Self> {
-        let json = json
-            .as_object()
-            .context("activation record not an object")?;
-        let tokens = json["tokens"]
-            .as_array()
-            .context("object 'tokens' not an array")?
-            .iter()
-            .map(|token| {
-                Ok(Token {
-                    token_id: token.as_str().context("token not an integer")?.to_string(),
-                })
-            })
-            .collect::<Result<Vec<_>>>()
-            .context("error parsing tokens")?;
-        let activations = json["activations"]
-            .as_array()
-            .context("object 'activations' not an array")?
-            .iter()
-            .map(|activation| {
-                Ok(f16::from_f64(
-                    activation.as_f64().context("activation not a float")?,
-                ))
-            })
-            .collect::<Result<Vec<_>>>()
-            .context("error parsing activations")?;
-        Ok(Self::new(tokens, activations))
+        })
     }
 }
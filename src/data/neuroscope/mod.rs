@@ -1,6 +1,8 @@
 mod neuroscope_page;
-pub use neuroscope_page::NeuroscopeNeuronPage;
+pub use neuroscope_page::{NeuroscopeNeuronPage, Text};
 mod neuroscope_layer_page;
 pub use neuroscope_layer_page::NeuroscopeLayerPage;
 mod neuroscope_model_page;
 pub use neuroscope_model_page::NeuroscopeModelPage;
+mod neuroscope_archive;
+pub use neuroscope_archive::NeuroscopeLayerArchive;
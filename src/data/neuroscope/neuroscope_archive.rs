@@ -0,0 +1,402 @@
+use std::{
+    fs::{self, File},
+    io::{BufWriter, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, ensure, Context, Result};
+use memmap2::Mmap;
+
+use crate::data::{retrieve::neuroscope::neuron_data_path, storage::Encoding, NeuronIndex};
+
+use super::NeuroscopeNeuronPage;
+
+const MAGIC: &[u8; 4] = b"NSAR";
+const VERSION: u32 = 1;
+/// Magic, version, layer index and number of neurons.
+const HEADER_SIZE: usize = 16;
+/// Offset and length of a record.
+const TABLE_ENTRY_SIZE: usize = 16;
+
+/// All neuroscope neuron pages of a layer packed into a single file.
+///
+/// The file starts with a header and an offset table with an entry for every neuron in the layer,
+/// followed by the independently compressed pages. Neurons without a page have an entry with
+/// length 0. The file is memory mapped, so only the pages that are read are loaded from disk.
+pub struct NeuroscopeLayerArchive {
+    path: PathBuf,
+    layer_index: u32,
+    num_neurons: u32,
+    mmap: Mmap,
+}
+
+impl NeuroscopeLayerArchive {
+    pub fn path<P: AsRef<Path>, S: AsRef<str>>(
+        data_path: P,
+        model_name: S,
+        layer_index: u32,
+    ) -> PathBuf {
+        data_path
+            .as_ref()
+            .join(model_name.as_ref())
+            .join("neuroscope")
+            .join(format!("l{layer_index}"))
+            .with_extension("archive")
+    }
+
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let file = File::open(path).with_context(|| format!("Failed to open file '{path:?}'."))?;
+        // SAFETY: Archives are written once and never modified in place, so the mapped memory
+        // does not change under us.
+        let mmap = unsafe { Mmap::map(&file) }
+            .with_context(|| format!("Failed to memory map file '{path:?}'."))?;
+
+        ensure!(
+            mmap.len() >= HEADER_SIZE && &mmap[..4] == MAGIC,
+            "File '{path:?}' is not a neuroscope layer archive."
+        );
+        let version = read_u32(&mmap, 4);
+        if version != VERSION {
+            bail!("Neuroscope layer archive '{path:?}' has unsupported version {version}.");
+        }
+        let layer_index = read_u32(&mmap, 8);
+        let num_neurons = read_u32(&mmap, 12);
+        ensure!(
+            mmap.len() >= HEADER_SIZE + num_neurons as usize * TABLE_ENTRY_SIZE,
+            "Neuroscope layer archive '{path:?}' is truncated."
+        );
+
+        Ok(Self {
+            path: path.to_owned(),
+            layer_index,
+            num_neurons,
+            mmap,
+        })
+    }
+
+    pub fn layer_index(&self) -> u32 {
+        self.layer_index
+    }
+
+    pub fn num_neurons(&self) -> u32 {
+        self.num_neurons
+    }
+
+    /// Number of bytes of the archive that are memory mapped.
+    pub fn mapped_size(&self) -> usize {
+        self.mmap.len()
+    }
+
+    /// Returns the compressed page of the given neuron, or `None` if the archive has no page for
+    /// it.
+    pub fn record(&self, neuron_index: u32) -> Result<Option<&[u8]>> {
+        let path = &self.path;
+        ensure!(
+            neuron_index < self.num_neurons,
+            "Neuron index {neuron_index} out of bounds for archive '{path:?}' with {} neurons.",
+            self.num_neurons
+        );
+        let entry_offset = HEADER_SIZE + neuron_index as usize * TABLE_ENTRY_SIZE;
+        let offset = read_u64(&self.mmap, entry_offset) as usize;
+        let length = read_u64(&self.mmap, entry_offset + 8) as usize;
+        if length == 0 {
+            return Ok(None);
+        }
+        let record = offset
+            .checked_add(length)
+            .and_then(|end| self.mmap.get(offset..end))
+            .with_context(|| {
+                format!("Record for neuron {neuron_index} out of bounds in archive '{path:?}'.")
+            })?;
+        Ok(Some(record))
+    }
+
+    pub fn page(&self, neuron_index: NeuronIndex) -> Result<NeuroscopeNeuronPage> {
+        let path = &self.path;
+        ensure!(
+            neuron_index.layer == self.layer_index,
+            "Neuron {neuron_index} is not in layer {} stored in archive '{path:?}'.",
+            self.layer_index
+        );
+        let record = self.record(neuron_index.neuron)?.with_context(|| {
+            format!("Archive '{path:?}' contains no page for neuron {neuron_index}.")
+        })?;
+        let dataset_path = path
+            .parent()
+            .with_context(|| format!("Invalid path '{path:?}'"))?;
+        NeuroscopeNeuronPage::from_bytes(record, dataset_path).with_context(|| {
+            format!("Failed to load page for neuron {neuron_index} from archive '{path:?}'.")
+        })
+    }
+
+    /// Writes an archive of the layer, storing the pages in the order they are produced. Pages
+    /// are only held in memory one at a time.
+    pub fn write<P, I>(
+        path: P,
+        layer_index: u32,
+        num_neurons: u32,
+        pages: I,
+        encoding: &Encoding,
+    ) -> Result<()>
+    where
+        P: AsRef<Path>,
+        I: IntoIterator<Item = Result<NeuroscopeNeuronPage>>,
+    {
+        let path = path.as_ref();
+        fs::create_dir_all(
+            path.parent()
+                .with_context(|| format!("Invalid path '{path:?}'"))?,
+        )
+        .with_context(|| format!("Failed to create directory for '{path:?}'"))?;
+        // Write to a temporary file first, so archives that are already mapped are replaced
+        // rather than modified.
+        let temporary_path = path.with_extension("archive.tmp");
+        let result = File::create(&temporary_path)
+            .with_context(|| format!("Failed to create file '{temporary_path:?}'."))
+            .and_then(|file| {
+                Self::write_to(
+                    BufWriter::new(file),
+                    layer_index,
+                    num_neurons,
+                    pages,
+                    encoding,
+                )
+            });
+        match result {
+            Ok(()) => fs::rename(&temporary_path, path)
+                .with_context(|| format!("Failed to move '{temporary_path:?}' to '{path:?}'.")),
+            Err(error) => {
+                // The archive is incomplete, and the error is more useful than a failure to clean
+                // it up.
+                let _ = fs::remove_file(&temporary_path);
+                Err(error.context(format!("Failed to write archive '{path:?}'.")))
+            }
+        }
+    }
+
+    fn write_to<W, I>(
+        mut writer: W,
+        layer_index: u32,
+        num_neurons: u32,
+        pages: I,
+        encoding: &Encoding,
+    ) -> Result<()>
+    where
+        W: Write + Seek,
+        I: IntoIterator<Item = Result<NeuroscopeNeuronPage>>,
+    {
+        let table_size = num_neurons as usize * TABLE_ENTRY_SIZE;
+        let mut table = vec![0u8; table_size];
+        writer.write_all(MAGIC)?;
+        writer.write_all(&VERSION.to_le_bytes())?;
+        writer.write_all(&layer_index.to_le_bytes())?;
+        writer.write_all(&num_neurons.to_le_bytes())?;
+        writer.write_all(&table)?;
+
+        let mut offset = (HEADER_SIZE + table_size) as u64;
+        for page in pages {
+            let page = page?;
+            let NeuronIndex {
+                layer: page_layer_index,
+                neuron: neuron_index,
+            } = page.neuron_index();
+            ensure!(
+                page_layer_index == layer_index && neuron_index < num_neurons,
+                "Page for neuron {} does not belong in archive of layer {layer_index} with {num_neurons} neurons.",
+                page.neuron_index()
+            );
+            let record = page.to_bytes(encoding)?;
+            writer.write_all(&record)?;
+
+            let entry_offset = neuron_index as usize * TABLE_ENTRY_SIZE;
+            table[entry_offset..entry_offset + 8].copy_from_slice(&offset.to_le_bytes());
+            table[entry_offset + 8..entry_offset + 16]
+                .copy_from_slice(&(record.len() as u64).to_le_bytes());
+            offset += record.len() as u64;
+        }
+
+        writer.seek(SeekFrom::Start(HEADER_SIZE as u64))?;
+        writer.write_all(&table)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Packs the individual neuron page files of a layer into an archive, with the encoding
+    /// configured for the model's neuroscope pages. Neurons without a page file are left out of
+    /// the archive. Returns the number of pages packed.
+    pub fn pack_page_files<P: AsRef<Path>, S: AsRef<str>>(
+        data_path: P,
+        model_name: S,
+        layer_index: u32,
+        num_neurons: u32,
+    ) -> Result<u32> {
+        let data_path = data_path.as_ref();
+        let model_name = model_name.as_ref();
+        let encoding = Encoding::for_dataset(data_path.join(model_name).join("neuroscope"))?;
+        let mut num_packed = 0;
+        let pages = (0..num_neurons)
+            .map(|neuron_index| NeuronIndex {
+                layer: layer_index,
+                neuron: neuron_index,
+            })
+            .map(|neuron_index| {
+                (
+                    neuron_index,
+                    neuron_data_path(data_path, model_name, neuron_index),
+                )
+            })
+            .filter(|(_, page_path)| page_path.exists())
+            .map(|(neuron_index, page_path)| {
+                num_packed += 1;
+                let page = NeuroscopeNeuronPage::from_file(&page_path)?;
+                ensure!(
+                    page.neuron_index() == neuron_index,
+                    "Page file '{page_path:?}' contains neuron {}.",
+                    page.neuron_index()
+                );
+                Ok(page)
+            });
+        Self::write(
+            Self::path(data_path, model_name, layer_index),
+            layer_index,
+            num_neurons,
+            pages,
+            &encoding,
+        )?;
+        Ok(num_packed)
+    }
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, process};
+
+    use super::*;
+    use crate::data::{
+        storage::{Codec, Encoding},
+        Text,
+    };
+
+    /// A directory of its own for every test, removed again when dropped.
+    struct TestDirectory(PathBuf);
+
+    impl TestDirectory {
+        fn new(name: &str) -> Self {
+            let path = env::temp_dir().join(format!("neuronav-archive-{name}-{}", process::id()));
+            fs::create_dir_all(&path).unwrap();
+            Self(path)
+        }
+    }
+
+    impl Drop for TestDirectory {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn page(layer: u32, neuron: u32) -> NeuroscopeNeuronPage {
+        let text = Text::new(
+            vec!["The".to_owned(), " neuron".to_owned()],
+            vec![neuron as f32, -1.],
+            layer.into(),
+            (-1., neuron as f32),
+        )
+        .unwrap();
+        NeuroscopeNeuronPage::new(NeuronIndex { layer, neuron }, vec![text])
+    }
+
+    #[test]
+    fn reads_the_pages_it_was_written_with() {
+        let directory = TestDirectory::new("round-trip");
+        let path = directory.0.join("l1.archive");
+        let encoding = Encoding::new(Codec::Zstd, None, None).unwrap();
+        let pages = [page(1, 3), page(1, 0)];
+        NeuroscopeLayerArchive::write(&path, 1, 4, pages.clone().map(Ok), &encoding).unwrap();
+
+        let archive = NeuroscopeLayerArchive::open(&path).unwrap();
+        assert_eq!((archive.layer_index(), archive.num_neurons()), (1, 4));
+        for page in pages {
+            assert_eq!(archive.page(page.neuron_index()).unwrap(), page);
+        }
+        assert!(archive.record(1).unwrap().is_none());
+        assert!(archive
+            .page(NeuronIndex {
+                layer: 1,
+                neuron: 1
+            })
+            .is_err());
+        assert!(archive.record(4).is_err());
+        assert!(archive
+            .page(NeuronIndex {
+                layer: 0,
+                neuron: 0
+            })
+            .is_err());
+    }
+
+    #[test]
+    fn leaves_no_archive_behind_when_writing_fails() {
+        let directory = TestDirectory::new("failure");
+        let path = directory.0.join("l1.archive");
+        let pages = [Ok(page(1, 0)), Ok(page(0, 1))];
+        assert!(NeuroscopeLayerArchive::write(&path, 1, 4, pages, &Encoding::default()).is_err());
+        assert!(!path.exists());
+        assert!(!path.with_extension("archive.tmp").exists());
+    }
+
+    #[test]
+    fn rejects_truncated_archives() {
+        let directory = TestDirectory::new("truncated");
+        let path = directory.0.join("l0.archive");
+        NeuroscopeLayerArchive::write(&path, 0, 8, [Ok(page(0, 7))], &Encoding::default()).unwrap();
+        let bytes = fs::read(&path).unwrap();
+        fs::write(&path, &bytes[..HEADER_SIZE + TABLE_ENTRY_SIZE]).unwrap();
+        assert!(NeuroscopeLayerArchive::open(&path).is_err());
+
+        // A record beyond the end of the file.
+        let table_end = HEADER_SIZE + 8 * TABLE_ENTRY_SIZE;
+        fs::write(&path, &bytes[..table_end + 1]).unwrap();
+        let archive = NeuroscopeLayerArchive::open(&path).unwrap();
+        assert!(archive.record(7).is_err());
+    }
+
+    #[test]
+    fn packs_page_files() {
+        let directory = TestDirectory::new("pack");
+        let data_path = &directory.0;
+        for neuron in [0, 2] {
+            page(0, neuron)
+                .to_file(neuron_data_path(
+                    data_path,
+                    "model",
+                    NeuronIndex { layer: 0, neuron },
+                ))
+                .unwrap();
+        }
+        let num_packed = NeuroscopeLayerArchive::pack_page_files(data_path, "model", 0, 3).unwrap();
+        assert_eq!(num_packed, 2);
+
+        let archive =
+            NeuroscopeLayerArchive::open(NeuroscopeLayerArchive::path(data_path, "model", 0))
+                .unwrap();
+        assert_eq!(
+            archive
+                .page(NeuronIndex {
+                    layer: 0,
+                    neuron: 2
+                })
+                .unwrap(),
+            page(0, 2)
+        );
+        assert!(archive.record(1).unwrap().is_none());
+    }
+}
@@ -1,16 +1,15 @@
-use std::{
-    fs::{self, File},
-    io::{self, BufReader, Read, Write},
-    path::Path,
-};
+use std::path::Path;
 
-use anyhow::{Context, Result};
-use flate2::{bufread::DeflateDecoder, write::DeflateEncoder, Compression};
+use anyhow::Result;
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 
-use crate::data::NeuronIndex;
+use crate::data::{
+    storage::{self, Encoding, PageKind, StoredPage, LEGACY_SCHEMA_VERSION},
+    NeuronIndex,
+};
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
 pub struct NeuroscopeLayerPage {
     num_neurons: u32,
     important_neurons: Vec<(NeuronIndex, f32)>,
@@ -38,33 +37,31 @@ impl NeuroscopeLayerPage {
     }
 
     pub fn to_file<P: AsRef<Path>>(&self, path: P) -> Result<()> {
-        let path = path.as_ref();
-        fs::create_dir_all(
-            path.parent()
-                .with_context(|| format!("Invalid path '{path:?}'"))?,
-        )
-        .with_context(|| format!("Failed to create directory for '{path:?}'"))?;
-        let data = postcard::to_allocvec(&self).context("Failed to serialize neuroscope page.")?;
+        storage::to_file(self, path)
+    }
 
-        let file =
-            File::create(path).with_context(|| format!("Failed to create file '{path:?}'."))?;
-        let mut encoder = DeflateEncoder::new(file, Compression::default());
-        encoder
-            .write_all(&data)
-            .context("Failed to compress neuroscope page.")
+    pub fn to_file_with_encoding<P: AsRef<Path>>(
+        &self,
+        path: P,
+        encoding: &Encoding,
+    ) -> Result<()> {
+        storage::to_file_with_encoding(self, path, encoding)
     }
 
     pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
-        let path = path.as_ref();
-        let file = File::open(path).with_context(|| format!("Failed to open file '{path:?}'."))?;
-        let buf_reader = BufReader::new(file);
-        let decoder = DeflateDecoder::new(buf_reader);
-        let data = decoder
-            .bytes()
-            .collect::<io::Result<Vec<u8>>>()
-            .context("Failed to decompress neuroscope page.")?;
+        storage::from_file(path)
+    }
+}
+
+impl StoredPage for NeuroscopeLayerPage {
+    const KIND: PageKind = PageKind::NeuroscopeLayer;
+    const SCHEMA_VERSION: u16 = 1;
 
-        postcard::from_bytes(&data)
-            .with_context(|| format!("Failed to deserialize neuroscope page from file '{path:?}'."))
+    fn deserialize_payload(version: u16, payload: &[u8]) -> Result<Self> {
+        match version {
+            // Files without a header have the same layout as version 1.
+            LEGACY_SCHEMA_VERSION | 1 => Ok(postcard::from_bytes(payload)?),
+            _ => unreachable!("Unsupported versions are rejected before deserializing."),
+        }
     }
 }
@@ -1,17 +1,17 @@
-use std::{
-    fs::{self, File},
-    io::{self, BufReader, Read, Write},
-    path::Path,
-    str::FromStr,
-};
+use std::{path::Path, str::FromStr};
 
 use anyhow::{bail, Context, Result};
-use flate2::{bufread::DeflateDecoder, write::DeflateEncoder, Compression};
 use itertools::Itertools;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::data::{
+    storage::{self, Encoding, PageKind, StoredPage, LEGACY_SCHEMA_VERSION},
+    NeuronIndex,
+};
 
-use crate::data::NeuronIndex;
+use super::NeuroscopeLayerArchive;
 
 const FLOAT_REGEX: &str = r"-?\d+(?:\.\d*)?";
 
@@ -33,13 +33,20 @@ where
     })
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
 pub struct NeuroscopeNeuronPage {
     neuron_index: NeuronIndex,
     texts: Vec<Text>,
 }
 
 impl NeuroscopeNeuronPage {
+    pub fn new(neuron_index: NeuronIndex, texts: Vec<Text>) -> Self {
+        Self {
+            neuron_index,
+            texts,
+        }
+    }
+
     fn from_html_header_and_texts(
         header_html: &str,
         texts: Vec<Text>,
@@ -92,35 +99,34 @@ impl NeuroscopeNeuronPage {
         Self::from_html_header_and_texts(header, texts, neuron_index)
     }
 
+    pub fn to_bytes(&self, encoding: &Encoding) -> Result<Vec<u8>> {
+        storage::to_bytes(self, encoding)
+    }
+
+    /// Decodes a page stored in the given dataset directory.
+    pub fn from_bytes<P: AsRef<Path>>(bytes: &[u8], dataset_path: P) -> Result<Self> {
+        storage::from_bytes(bytes, dataset_path)
+    }
+
     pub fn to_file<P: AsRef<Path>>(&self, path: P) -> Result<()> {
-        let path = path.as_ref();
-        fs::create_dir_all(
-            path.parent()
-                .with_context(|| format!("Invalid path '{path:?}'"))?,
-        )
-        .with_context(|| format!("Failed to create directory for '{path:?}'"))?;
-        let data = postcard::to_allocvec(&self).context("Failed to serialize neuroscope page.")?;
+        storage::to_file(self, path)
+    }
 
-        let file =
-            File::create(path).with_context(|| format!("Failed to create file '{path:?}'."))?;
-        let mut encoder = DeflateEncoder::new(file, Compression::default());
-        encoder
-            .write_all(&data)
-            .context("Failed to compress neuroscope page.")
+    pub fn to_file_with_encoding<P: AsRef<Path>>(
+        &self,
+        path: P,
+        encoding: &Encoding,
+    ) -> Result<()> {
+        storage::to_file_with_encoding(self, path, encoding)
     }
 
     pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
-        let path = path.as_ref();
-        let file = File::open(path).with_context(|| format!("Failed to open file '{path:?}'."))?;
-        let buf_reader = BufReader::new(file);
-        let decoder = DeflateDecoder::new(buf_reader);
-        let data = decoder
-            .bytes()
-            .collect::<io::Result<Vec<u8>>>()
-            .context("Failed to decompress neuroscope page.")?;
+        storage::from_file(path)
+    }
 
-        postcard::from_bytes(&data)
-            .with_context(|| format!("Failed to deserialize neuroscope page from file '{path:?}'."))
+    /// Loads a single page from a packed layer archive without reading the rest of the layer.
+    pub fn from_archive<P: AsRef<Path>>(path: P, neuron_index: NeuronIndex) -> Result<Self> {
+        NeuroscopeLayerArchive::open(path)?.page(neuron_index)
     }
 
     pub fn neuron_index(&self) -> NeuronIndex {
@@ -132,7 +138,20 @@ impl NeuroscopeNeuronPage {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl StoredPage for NeuroscopeNeuronPage {
+    const KIND: PageKind = PageKind::NeuroscopeNeuron;
+    const SCHEMA_VERSION: u16 = 1;
+
+    fn deserialize_payload(version: u16, payload: &[u8]) -> Result<Self> {
+        match version {
+            // Files without a header have the same layout as version 1.
+            LEGACY_SCHEMA_VERSION | 1 => Ok(postcard::from_bytes(payload)?),
+            _ => unreachable!("Unsupported versions are rejected before deserializing."),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
 pub struct Text {
     min_range: f32,
     max_range: f32,
@@ -145,6 +164,41 @@ pub struct Text {
 }
 
 impl Text {
+    /// Creates a text from the activations of a neuron on its tokens. `activation_range` is the
+    /// range that activations are colored in, shared by all texts of the neuron.
+    pub fn new(
+        tokens: Vec<String>,
+        activations: Vec<f32>,
+        data_index: u64,
+        activation_range: (f32, f32),
+    ) -> Result<Self> {
+        if tokens.len() != activations.len() {
+            bail!("Tokens and activations have different lengths.")
+        }
+        let (max_activating_token_index, max_activation) = activations
+            .iter()
+            .copied()
+            .enumerate()
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .context("Text has no tokens.")?;
+        let min_activation = activations
+            .iter()
+            .copied()
+            .min_by(f32::total_cmp)
+            .context("Text has no tokens.")?;
+        let (min_range, max_range) = activation_range;
+        Ok(Self {
+            min_range,
+            max_range,
+            min_activation,
+            max_activation,
+            data_index,
+            max_activating_token_index: max_activating_token_index as u32,
+            tokens,
+            activations,
+        })
+    }
+
     pub fn from_html_str(html: &str) -> Result<Self> {
         let max_range_regex = Regex::new(&format!(r"<h4>Max Range: <b>({FLOAT_REGEX})</b>."))
             .context("Failed to create regex.")?;
@@ -226,4 +280,28 @@ impl Text {
     pub fn max_activation(&self) -> f32 {
         self.max_activation
     }
+
+    pub fn min_range(&self) -> f32 {
+        self.min_range
+    }
+
+    pub fn max_range(&self) -> f32 {
+        self.max_range
+    }
+
+    pub fn data_index(&self) -> u64 {
+        self.data_index
+    }
+
+    pub fn max_activating_token_index(&self) -> u32 {
+        self.max_activating_token_index
+    }
+
+    pub fn tokens(&self) -> &[String] {
+        self.tokens.as_slice()
+    }
+
+    pub fn activations(&self) -> &[f32] {
+        self.activations.as_slice()
+    }
 }
@@ -13,15 +13,17 @@ pub struct Payload {
 impl Payload {
     pub fn initialize() -> Self {
         let metadata_service_provider = ServiceProvider::Metadata;
-        let metadata_service = Service::new("metadata".to_string(), metadata_service_provider);
+        let metadata_service = Service::new("metadata".to_string(), metadata_service_provider)
+            .expect("'metadata' is not a reserved service name.");
         let services = HashMap::from([("metadata".to_string(), metadata_service)]);
         Self { services }
     }
 
     pub fn add_service(&mut self, service: Service) -> Result<()> {
-        if service.name() == "metadata" {
+        if service.name() == "metadata" || service.provider().is_metadata() {
             bail!("A payload always contains a 'metadata' service. Another cannot be added.")
         }
+        Service::check_name(service.name())?;
         if self.services.contains_key(service.name()) {
             bail!("A service named '{}' already exists.", service.name());
         }
@@ -49,21 +51,27 @@ impl Default for Payload {
 
         let neuroscope_service_provider = ServiceProvider::Neuroscope;
         let neuroscope_service =
-            Service::new("neuroscope".to_string(), neuroscope_service_provider);
+            Service::new("neuroscope".to_string(), neuroscope_service_provider).unwrap();
         result.add_service(neuroscope_service).unwrap();
 
         let neuron2graph_service_provider = ServiceProvider::Neuron2Graph;
         let neuron2graph_service =
-            Service::new("neuron2graph".to_string(), neuron2graph_service_provider);
+            Service::new("neuron2graph".to_string(), neuron2graph_service_provider).unwrap();
         result.add_service(neuron2graph_service).unwrap();
 
         let neuron2graph_service_provider = ServiceProvider::Neuron2GraphSearch;
         let neuron2graph_service = Service::new(
             "neuron2graph-search".to_string(),
             neuron2graph_service_provider,
-        );
+        )
+        .unwrap();
         result.add_service(neuron2graph_service).unwrap();
 
+        let explanation_service_provider = ServiceProvider::Explanation;
+        let explanation_service =
+            Service::new("explanation".to_string(), explanation_service_provider).unwrap();
+        result.add_service(explanation_service).unwrap();
+
         result
     }
 }
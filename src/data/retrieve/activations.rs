@@ -0,0 +1,544 @@
+use std::{
+    cmp::Reverse,
+    collections::BinaryHeap,
+    fs::{self, File},
+    path::Path,
+};
+
+use anyhow::{bail, ensure, Context, Result};
+use half::{bf16, f16};
+use memmap2::Mmap;
+use npyz::{DType, NpyHeader, Order};
+use safetensors::{Dtype, SafeTensors};
+
+use crate::data::{
+    neuroscope::{NeuroscopeLayerPage, NeuroscopeModelPage, NeuroscopeNeuronPage, Text},
+    retrieve::neuroscope::neuron_data_path,
+    storage::Encoding,
+    ModelMetadata, NeuronIndex,
+};
+
+#[derive(Clone, Copy, Debug)]
+enum ElementType {
+    F16,
+    BF16,
+    F32,
+    F64,
+}
+
+impl ElementType {
+    fn size(self) -> usize {
+        match self {
+            Self::F16 | Self::BF16 => 2,
+            Self::F32 => 4,
+            Self::F64 => 8,
+        }
+    }
+}
+
+/// The activations of the neurons of one layer on a set of texts, as a memory mapped
+/// `[n_texts, n_tokens, n_neurons]` array, so arrays larger than memory can be ingested.
+pub struct ActivationArray {
+    mmap: Mmap,
+    /// Offset of the first element in the file.
+    offset: usize,
+    shape: [usize; 3],
+    element_type: ElementType,
+}
+
+impl ActivationArray {
+    /// Opens a `.npy` or `.safetensors` file. Safetensors files with several tensors need the
+    /// name of the tensor to read.
+    pub fn open<P: AsRef<Path>>(path: P, tensor_name: Option<&str>) -> Result<Self> {
+        let path = path.as_ref();
+        let file = File::open(path).with_context(|| format!("Failed to open file '{path:?}'."))?;
+        // SAFETY: The file is only read, and is not expected to change while it is ingested.
+        let mmap = unsafe { Mmap::map(&file) }
+            .with_context(|| format!("Failed to memory map file '{path:?}'."))?;
+        let (offset, shape, element_type) =
+            match path.extension().and_then(|extension| extension.to_str()) {
+                Some("npy") => Self::npy_layout(&mmap),
+                Some("safetensors") => Self::safetensors_layout(&mmap, tensor_name),
+                _ => bail!("Activation file '{path:?}' should be a .npy or .safetensors file."),
+            }
+            .with_context(|| format!("Failed to read activation file '{path:?}'."))?;
+
+        let shape: [usize; 3] = shape.try_into().map_err(|shape: Vec<usize>| {
+            anyhow::anyhow!(
+                "Activation array in '{path:?}' should have shape [n_texts, n_tokens, n_neurons], but has shape {shape:?}."
+            )
+        })?;
+        let size = shape
+            .iter()
+            .try_fold(element_type.size(), |size, &dim| size.checked_mul(dim));
+        ensure!(
+            size.and_then(|size| size.checked_add(offset))
+                .is_some_and(|end| end <= mmap.len()),
+            "Activation file '{path:?}' is truncated."
+        );
+        Ok(Self {
+            mmap,
+            offset,
+            shape,
+            element_type,
+        })
+    }
+
+    fn npy_layout(bytes: &[u8]) -> Result<(usize, Vec<usize>, ElementType)> {
+        let mut data = bytes;
+        let header = NpyHeader::from_reader(&mut data)?;
+        ensure!(
+            header.order() == Order::C,
+            "Only arrays in C order are supported."
+        );
+        let element_type = match header.dtype() {
+            DType::Plain(type_str) => match type_str.to_string().as_str() {
+                "<f2" => ElementType::F16,
+                "<f4" => ElementType::F32,
+                "<f8" => ElementType::F64,
+                type_str => {
+                    bail!("Unsupported element type '{type_str}'. Should be a little endian float.")
+                }
+            },
+            dtype => bail!(
+                "Unsupported element type {}. Should be a little endian float.",
+                dtype.descr()
+            ),
+        };
+        let shape = header.shape().iter().map(|&size| size as usize).collect();
+        Ok((bytes.len() - data.len(), shape, element_type))
+    }
+
+    fn safetensors_layout(
+        bytes: &[u8],
+        tensor_name: Option<&str>,
+    ) -> Result<(usize, Vec<usize>, ElementType)> {
+        let tensors = SafeTensors::deserialize(bytes)?;
+        let tensor = match tensor_name {
+            Some(tensor_name) => tensors
+                .tensor(tensor_name)
+                .with_context(|| format!("No tensor named '{tensor_name}'."))?,
+            None => {
+                let mut names = tensors.names();
+                ensure!(
+                    names.len() == 1,
+                    "File contains {} tensors, so the tensor to read must be named. Tensors: {names:?}",
+                    names.len()
+                );
+                tensors.tensor(names.remove(0))?
+            }
+        };
+        let element_type = match tensor.dtype() {
+            Dtype::F16 => ElementType::F16,
+            Dtype::BF16 => ElementType::BF16,
+            Dtype::F32 => ElementType::F32,
+            Dtype::F64 => ElementType::F64,
+            dtype => bail!("Unsupported element type {dtype:?}. Should be a float."),
+        };
+        let offset = tensor.data().as_ptr() as usize - bytes.as_ptr() as usize;
+        Ok((offset, tensor.shape().to_vec(), element_type))
+    }
+
+    pub fn num_texts(&self) -> usize {
+        self.shape[0]
+    }
+
+    pub fn num_tokens(&self) -> usize {
+        self.shape[1]
+    }
+
+    pub fn num_neurons(&self) -> usize {
+        self.shape[2]
+    }
+
+    fn decode(&self, bytes: &[u8]) -> f32 {
+        match self.element_type {
+            ElementType::F16 => f16::from_le_bytes([bytes[0], bytes[1]]).to_f32(),
+            ElementType::BF16 => bf16::from_le_bytes([bytes[0], bytes[1]]).to_f32(),
+            ElementType::F32 => f32::from_le_bytes(bytes.try_into().unwrap()),
+            ElementType::F64 => f64::from_le_bytes(bytes.try_into().unwrap()) as f32,
+        }
+    }
+
+    fn element_offset(&self, text_index: usize, token_index: usize, neuron: usize) -> usize {
+        let [_, num_tokens, num_neurons] = self.shape;
+        self.offset
+            + ((text_index * num_tokens + token_index) * num_neurons + neuron)
+                * self.element_type.size()
+    }
+
+    /// Activations of all neurons on the given token, converted to `f32`.
+    fn token_activations(&self, text_index: usize, token_index: usize, activations: &mut [f32]) {
+        let size = self.element_type.size();
+        let start = self.element_offset(text_index, token_index, 0);
+        let bytes = &self.mmap[start..start + self.num_neurons() * size];
+        for (activation, bytes) in activations.iter_mut().zip(bytes.chunks_exact(size)) {
+            *activation = self.decode(bytes);
+        }
+    }
+
+    /// Activations of one neuron on the first `num_tokens` tokens of a text.
+    fn neuron_activations(&self, text_index: usize, num_tokens: usize, neuron: usize) -> Vec<f32> {
+        let size = self.element_type.size();
+        (0..num_tokens)
+            .map(|token_index| {
+                let start = self.element_offset(text_index, token_index, neuron);
+                self.decode(&self.mmap[start..start + size])
+            })
+            .collect()
+    }
+}
+
+/// Loads token sequences from a JSON file containing a list of lists of token strings.
+pub fn load_tokens<P: AsRef<Path>>(path: P) -> Result<Vec<Vec<String>>> {
+    let path = path.as_ref();
+    let text =
+        fs::read_to_string(path).with_context(|| format!("Failed to read file '{path:?}'."))?;
+    serde_json::from_str(&text)
+        .with_context(|| format!("Failed to parse token sequences from '{path:?}'."))
+}
+
+/// Orders text indices by the max activation of a neuron on them.
+#[derive(Clone, Copy, PartialEq)]
+struct TextActivation {
+    max_activation: f32,
+    text_index: usize,
+}
+
+impl Eq for TextActivation {}
+
+impl PartialOrd for TextActivation {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for TextActivation {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.max_activation
+            .total_cmp(&other.max_activation)
+            .then(other.text_index.cmp(&self.text_index))
+    }
+}
+
+/// Selects the `num_texts_per_neuron` texts each neuron of the layer activates most on, and writes
+/// them as neuroscope neuron pages along with the layer page.
+pub fn ingest_layer_to_files<P: AsRef<Path>, S: AsRef<str>>(
+    data_path: P,
+    model_name: S,
+    layer_index: u32,
+    tokens: &[Vec<String>],
+    activations: &ActivationArray,
+    num_texts_per_neuron: usize,
+) -> Result<NeuroscopeLayerPage> {
+    let data_path = data_path.as_ref();
+    let model_name = model_name.as_ref();
+    ensure!(
+        num_texts_per_neuron > 0,
+        "Text count per neuron should be at least 1."
+    );
+    ensure!(
+        tokens.len() == activations.num_texts(),
+        "There are {} token sequences, but activations for {} texts.",
+        tokens.len(),
+        activations.num_texts()
+    );
+    let num_neurons = activations.num_neurons();
+
+    // Texts can be shorter than the activation array, in which case the remaining activations are
+    // padding.
+    for (text_index, text_tokens) in tokens.iter().enumerate() {
+        ensure!(
+            !text_tokens.is_empty() && text_tokens.len() <= activations.num_tokens(),
+            "Text {text_index} has {} tokens, but should have between 1 and {}.",
+            text_tokens.len(),
+            activations.num_tokens()
+        );
+    }
+
+    let mut top_texts = vec![BinaryHeap::new(); num_neurons];
+    let mut max_activations = vec![f32::NEG_INFINITY; num_neurons];
+    let mut min_activations = vec![f32::INFINITY; num_neurons];
+    let mut text_max_activations = vec![f32::NEG_INFINITY; num_neurons];
+    let mut token_activations = vec![0.; num_neurons];
+    for (text_index, text_tokens) in tokens.iter().enumerate() {
+        text_max_activations.fill(f32::NEG_INFINITY);
+        for token_index in 0..text_tokens.len() {
+            activations.token_activations(text_index, token_index, &mut token_activations);
+            for ((max_activation, min_activation), &activation) in text_max_activations
+                .iter_mut()
+                .zip(&mut min_activations)
+                .zip(&token_activations)
+            {
+                *max_activation = max_activation.max(activation);
+                *min_activation = min_activation.min(activation);
+            }
+        }
+        for (neuron, &max_activation) in text_max_activations.iter().enumerate() {
+            max_activations[neuron] = max_activations[neuron].max(max_activation);
+            // Min-heap of the best texts so far.
+            top_texts[neuron].push(Reverse(TextActivation {
+                max_activation,
+                text_index,
+            }));
+            if top_texts[neuron].len() > num_texts_per_neuron {
+                top_texts[neuron].pop();
+            }
+        }
+    }
+
+    let dataset_path = data_path.join(model_name).join("neuroscope");
+    let encoding = Encoding::for_dataset(&dataset_path)?;
+    let mut important_neurons = Vec::with_capacity(num_neurons);
+    for (neuron, top_texts) in top_texts.into_iter().enumerate() {
+        let neuron_index = NeuronIndex {
+            layer: layer_index,
+            neuron: neuron as u32,
+        };
+        // Activations are colored on a scale symmetric around zero, like on neuroscope, unless
+        // they go further below zero than above it.
+        let max_range = max_activations[neuron].abs();
+        let activation_range = (min_activations[neuron].min(-max_range), max_range);
+        let texts = top_texts
+            .into_sorted_vec()
+            .into_iter()
+            .map(|Reverse(TextActivation { text_index, .. })| {
+                let text_tokens = &tokens[text_index];
+                Text::new(
+                    text_tokens.clone(),
+                    activations.neuron_activations(text_index, text_tokens.len(), neuron),
+                    text_index as u64,
+                    activation_range,
+                )
+            })
+            .collect::<Result<Vec<_>>>()?;
+        if let Some(first_text) = texts.first() {
+            important_neurons.push((
+                neuron_index,
+                first_text.max_activation() - first_text.min_activation(),
+            ));
+        }
+        NeuroscopeNeuronPage::new(neuron_index, texts).to_file_with_encoding(
+            neuron_data_path(data_path, model_name, neuron_index),
+            &encoding,
+        )?;
+    }
+
+    let layer_page = NeuroscopeLayerPage::new(important_neurons);
+    layer_page.to_file_with_encoding(
+        dataset_path
+            .join(format!("l{layer_index}"))
+            .with_extension("postcard"),
+        &encoding,
+    )?;
+    Ok(layer_page)
+}
+
+/// Writes the metadata and neuroscope pages of a model from the activations of each of its layers.
+pub fn ingest_model_to_files<P: AsRef<Path>>(
+    data_path: P,
+    model_metadata: &ModelMetadata,
+    tokens: &[Vec<String>],
+    layer_activations: &[ActivationArray],
+    num_texts_per_neuron: usize,
+) -> Result<()> {
+    let data_path = data_path.as_ref();
+    let model_name = model_metadata.name.as_str();
+    // Checked before anything is written, although every layer checks it too.
+    ensure!(
+        num_texts_per_neuron > 0,
+        "Text count per neuron should be at least 1."
+    );
+    ensure!(
+        model_metadata.layers.len() == layer_activations.len(),
+        "Model has {} layers, but activations for {} layers were given.",
+        model_metadata.layers.len(),
+        layer_activations.len()
+    );
+    model_metadata.to_file(data_path)?;
+
+    let mut neuron_importance = Vec::new();
+    for (layer_index, (layer_metadata, activations)) in model_metadata
+        .layers
+        .iter()
+        .zip(layer_activations)
+        .enumerate()
+    {
+        ensure!(
+            layer_metadata.num_neurons as usize == activations.num_neurons(),
+            "Layer {layer_index} has {} neurons, but its activations are for {} neurons.",
+            layer_metadata.num_neurons,
+            activations.num_neurons()
+        );
+        println!("Ingesting layer {layer_index}...");
+        let layer_page = ingest_layer_to_files(
+            data_path,
+            model_name,
+            layer_index as u32,
+            tokens,
+            activations,
+            num_texts_per_neuron,
+        )
+        .with_context(|| format!("Failed to ingest layer {layer_index}."))?;
+        neuron_importance.extend_from_slice(layer_page.important_neurons());
+    }
+
+    NeuroscopeModelPage::new(neuron_importance).to_file(
+        data_path
+            .join(model_name)
+            .join("neuroscope")
+            .join("model")
+            .with_extension("postcard"),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, path::PathBuf, process};
+
+    use super::*;
+
+    /// A directory of its own for every test, removed again when dropped.
+    struct TestDirectory(PathBuf);
+
+    impl TestDirectory {
+        fn new(name: &str) -> Self {
+            let path =
+                env::temp_dir().join(format!("neuronav-activations-{name}-{}", process::id()));
+            fs::create_dir_all(&path).unwrap();
+            Self(path)
+        }
+    }
+
+    impl Drop for TestDirectory {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    /// Writes a little endian `f32` array in the `.npy` format.
+    fn write_npy(path: &Path, shape: [usize; 3], values: &[f32]) {
+        let mut header = format!(
+            "{{'descr': '<f4', 'fortran_order': False, 'shape': ({}, {}, {}), }}",
+            shape[0], shape[1], shape[2]
+        );
+        while (10 + header.len() + 1) % 64 != 0 {
+            header.push(' ');
+        }
+        header.push('\n');
+        let mut bytes = b"\x93NUMPY\x01\x00".to_vec();
+        bytes.extend_from_slice(&(header.len() as u16).to_le_bytes());
+        bytes.extend_from_slice(header.as_bytes());
+        for value in values {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        fs::write(path, bytes).unwrap();
+    }
+
+    /// Four texts of two tokens on two neurons. The last text has a single token, so its second
+    /// token is padding that must not be ingested.
+    fn activations(directory: &TestDirectory) -> (Vec<Vec<String>>, ActivationArray) {
+        let tokens = [&["a", "b"][..], &["c", "d"], &["e", "f"], &["g"]]
+            .map(|text| text.iter().map(|&token| token.to_owned()).collect())
+            .to_vec();
+        let path = directory.0.join("activations.npy");
+        #[rustfmt::skip]
+        write_npy(&path, [4, 2, 2], &[
+            1., -1., 0., -2.,
+            3., -0.5, 2., -1.,
+            -1., -5., 3., -3.,
+            2., -4., 100., 100.,
+        ]);
+        (tokens, ActivationArray::open(&path, None).unwrap())
+    }
+
+    fn neuron_page(directory: &TestDirectory, neuron: u32) -> NeuroscopeNeuronPage {
+        NeuroscopeNeuronPage::from_file(neuron_data_path(
+            &directory.0,
+            "model",
+            NeuronIndex { layer: 1, neuron },
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn writes_the_top_texts_of_each_neuron() {
+        let directory = TestDirectory::new("top-texts");
+        let (tokens, activations) = activations(&directory);
+        let layer_page =
+            ingest_layer_to_files(&directory.0, "model", 1, &tokens, &activations, 2).unwrap();
+
+        // Ties go to the earlier text, and padding is ignored.
+        let page = neuron_page(&directory, 0);
+        let texts = page.texts();
+        assert_eq!(
+            texts.iter().map(Text::data_index).collect::<Vec<_>>(),
+            [1, 2]
+        );
+        assert_eq!(texts[0].tokens(), ["c", "d"]);
+        assert_eq!(texts[0].activations(), [3., 2.]);
+        assert_eq!(texts[1].activations(), [-1., 3.]);
+
+        let page = neuron_page(&directory, 1);
+        assert_eq!(
+            page.texts()
+                .iter()
+                .map(Text::data_index)
+                .collect::<Vec<_>>(),
+            [1, 0]
+        );
+
+        // Sorted by the activation range of the top text.
+        assert_eq!(
+            layer_page.important_neurons(),
+            [
+                (
+                    NeuronIndex {
+                        layer: 1,
+                        neuron: 1
+                    },
+                    0.5
+                ),
+                (
+                    NeuronIndex {
+                        layer: 1,
+                        neuron: 0
+                    },
+                    1.
+                )
+            ]
+        );
+        let layer_path = directory
+            .0
+            .join("model")
+            .join("neuroscope")
+            .join("l1.postcard");
+        assert_eq!(
+            NeuroscopeLayerPage::from_file(layer_path)
+                .unwrap()
+                .important_neurons(),
+            layer_page.important_neurons()
+        );
+    }
+
+    #[test]
+    fn colors_activations_symmetrically_unless_they_go_further_below_zero() {
+        let directory = TestDirectory::new("range");
+        let (tokens, activations) = activations(&directory);
+        ingest_layer_to_files(&directory.0, "model", 1, &tokens, &activations, 4).unwrap();
+
+        for (neuron, range) in [(0, (-3., 3.)), (1, (-5., 0.5))] {
+            for text in neuron_page(&directory, neuron).texts() {
+                assert_eq!((text.min_range(), text.max_range()), range);
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_a_text_count_of_zero() {
+        let directory = TestDirectory::new("zero");
+        let (tokens, activations) = activations(&directory);
+        assert!(ingest_layer_to_files(&directory.0, "model", 1, &tokens, &activations, 0).is_err());
+        assert!(!directory.0.join("model").exists());
+    }
+}
@@ -0,0 +1,314 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{ensure, Context, Result};
+use serde::Deserialize;
+
+use crate::data::{
+    storage::Encoding, ExplanationLayerPage, ExplanationNeuronPage, NeuronIndex, RankedNeuron,
+    ScoredActivationRecord, ScoredExplanation,
+};
+
+/// Layout of the explanation files of OpenAI's automated interpretability project, one per
+/// neuron. Only the fields that are imported are listed.
+#[derive(Deserialize)]
+struct NeuronSimulationResults {
+    neuron_id: NeuronId,
+    scored_explanations: Vec<ScoredExplanationJson>,
+}
+
+#[derive(Deserialize)]
+struct NeuronId {
+    layer_index: u32,
+    neuron_index: u32,
+}
+
+#[derive(Deserialize)]
+struct ScoredExplanationJson {
+    explanation: String,
+    scored_simulation: ScoredSimulation,
+}
+
+#[derive(Deserialize)]
+struct ScoredSimulation {
+    scored_sequence_simulations: Vec<ScoredSequenceSimulation>,
+    ev_correlation_score: Option<f32>,
+    rsquared_score: Option<f32>,
+    absolute_dev_explained_score: Option<f32>,
+}
+
+#[derive(Deserialize)]
+struct ScoredSequenceSimulation {
+    simulation: SequenceSimulation,
+    true_activations: Vec<f32>,
+    ev_correlation_score: Option<f32>,
+}
+
+#[derive(Deserialize)]
+struct SequenceSimulation {
+    tokens: Vec<String>,
+    expected_activations: Vec<f32>,
+}
+
+impl NeuronSimulationResults {
+    fn into_page(self) -> Result<ExplanationNeuronPage> {
+        let neuron_index = NeuronIndex {
+            layer: self.neuron_id.layer_index,
+            neuron: self.neuron_id.neuron_index,
+        };
+        let explanations = self
+            .scored_explanations
+            .into_iter()
+            .map(|scored_explanation| {
+                let ScoredExplanationJson {
+                    explanation,
+                    scored_simulation,
+                } = scored_explanation;
+                let records = scored_simulation
+                    .scored_sequence_simulations
+                    .into_iter()
+                    .enumerate()
+                    .map(|(index, sequence)| {
+                        ScoredActivationRecord::new(
+                            sequence.simulation.tokens,
+                            sequence.true_activations,
+                            sequence.simulation.expected_activations,
+                            sequence.ev_correlation_score,
+                        )
+                        .with_context(|| format!("Invalid scored sequence {index}."))
+                    })
+                    .collect::<Result<Vec<_>>>()
+                    .with_context(|| format!("Invalid scores for explanation '{explanation}'."))?;
+                Ok(ScoredExplanation {
+                    explanation,
+                    ev_correlation_score: scored_simulation.ev_correlation_score,
+                    rsquared_score: scored_simulation.rsquared_score,
+                    absolute_dev_explained_score: scored_simulation.absolute_dev_explained_score,
+                    records,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(ExplanationNeuronPage::new(neuron_index, explanations))
+    }
+}
+
+/// Python writes scores that could not be computed as `NaN`, which is not valid JSON. Replaces
+/// such non-finite numbers outside of strings with `null`.
+fn replace_non_finite_numbers(json: &str) -> String {
+    let mut result = String::with_capacity(json.len());
+    let mut rest = json;
+    let mut in_string = false;
+    while let Some(char) = rest.chars().next() {
+        if in_string {
+            if char == '\\' {
+                let escaped_length = rest[1..].chars().next().map_or(0, char::len_utf8);
+                result.push_str(&rest[..1 + escaped_length]);
+                rest = &rest[1 + escaped_length..];
+                continue;
+            }
+            in_string = char != '"';
+        } else if char == '"' {
+            in_string = true;
+        } else if let Some(literal) = ["NaN", "-Infinity", "Infinity"]
+            .into_iter()
+            .find(|literal| rest.starts_with(literal))
+        {
+            result.push_str("null");
+            rest = &rest[literal.len()..];
+            continue;
+        }
+        result.push(char);
+        rest = &rest[char.len_utf8()..];
+    }
+    result
+}
+
+fn parse_explanation_file(path: &Path) -> Result<ExplanationNeuronPage> {
+    let text =
+        fs::read_to_string(path).with_context(|| format!("Failed to read file '{path:?}'."))?;
+    // The files are JSON lines, but only ever contain the results of one neuron.
+    let results: NeuronSimulationResults = serde_json::from_str(&replace_non_finite_numbers(&text))
+        .with_context(|| format!("Failed to parse explanation file '{path:?}'."))?;
+    results
+        .into_page()
+        .with_context(|| format!("Invalid explanation file '{path:?}'."))
+}
+
+/// Returns the neuron files in the explanation directory, which is laid out like the published
+/// data set as `{layer_index}/{neuron_index}.jsonl`.
+fn explanation_files(source_path: &Path) -> Result<BTreeMap<NeuronIndex, PathBuf>> {
+    let mut files = BTreeMap::new();
+    for layer_entry in fs::read_dir(source_path)
+        .with_context(|| format!("Failed to read explanation directory '{source_path:?}'."))?
+    {
+        let layer_path = layer_entry?.path();
+        let Some(layer_index) = file_index(&layer_path, None) else {
+            continue;
+        };
+        if !layer_path.is_dir() {
+            continue;
+        }
+        for neuron_entry in fs::read_dir(&layer_path)
+            .with_context(|| format!("Failed to read directory '{layer_path:?}'."))?
+        {
+            let neuron_path = neuron_entry?.path();
+            if let Some(neuron_index) = file_index(&neuron_path, Some("jsonl")) {
+                files.insert(
+                    NeuronIndex {
+                        layer: layer_index,
+                        neuron: neuron_index,
+                    },
+                    neuron_path,
+                );
+            }
+        }
+    }
+    Ok(files)
+}
+
+fn file_index(path: &Path, extension: Option<&str>) -> Option<u32> {
+    if path.extension().and_then(|extension| extension.to_str()) != extension {
+        return None;
+    }
+    path.file_stem()?.to_str()?.parse().ok()
+}
+
+/// Imports the explanations in `source_path` as pages of the model's explanation service, and
+/// writes a page per layer ranking its neurons by explanation score. Returns the number of neurons
+/// imported.
+pub fn import_explanations<P: AsRef<Path>, Q: AsRef<Path>, S: AsRef<str>>(
+    source_path: P,
+    data_path: Q,
+    model_name: S,
+) -> Result<usize> {
+    let source_path = source_path.as_ref();
+    let data_path = data_path.as_ref();
+    let model_name = model_name.as_ref();
+
+    let files = explanation_files(source_path)?;
+    let encoding = Encoding::for_dataset(data_path.join(model_name).join("explanation"))?;
+    let mut layers: BTreeMap<u32, Vec<RankedNeuron>> = BTreeMap::new();
+    for (neuron_index, path) in &files {
+        let page = parse_explanation_file(path)?;
+        ensure!(
+            page.neuron_index() == *neuron_index,
+            "Explanation file '{path:?}' contains neuron {}.",
+            page.neuron_index()
+        );
+        page.to_file_with_encoding(
+            ExplanationNeuronPage::path(data_path, model_name, *neuron_index),
+            &encoding,
+        )?;
+        layers
+            .entry(neuron_index.layer)
+            .or_default()
+            .extend(RankedNeuron::from_page(&page));
+    }
+    for (layer_index, ranked_neurons) in layers {
+        ExplanationLayerPage::new(ranked_neurons).to_file_with_encoding(
+            ExplanationLayerPage::path(data_path, model_name, layer_index),
+            &encoding,
+        )?;
+    }
+    Ok(files.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, process};
+
+    use super::*;
+
+    fn explanation_file(layer_index: u32, neuron_index: u32) -> String {
+        format!(
+            r#"{{"neuron_id": {{"layer_index": {layer_index}, "neuron_index": {neuron_index}}},
+            "scored_explanations": [
+                {{"explanation": "NaN values", "scored_simulation": {{
+                    "scored_sequence_simulations": [{{
+                        "simulation": {{"tokens": ["a", "b"], "expected_activations": [0.0, 1.0]}},
+                        "true_activations": [0.5, 2.0],
+                        "ev_correlation_score": NaN
+                    }}],
+                    "ev_correlation_score": NaN,
+                    "rsquared_score": -Infinity,
+                    "absolute_dev_explained_score": Infinity
+                }}}},
+                {{"explanation": "letters", "scored_simulation": {{
+                    "scored_sequence_simulations": [],
+                    "ev_correlation_score": 0.5,
+                    "rsquared_score": 0.25,
+                    "absolute_dev_explained_score": null
+                }}}}
+            ]}}"#
+        )
+    }
+
+    #[test]
+    fn replaces_non_finite_numbers_outside_of_strings() {
+        assert_eq!(
+            replace_non_finite_numbers(
+                r#"{"a": NaN, "NaN": "Infinity \" NaN \\", "b": [-Infinity, Infinity, 1.5]}"#
+            ),
+            r#"{"a": null, "NaN": "Infinity \" NaN \\", "b": [null, null, 1.5]}"#
+        );
+    }
+
+    #[test]
+    fn imports_explanations_with_non_finite_scores() {
+        let path = env::temp_dir().join(format!("neuronav-explanations-{}", process::id()));
+        let source_path = path.join("source");
+        let data_path = path.join("data");
+        let write = |layer_index: u32, neuron_index: u32, text: &str| {
+            let layer_path = source_path.join(layer_index.to_string());
+            fs::create_dir_all(&layer_path).unwrap();
+            fs::write(layer_path.join(format!("{neuron_index}.jsonl")), text).unwrap();
+        };
+        write(0, 3, &explanation_file(0, 3));
+        write(1, 0, &explanation_file(1, 0));
+        // Files that are not neuron files are skipped.
+        fs::write(source_path.join("1").join("notes.txt"), "").unwrap();
+
+        let num_imported = import_explanations(&source_path, &data_path, "model");
+        let neuron_index = NeuronIndex {
+            layer: 0,
+            neuron: 3,
+        };
+        let page = ExplanationNeuronPage::from_file(ExplanationNeuronPage::path(
+            &data_path,
+            "model",
+            neuron_index,
+        ));
+        let layer_page =
+            ExplanationLayerPage::from_file(ExplanationLayerPage::path(&data_path, "model", 0));
+        write(2, 0, &explanation_file(2, 1));
+        let mismatched = import_explanations(&source_path, &data_path, "model");
+        let _ = fs::remove_dir_all(&path);
+
+        assert_eq!(num_imported.unwrap(), 2);
+        let page = page.unwrap();
+        assert_eq!(page.neuron_index(), neuron_index);
+        // Unscored explanations are ranked last.
+        let [best, unscored] = page.explanations() else {
+            panic!("Both explanations should be imported.");
+        };
+        assert_eq!(
+            (best.explanation.as_str(), best.ev_correlation_score),
+            ("letters", Some(0.5))
+        );
+        assert_eq!(unscored.explanation, "NaN values");
+        assert_eq!(
+            (
+                unscored.ev_correlation_score,
+                unscored.rsquared_score,
+                unscored.absolute_dev_explained_score,
+                unscored.records[0].ev_correlation_score,
+            ),
+            (None, None, None, None)
+        );
+        assert_eq!(layer_page.unwrap().ranked_neurons()[0].score, Some(0.5));
+        assert!(mismatched.is_err());
+    }
+}
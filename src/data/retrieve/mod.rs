@@ -1 +1,3 @@
+pub mod activations;
+pub mod explanation;
 pub mod neuroscope;
@@ -7,6 +7,7 @@ use std::{
 
 use crate::data::{
     neuroscope::{NeuroscopeLayerPage, NeuroscopeModelPage},
+    storage::Encoding,
     LayerMetadata, ModelMetadata, NeuronIndex, NeuroscopeNeuronPage,
 };
 
@@ -59,6 +60,7 @@ pub async fn scrape_neuron_page_to_file<S: AsRef<str>, P: AsRef<Path>>(
     data_path: P,
     model: S,
     neuron_index: NeuronIndex,
+    encoding: &Encoding,
 ) -> Result<f32> {
     let model = model.as_ref();
     let page_path = neuron_data_path(data_path, model, neuron_index);
@@ -66,12 +68,12 @@ pub async fn scrape_neuron_page_to_file<S: AsRef<str>, P: AsRef<Path>>(
         NeuroscopeNeuronPage::from_file(page_path).with_context(|| format!("File for neuroscape page exists, but cannot be loaded. Neuron {neuron_index} in model '{model}'."))?
     } else {
         let page = scrape_neuron_page(model, neuron_index).await?;
-        page.to_file(page_path).with_context(|| format!("Failed to write neuroscope page to file for neuron {neuron_index} in model '{model}'."))?;
+        page.to_file_with_encoding(page_path, encoding).with_context(|| format!("Failed to write neuroscope page to file for neuron {neuron_index} in model '{model}'."))?;
         page
     };
     let first_text = page
         .texts()
-        .get(0)
+        .first()
         .with_context(|| format!("Failed to get first text from neuroscope page for neuron {neuron_index} in model '{model}'."))?;
     let activation_range = first_text.max_activation() - first_text.min_activation();
 
@@ -134,6 +136,8 @@ pub async fn scrape_layer_to_files<P: AsRef<Path>, S: AsRef<str>>(
     num_neurons: u32,
 ) -> Result<NeuroscopeLayerPage> {
     let data_path = data_path.as_ref();
+    let dataset_path = data_path.join(model.as_ref()).join("neuroscope");
+    let encoding = Arc::new(Encoding::for_dataset(&dataset_path)?);
 
     let mut join_set = JoinSet::new();
 
@@ -151,8 +155,10 @@ pub async fn scrape_layer_to_files<P: AsRef<Path>, S: AsRef<str>>(
 
         let model = model.as_ref().to_owned();
         let data_path = data_path.to_owned();
+        let encoding = Arc::clone(&encoding);
         join_set.spawn(async move {
-            let result = scrape_neuron_page_to_file(data_path, model, neuron_index).await;
+            let result =
+                scrape_neuron_page_to_file(data_path, model, neuron_index, &encoding).await;
             drop(permit);
             Ok::<_, anyhow::Error>((neuron_index, result?))
         });
@@ -179,12 +185,10 @@ pub async fn scrape_layer_to_files<P: AsRef<Path>, S: AsRef<str>>(
     }
 
     let layer_page = NeuroscopeLayerPage::new(max_activations);
-    let layer_page_path = data_path
-        .join(model.as_ref())
-        .join("neuroscope")
+    let layer_page_path = dataset_path
         .join(format!("l{layer_index}"))
         .with_extension("postcard");
-    layer_page.to_file(layer_page_path)?;
+    layer_page.to_file_with_encoding(layer_page_path, &encoding)?;
 
     assert_eq!(
         num_completed, num_neurons,
@@ -0,0 +1,649 @@
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    fs,
+    io::{Read, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{Arc, Mutex, OnceLock},
+};
+
+use anyhow::{bail, Context, Result};
+use flate2::{bufread::DeflateDecoder, write::DeflateEncoder, Compression};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use thiserror::Error;
+use zstd::dict::{DecoderDictionary, EncoderDictionary};
+
+const MAGIC: &[u8; 4] = b"NNAV";
+/// Magic, page kind, codec, schema version and checksum.
+const HEADER_SIZE: usize = 12;
+/// Schema version of files written before headers were introduced.
+pub const LEGACY_SCHEMA_VERSION: u16 = 0;
+
+const STORAGE_CONFIG_FILE_NAME: &str = "storage.json";
+
+/// Values of the codec byte in page headers. Pages compressed with a zstd dictionary start their
+/// payload with the id of the dictionary.
+const CODEC_NONE: u8 = 0;
+const CODEC_DEFLATE: u8 = 1;
+const CODEC_ZSTD: u8 = 2;
+const CODEC_ZSTD_DICTIONARY: u8 = 3;
+
+#[derive(Debug, Error)]
+pub enum StorageError {
+    #[error("File has no page header and could not be read as a legacy page file either.")]
+    UnknownFormat,
+    #[error("File is too short to contain a page header.")]
+    Truncated,
+    #[error("Unknown page kind {0}.")]
+    UnknownPageKind(u8),
+    #[error("Expected a {expected} page, but found a {found} page.")]
+    WrongPageKind { expected: PageKind, found: PageKind },
+    #[error("Unknown compression codec {0}.")]
+    UnknownCodec(u8),
+    #[error("{kind} page has schema version {version}, but only versions up to {supported} are supported.")]
+    UnsupportedVersion {
+        kind: PageKind,
+        version: u16,
+        supported: u16,
+    },
+    #[error("Checksum mismatch: header says {expected:#010x}, but contents hash to {found:#010x}. The file is corrupted.")]
+    ChecksumMismatch { expected: u32, found: u32 },
+    #[error("Page is compressed with zstd dictionary {0:08x}, which is not in the dataset.")]
+    MissingDictionary(u32),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PageKind {
+    NeuroscopeNeuron,
+    NeuroscopeLayer,
+    NeuroscopeModel,
+    NeuronViewer,
+    ExplanationNeuron,
+    ExplanationLayer,
+}
+
+impl PageKind {
+    fn to_u8(self) -> u8 {
+        match self {
+            Self::NeuroscopeNeuron => 1,
+            Self::NeuroscopeLayer => 2,
+            Self::NeuroscopeModel => 3,
+            Self::NeuronViewer => 4,
+            Self::ExplanationNeuron => 5,
+            Self::ExplanationLayer => 6,
+        }
+    }
+
+    fn from_u8(value: u8) -> Result<Self, StorageError> {
+        match value {
+            1 => Ok(Self::NeuroscopeNeuron),
+            2 => Ok(Self::NeuroscopeLayer),
+            3 => Ok(Self::NeuroscopeModel),
+            4 => Ok(Self::NeuronViewer),
+            5 => Ok(Self::ExplanationNeuron),
+            6 => Ok(Self::ExplanationLayer),
+            _ => Err(StorageError::UnknownPageKind(value)),
+        }
+    }
+}
+
+impl Display for PageKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::NeuroscopeNeuron => "neuroscope neuron",
+            Self::NeuroscopeLayer => "neuroscope layer",
+            Self::NeuroscopeModel => "neuroscope model",
+            Self::NeuronViewer => "neuron viewer",
+            Self::ExplanationNeuron => "explanation neuron",
+            Self::ExplanationLayer => "explanation layer",
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Codec {
+    None,
+    #[default]
+    Deflate,
+    Zstd,
+}
+
+impl Codec {
+    pub const ALL: [Codec; 3] = [Codec::None, Codec::Deflate, Codec::Zstd];
+
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Self::None => "none",
+            Self::Deflate => "deflate",
+            Self::Zstd => "zstd",
+        }
+    }
+
+    fn default_level(self) -> i32 {
+        match self {
+            Self::None => 0,
+            Self::Deflate => Compression::default().level() as i32,
+            Self::Zstd => zstd::DEFAULT_COMPRESSION_LEVEL,
+        }
+    }
+}
+
+impl Display for Codec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.to_str())
+    }
+}
+
+impl FromStr for Codec {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "none" => Ok(Self::None),
+            "deflate" => Ok(Self::Deflate),
+            "zstd" => Ok(Self::Zstd),
+            _ => bail!("Invalid codec: '{s}'. Should be one of 'none', 'deflate' or 'zstd'."),
+        }
+    }
+}
+
+/// How the pages of a dataset, i.e. a directory of pages, are written. Stored as `storage.json`
+/// in the dataset directory. Pages can always be read regardless of the configuration, as their
+/// headers describe how they were written.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct StorageConfig {
+    pub codec: Codec,
+    /// Compression level. The codec's default level is used if not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub level: Option<i32>,
+    /// Id of the zstd dictionary to compress pages with. Only used with the zstd codec.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dictionary_id: Option<u32>,
+}
+
+impl StorageConfig {
+    pub fn path<P: AsRef<Path>>(dataset_path: P) -> PathBuf {
+        dataset_path.as_ref().join(STORAGE_CONFIG_FILE_NAME)
+    }
+
+    /// Loads the configuration of the dataset, or the default configuration if it has none.
+    pub fn from_dataset<P: AsRef<Path>>(dataset_path: P) -> Result<Self> {
+        let path = Self::path(dataset_path);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let text = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read storage config '{path:?}'."))?;
+        serde_json::from_str(&text)
+            .with_context(|| format!("Failed to parse storage config '{path:?}'."))
+    }
+
+    pub fn to_dataset<P: AsRef<Path>>(&self, dataset_path: P) -> Result<()> {
+        let path = Self::path(dataset_path);
+        let text = serde_json::to_string_pretty(self)?;
+        fs::write(&path, text)
+            .with_context(|| format!("Failed to write storage config '{path:?}'."))
+    }
+}
+
+/// A zstd dictionary trained on the pages of a dataset. Dictionaries are stored next to the pages
+/// in files named after their id, so pages compressed with an older dictionary stay readable.
+pub struct Dictionary {
+    id: u32,
+    bytes: Vec<u8>,
+}
+
+impl Dictionary {
+    pub fn train<S: AsRef<[u8]>>(samples: &[S], max_size: usize) -> Result<Self> {
+        let bytes = zstd::dict::from_samples(samples, max_size)
+            .context("Failed to train zstd dictionary.")?;
+        Ok(Self {
+            id: crc32fast::hash(&bytes),
+            bytes,
+        })
+    }
+
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    pub fn path<P: AsRef<Path>>(dataset_path: P, id: u32) -> PathBuf {
+        dataset_path.as_ref().join(format!("zstd-{id:08x}.dict"))
+    }
+
+    /// Returns the id of the dictionary stored in the file, or `None` if it is not a dictionary.
+    pub fn id_of_file_name(file_name: &str) -> Option<u32> {
+        file_name
+            .strip_prefix("zstd-")
+            .and_then(|file_name| file_name.strip_suffix(".dict"))
+            .filter(|id| id.len() == 8)
+            .and_then(|id| u32::from_str_radix(id, 16).ok())
+    }
+
+    pub fn load<P: AsRef<Path>>(dataset_path: P, id: u32) -> Result<Self> {
+        let path = Self::path(dataset_path, id);
+        if !path.exists() {
+            return Err(StorageError::MissingDictionary(id).into());
+        }
+        let bytes =
+            fs::read(&path).with_context(|| format!("Failed to read dictionary '{path:?}'."))?;
+        let found_id = crc32fast::hash(&bytes);
+        if found_id != id {
+            return Err(StorageError::ChecksumMismatch {
+                expected: id,
+                found: found_id,
+            })
+            .with_context(|| format!("Dictionary '{path:?}' is corrupted."));
+        }
+        Ok(Self { id, bytes })
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, dataset_path: P) -> Result<()> {
+        let path = Self::path(dataset_path, self.id);
+        fs::write(&path, &self.bytes)
+            .with_context(|| format!("Failed to write dictionary '{path:?}'."))
+    }
+}
+
+/// Dictionaries never change once written, so they are prepared for decoding once per process.
+fn decoder_dictionary(dataset_path: &Path, id: u32) -> Result<Arc<DecoderDictionary<'static>>> {
+    static DICTIONARIES: OnceLock<Mutex<HashMap<PathBuf, Arc<DecoderDictionary<'static>>>>> =
+        OnceLock::new();
+    let path = Dictionary::path(dataset_path, id);
+    let mut dictionaries = DICTIONARIES
+        .get_or_init(Default::default)
+        .lock()
+        .expect("Dictionary cache lock poisoned.");
+    if let Some(dictionary) = dictionaries.get(&path) {
+        return Ok(Arc::clone(dictionary));
+    }
+    let dictionary = Dictionary::load(dataset_path, id)?;
+    let dictionary = Arc::new(DecoderDictionary::copy(&dictionary.bytes));
+    dictionaries.insert(path, Arc::clone(&dictionary));
+    Ok(dictionary)
+}
+
+/// Codec, level and dictionary used to write pages.
+pub struct Encoding {
+    codec: Codec,
+    level: i32,
+    dictionary: Option<(u32, EncoderDictionary<'static>)>,
+}
+
+impl Encoding {
+    pub fn new(codec: Codec, level: Option<i32>, dictionary: Option<&Dictionary>) -> Result<Self> {
+        let level = level.unwrap_or_else(|| codec.default_level());
+        match codec {
+            Codec::None => {}
+            Codec::Deflate => {
+                if !(0..=9).contains(&level) {
+                    bail!("Deflate compression level should be between 0 and 9, but is {level}.");
+                }
+            }
+            Codec::Zstd => {
+                if !zstd::compression_level_range().contains(&level) {
+                    bail!(
+                        "Zstd compression level should be in {:?}, but is {level}.",
+                        zstd::compression_level_range()
+                    );
+                }
+            }
+        }
+        if dictionary.is_some() && codec != Codec::Zstd {
+            bail!("Dictionaries are only supported by the zstd codec, not {codec}.");
+        }
+        let dictionary = dictionary.map(|dictionary| {
+            (
+                dictionary.id,
+                EncoderDictionary::copy(&dictionary.bytes, level),
+            )
+        });
+        Ok(Self {
+            codec,
+            level,
+            dictionary,
+        })
+    }
+
+    /// The encoding configured for the dataset in its `storage.json`.
+    pub fn for_dataset<P: AsRef<Path>>(dataset_path: P) -> Result<Self> {
+        let dataset_path = dataset_path.as_ref();
+        let StorageConfig {
+            codec,
+            level,
+            dictionary_id,
+        } = StorageConfig::from_dataset(dataset_path)?;
+        let dictionary = match (codec, dictionary_id) {
+            (Codec::Zstd, Some(id)) => Some(Dictionary::load(dataset_path, id)?),
+            _ => None,
+        };
+        Self::new(codec, level, dictionary.as_ref())
+    }
+
+    pub fn codec(&self) -> Codec {
+        self.codec
+    }
+
+    /// Returns the codec byte for the header and the compressed payload.
+    fn compress(&self, data: &[u8]) -> Result<(u8, Vec<u8>)> {
+        match (self.codec, &self.dictionary) {
+            (Codec::None, _) => Ok((CODEC_NONE, data.to_vec())),
+            (Codec::Deflate, _) => {
+                let mut encoder =
+                    DeflateEncoder::new(Vec::new(), Compression::new(self.level as u32));
+                encoder.write_all(data)?;
+                Ok((CODEC_DEFLATE, encoder.finish()?))
+            }
+            (Codec::Zstd, None) => Ok((CODEC_ZSTD, zstd::bulk::compress(data, self.level)?)),
+            (Codec::Zstd, Some((id, dictionary))) => {
+                let mut payload = id.to_le_bytes().to_vec();
+                let mut compressor = zstd::bulk::Compressor::with_prepared_dictionary(dictionary)?;
+                payload.extend(compressor.compress(data)?);
+                Ok((CODEC_ZSTD_DICTIONARY, payload))
+            }
+        }
+    }
+}
+
+impl Default for Encoding {
+    fn default() -> Self {
+        Self::new(Codec::default(), None, None).expect("Default encoding should be valid.")
+    }
+}
+
+fn decompress(codec: u8, payload: &[u8], dataset_path: &Path) -> Result<Vec<u8>> {
+    let mut data = Vec::new();
+    match codec {
+        CODEC_NONE => data.extend_from_slice(payload),
+        CODEC_DEFLATE => {
+            DeflateDecoder::new(payload).read_to_end(&mut data)?;
+        }
+        CODEC_ZSTD => {
+            zstd::stream::Decoder::with_buffer(payload)?.read_to_end(&mut data)?;
+        }
+        CODEC_ZSTD_DICTIONARY => {
+            if payload.len() < 4 {
+                return Err(StorageError::Truncated.into());
+            }
+            let id = u32::from_le_bytes([payload[0], payload[1], payload[2], payload[3]]);
+            let dictionary = decoder_dictionary(dataset_path, id)?;
+            zstd::stream::Decoder::with_prepared_dictionary(&payload[4..], &dictionary)?
+                .read_to_end(&mut data)?;
+        }
+        _ => return Err(StorageError::UnknownCodec(codec).into()),
+    }
+    Ok(data)
+}
+
+/// A page that is stored on disk with a header describing its kind, schema version, codec and
+/// checksum.
+pub trait StoredPage: Serialize + DeserializeOwned {
+    const KIND: PageKind;
+    /// Version of the serialized layout of the page. Must be increased whenever the layout
+    /// changes, along with a migration from the old version in
+    /// [`StoredPage::deserialize_payload`].
+    const SCHEMA_VERSION: u16;
+
+    /// Deserializes a payload written with the given schema version, which is at most
+    /// [`StoredPage::SCHEMA_VERSION`]. Payloads of older versions must be migrated here.
+    fn deserialize_payload(version: u16, payload: &[u8]) -> Result<Self>;
+
+    /// Decodes a file written before headers were introduced.
+    fn from_legacy_bytes(bytes: &[u8]) -> Result<Self> {
+        let mut payload = Vec::new();
+        DeflateDecoder::new(bytes).read_to_end(&mut payload)?;
+        Self::deserialize_payload(LEGACY_SCHEMA_VERSION, &payload)
+    }
+}
+
+pub fn to_bytes<T: StoredPage>(page: &T, encoding: &Encoding) -> Result<Vec<u8>> {
+    let data = postcard::to_allocvec(page)
+        .with_context(|| format!("Failed to serialize {} page.", T::KIND))?;
+    let (codec, payload) = encoding
+        .compress(&data)
+        .with_context(|| format!("Failed to compress {} page.", T::KIND))?;
+
+    let mut bytes = Vec::with_capacity(HEADER_SIZE + payload.len());
+    bytes.extend_from_slice(MAGIC);
+    bytes.push(T::KIND.to_u8());
+    bytes.push(codec);
+    bytes.extend_from_slice(&T::SCHEMA_VERSION.to_le_bytes());
+    bytes.extend_from_slice(&crc32fast::hash(&payload).to_le_bytes());
+    bytes.extend_from_slice(&payload);
+    Ok(bytes)
+}
+
+/// Returns the id of the dictionary the page is compressed with, or `None` if it is not compressed
+/// with one. Only the header and the start of the payload are read.
+pub fn dictionary_id(bytes: &[u8]) -> Option<u32> {
+    if !bytes.starts_with(MAGIC) || bytes.get(5) != Some(&CODEC_ZSTD_DICTIONARY) {
+        return None;
+    }
+    let id = bytes.get(HEADER_SIZE..HEADER_SIZE + 4)?;
+    Some(u32::from_le_bytes(id.try_into().unwrap()))
+}
+
+/// Returns the id of the dictionary the page in the file is compressed with, reading only the
+/// start of the file.
+pub fn dictionary_id_of_file<P: AsRef<Path>>(path: P) -> Result<Option<u32>> {
+    let path = path.as_ref();
+    let mut bytes = Vec::with_capacity(HEADER_SIZE + 4);
+    fs::File::open(path)
+        .and_then(|file| file.take((HEADER_SIZE + 4) as u64).read_to_end(&mut bytes))
+        .with_context(|| format!("Failed to read file '{path:?}'."))?;
+    Ok(dictionary_id(&bytes))
+}
+
+/// Decodes a page stored in the given dataset directory, in which its dictionary is looked up if
+/// it has one.
+pub fn from_bytes<T: StoredPage, P: AsRef<Path>>(bytes: &[u8], dataset_path: P) -> Result<T> {
+    if !bytes.starts_with(MAGIC) {
+        return T::from_legacy_bytes(bytes)
+            .map_err(|error| error.context(StorageError::UnknownFormat));
+    }
+    if bytes.len() < HEADER_SIZE {
+        return Err(StorageError::Truncated.into());
+    }
+
+    let kind = PageKind::from_u8(bytes[4])?;
+    if kind != T::KIND {
+        return Err(StorageError::WrongPageKind {
+            expected: T::KIND,
+            found: kind,
+        }
+        .into());
+    }
+    let codec = bytes[5];
+    let version = u16::from_le_bytes([bytes[6], bytes[7]]);
+    if version > T::SCHEMA_VERSION {
+        return Err(StorageError::UnsupportedVersion {
+            kind,
+            version,
+            supported: T::SCHEMA_VERSION,
+        }
+        .into());
+    }
+    let checksum = u32::from_le_bytes([bytes[8], bytes[9], bytes[10], bytes[11]]);
+    let payload = &bytes[HEADER_SIZE..];
+    let found_checksum = crc32fast::hash(payload);
+    if checksum != found_checksum {
+        return Err(StorageError::ChecksumMismatch {
+            expected: checksum,
+            found: found_checksum,
+        }
+        .into());
+    }
+
+    let data = decompress(codec, payload, dataset_path.as_ref())
+        .with_context(|| format!("Failed to decompress {kind} page."))?;
+    T::deserialize_payload(version, &data).with_context(|| {
+        format!("Failed to deserialize {kind} page with schema version {version}.")
+    })
+}
+
+fn dataset_path(path: &Path) -> Result<&Path> {
+    path.parent()
+        .with_context(|| format!("Invalid path '{path:?}'"))
+}
+
+/// Writes the page with the encoding configured for the dataset directory it is written to.
+pub fn to_file<T: StoredPage, P: AsRef<Path>>(page: &T, path: P) -> Result<()> {
+    let path = path.as_ref();
+    let dataset_path = dataset_path(path)?;
+    fs::create_dir_all(dataset_path)
+        .with_context(|| format!("Failed to create directory for '{path:?}'"))?;
+    let encoding = Encoding::for_dataset(dataset_path)?;
+    to_file_with_encoding(page, path, &encoding)
+}
+
+/// Writes the page with the given encoding. The page is written to a temporary file first, so
+/// readers never see a partially written page when it is replaced.
+pub fn to_file_with_encoding<T: StoredPage, P: AsRef<Path>>(
+    page: &T,
+    path: P,
+    encoding: &Encoding,
+) -> Result<()> {
+    let path = path.as_ref();
+    fs::create_dir_all(dataset_path(path)?)
+        .with_context(|| format!("Failed to create directory for '{path:?}'"))?;
+    let bytes = to_bytes(page, encoding)?;
+
+    let mut temporary_path = path.as_os_str().to_owned();
+    temporary_path.push(".tmp");
+    let temporary_path = PathBuf::from(temporary_path);
+    if let Err(error) = fs::write(&temporary_path, bytes) {
+        // The temporary file is useless after a failed write.
+        let _ = fs::remove_file(&temporary_path);
+        return Err(error).with_context(|| format!("Failed to write file '{temporary_path:?}'."));
+    }
+    fs::rename(&temporary_path, path)
+        .with_context(|| format!("Failed to move '{temporary_path:?}' to '{path:?}'."))
+}
+
+pub fn from_file<T: StoredPage, P: AsRef<Path>>(path: P) -> Result<T> {
+    let path = path.as_ref();
+    let bytes = fs::read(path).with_context(|| format!("Failed to read file '{path:?}'."))?;
+
+    from_bytes(&bytes, dataset_path(path)?)
+        .with_context(|| format!("Failed to load {} page from file '{path:?}'.", T::KIND))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, process};
+
+    use super::*;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct TestPage {
+        activations: Vec<f32>,
+        tokens: Vec<String>,
+    }
+
+    impl StoredPage for TestPage {
+        const KIND: PageKind = PageKind::NeuroscopeLayer;
+        const SCHEMA_VERSION: u16 = 1;
+
+        fn deserialize_payload(_version: u16, payload: &[u8]) -> Result<Self> {
+            Ok(postcard::from_bytes(payload)?)
+        }
+    }
+
+    fn test_page() -> TestPage {
+        TestPage {
+            activations: (0..100).map(|index| index as f32 / 10.).collect(),
+            tokens: ["The", " quick", " brown", " fox"]
+                .repeat(20)
+                .into_iter()
+                .map(str::to_owned)
+                .collect(),
+        }
+    }
+
+    fn round_trip(encoding: &Encoding, dataset_path: &Path) -> Result<TestPage> {
+        let bytes = to_bytes(&test_page(), encoding)?;
+        assert_eq!(&bytes[..4], MAGIC);
+        from_bytes(&bytes, dataset_path)
+    }
+
+    #[test]
+    fn round_trips_every_codec() -> Result<()> {
+        for codec in Codec::ALL {
+            let encoding = Encoding::new(codec, None, None)?;
+            assert_eq!(
+                round_trip(&encoding, Path::new("."))?,
+                test_page(),
+                "{codec}"
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn round_trips_zstd_with_dictionary() -> Result<()> {
+        let dataset_path = env::temp_dir().join(format!("neuronav-storage-{}", process::id()));
+        fs::create_dir_all(&dataset_path)?;
+        // Raw content serves as a zstd dictionary without training one.
+        let bytes = postcard::to_allocvec(&test_page())?;
+        let dictionary = Dictionary {
+            id: crc32fast::hash(&bytes),
+            bytes,
+        };
+        dictionary.save(&dataset_path)?;
+        let encoding = Encoding::new(Codec::Zstd, None, Some(&dictionary))?;
+        let result = round_trip(&encoding, &dataset_path);
+        let missing = round_trip(&encoding, Path::new("."));
+        let _ = fs::remove_dir_all(&dataset_path);
+
+        assert_eq!(result?, test_page());
+        let bytes = to_bytes(&test_page(), &encoding)?;
+        assert_eq!(
+            dictionary_id(&bytes[..HEADER_SIZE + 4]),
+            Some(dictionary.id)
+        );
+        assert_eq!(
+            dictionary_id(&to_bytes(&test_page(), &Encoding::default())?),
+            None
+        );
+        assert_eq!(
+            Dictionary::id_of_file_name(&format!("zstd-{:08x}.dict", dictionary.id)),
+            Some(dictionary.id)
+        );
+        assert!(matches!(
+            missing.unwrap_err().downcast_ref::<StorageError>(),
+            Some(StorageError::MissingDictionary(id)) if *id == dictionary.id
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn reads_legacy_pages_without_header() -> Result<()> {
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&postcard::to_allocvec(&test_page())?)?;
+        let bytes = encoder.finish()?;
+        assert_eq!(from_bytes::<TestPage, _>(&bytes, ".")?, test_page());
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_corrupted_pages() -> Result<()> {
+        let mut bytes = to_bytes(&test_page(), &Encoding::default())?;
+        *bytes.last_mut().unwrap() ^= 1;
+        let error = from_bytes::<TestPage, _>(&bytes, ".").unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<StorageError>(),
+            Some(StorageError::ChecksumMismatch { .. })
+        ));
+        Ok(())
+    }
+}
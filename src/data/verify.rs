@@ -0,0 +1,326 @@
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, ensure, Context, Result};
+use serde::Serialize;
+
+use super::{
+    retrieve::neuroscope::neuron_data_path,
+    storage::{Dictionary, Encoding, StorageConfig},
+    ModelMetadata, Neuron2GraphPage, NeuronIndex, NeuroscopeLayerArchive, NeuroscopeLayerPage,
+    NeuroscopeModelPage, NeuroscopeNeuronPage,
+};
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Issue {
+    pub path: PathBuf,
+    /// Set if the issue concerns a single neuron in a file containing several neurons.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub neuron: Option<NeuronIndex>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ModelReport {
+    pub model_name: String,
+    /// Services with a data directory for the model. Services without one are not checked.
+    pub services: Vec<String>,
+    pub num_checked: u64,
+    pub missing: Vec<Issue>,
+    pub corrupt: Vec<Issue>,
+    pub orphaned: Vec<PathBuf>,
+}
+
+impl ModelReport {
+    fn new(model_name: &str) -> Self {
+        Self {
+            model_name: model_name.to_owned(),
+            services: Vec::new(),
+            num_checked: 0,
+            missing: Vec::new(),
+            corrupt: Vec::new(),
+            orphaned: Vec::new(),
+        }
+    }
+
+    pub fn is_ok(&self) -> bool {
+        self.missing.is_empty() && self.corrupt.is_empty() && self.orphaned.is_empty()
+    }
+
+    fn check<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        neuron: Option<NeuronIndex>,
+        check: impl FnOnce(&Path) -> Result<()>,
+    ) {
+        let path = path.as_ref();
+        self.num_checked += 1;
+        if !path.exists() {
+            self.missing.push(Issue {
+                path: path.to_owned(),
+                neuron,
+                error: None,
+            });
+        } else if let Err(error) = check(path) {
+            self.corrupt(path, neuron, error);
+        }
+    }
+
+    fn corrupt(&mut self, path: &Path, neuron: Option<NeuronIndex>, error: anyhow::Error) {
+        self.corrupt.push(Issue {
+            path: path.to_owned(),
+            neuron,
+            error: Some(format!("{error:#}")),
+        });
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct DataReport {
+    pub models: Vec<ModelReport>,
+}
+
+impl DataReport {
+    pub fn is_ok(&self) -> bool {
+        self.models.iter().all(ModelReport::is_ok)
+    }
+}
+
+/// Verifies all models in the data directory. Every directory in it is considered a model.
+pub fn verify_data<P: AsRef<Path>>(data_path: P) -> Result<DataReport> {
+    let data_path = data_path.as_ref();
+    let mut model_names = fs::read_dir(data_path)
+        .with_context(|| format!("Failed to read data directory '{data_path:?}'."))?
+        .map(|entry| {
+            let entry = entry?;
+            Ok(entry
+                .file_type()?
+                .is_dir()
+                .then(|| entry.file_name().to_string_lossy().into_owned()))
+        })
+        .filter_map(Result::transpose)
+        .collect::<Result<Vec<_>>>()?;
+    model_names.sort_unstable();
+
+    let models = model_names
+        .iter()
+        .map(|model_name| verify_model(data_path, model_name))
+        .collect();
+    Ok(DataReport { models })
+}
+
+/// Checks that every page expected from the model's metadata exists and can be read the way the
+/// server reads it, and that no unexpected files are present. Only services with a data directory for the model are checked.
+pub fn verify_model<P: AsRef<Path>>(data_path: P, model_name: &str) -> ModelReport {
+    let data_path = data_path.as_ref();
+    let mut report = ModelReport::new(model_name);
+
+    let metadata_path = data_path.join(model_name).join("metadata.json");
+    let mut model_metadata = None;
+    report.check(&metadata_path, None, |_| {
+        model_metadata = Some(ModelMetadata::from_file(data_path, model_name)?);
+        Ok(())
+    });
+    let Some(model_metadata) = model_metadata else {
+        return report;
+    };
+
+    if data_path.join(model_name).join("neuroscope").is_dir() {
+        report.services.push("neuroscope".to_owned());
+        verify_neuroscope(&mut report, data_path, &model_metadata);
+    }
+    if data_path.join(model_name).join("neuron2graph").is_dir() {
+        report.services.push("neuron2graph".to_owned());
+        verify_neuron2graph(&mut report, data_path, &model_metadata);
+    }
+
+    report
+}
+
+fn verify_neuroscope(report: &mut ModelReport, data_path: &Path, model_metadata: &ModelMetadata) {
+    let model_name = model_metadata.name.as_str();
+    let neuroscope_path = data_path.join(model_name).join("neuroscope");
+    let mut expected_paths = HashSet::new();
+
+    let model_page_path = neuroscope_path.join("model.postcard");
+    report.check(&model_page_path, None, |path| {
+        NeuroscopeModelPage::from_file(path).map(|_| ())
+    });
+    expected_paths.insert(model_page_path);
+
+    for (layer_index, layer_metadata) in model_metadata.layers.iter().enumerate() {
+        let layer_index = layer_index as u32;
+        let num_neurons = layer_metadata.num_neurons;
+
+        let layer_page_path = neuroscope_path.join(format!("l{layer_index}.postcard"));
+        report.check(&layer_page_path, None, |path| {
+            let layer_page = NeuroscopeLayerPage::from_file(path)?;
+            ensure!(
+                layer_page.num_neurons() == num_neurons,
+                "Layer page has {} neurons, but the metadata says {num_neurons}.",
+                layer_page.num_neurons()
+            );
+            Ok(())
+        });
+        expected_paths.insert(layer_page_path);
+
+        let archive_path = NeuroscopeLayerArchive::path(data_path, model_name, layer_index);
+        if archive_path.exists() {
+            verify_neuroscope_archive(report, &archive_path, layer_index, num_neurons);
+            expected_paths.insert(archive_path);
+            // Packing keeps the page files unless asked to remove them. The server reads the
+            // archive instead, so they are neither checked nor orphaned.
+            expected_paths.extend(
+                layer_metadata
+                    .neuron_indices(layer_index)
+                    .map(|neuron_index| neuron_data_path(data_path, model_name, neuron_index)),
+            );
+        } else {
+            for neuron_index in layer_metadata.neuron_indices(layer_index) {
+                let page_path = neuron_data_path(data_path, model_name, neuron_index);
+                report.check(&page_path, Some(neuron_index), |path| {
+                    let page = NeuroscopeNeuronPage::from_file(path)?;
+                    check_neuron_index(page.neuron_index(), neuron_index)
+                });
+                expected_paths.insert(page_path);
+            }
+        }
+    }
+
+    let storage_config_path = StorageConfig::path(&neuroscope_path);
+    if storage_config_path.exists() {
+        report.check(&storage_config_path, None, |_| {
+            Encoding::for_dataset(&neuroscope_path).map(|_| ())
+        });
+        expected_paths.insert(storage_config_path);
+    }
+
+    find_orphans(report, &neuroscope_path, |path| {
+        path.is_file()
+            && (expected_paths.contains(path)
+                || path
+                    .file_name()
+                    .and_then(|file_name| file_name.to_str())
+                    .and_then(Dictionary::id_of_file_name)
+                    .is_some())
+    });
+}
+
+fn verify_neuroscope_archive(
+    report: &mut ModelReport,
+    archive_path: &Path,
+    layer_index: u32,
+    num_neurons: u32,
+) {
+    let archive = match NeuroscopeLayerArchive::open(archive_path) {
+        Ok(archive) => archive,
+        Err(error) => {
+            report.num_checked += 1;
+            report.corrupt(archive_path, None, error);
+            return;
+        }
+    };
+    if archive.layer_index() != layer_index || archive.num_neurons() != num_neurons {
+        report.num_checked += 1;
+        report.corrupt(
+            archive_path,
+            None,
+            anyhow!(
+                "Archive contains {} neurons of layer {}, but {num_neurons} neurons of layer {layer_index} were expected.",
+                archive.num_neurons(),
+                archive.layer_index()
+            ),
+        );
+        return;
+    }
+
+    let dataset_path = archive_path.parent().unwrap();
+    for neuron in 0..num_neurons {
+        let neuron_index = NeuronIndex {
+            layer: layer_index,
+            neuron,
+        };
+        report.num_checked += 1;
+        match archive.record(neuron) {
+            Ok(Some(record)) => {
+                if let Err(error) = NeuroscopeNeuronPage::from_bytes(record, dataset_path)
+                    .and_then(|page| check_neuron_index(page.neuron_index(), neuron_index))
+                {
+                    report.corrupt(archive_path, Some(neuron_index), error);
+                }
+            }
+            Ok(None) => report.missing.push(Issue {
+                path: archive_path.to_owned(),
+                neuron: Some(neuron_index),
+                error: None,
+            }),
+            Err(error) => report.corrupt(archive_path, Some(neuron_index), error),
+        }
+    }
+}
+
+fn verify_neuron2graph(report: &mut ModelReport, data_path: &Path, model_metadata: &ModelMetadata) {
+    let neuron2graph_path = data_path.join(&model_metadata.name).join("neuron2graph");
+    let mut expected_paths = HashSet::new();
+
+    for (layer_index, layer_metadata) in model_metadata.layers.iter().enumerate() {
+        let layer_index = layer_index as u32;
+        let layer_path = neuron2graph_path.join(format!("layer_{layer_index}"));
+        expected_paths.insert(layer_path.clone());
+        for neuron_index in layer_metadata.neuron_indices(layer_index) {
+            let neuron_path = layer_path.join(format!("{layer_index}_{}", neuron_index.neuron));
+            let graph_path = neuron_path.join("graph");
+            report.check(&graph_path, Some(neuron_index), |path| {
+                Neuron2GraphPage::read_graph(path).map(|_| ())
+            });
+            expected_paths.insert(neuron_path);
+            expected_paths.insert(graph_path);
+        }
+    }
+
+    find_orphans(report, &neuron2graph_path, |path| {
+        expected_paths.contains(path)
+    });
+}
+
+fn check_neuron_index(found: NeuronIndex, expected: NeuronIndex) -> Result<()> {
+    ensure!(
+        found == expected,
+        "Page contains neuron {found}, but is stored as neuron {expected}."
+    );
+    Ok(())
+}
+
+/// Reports every file or directory below `directory` that is not expected. Directories that are
+/// not expected are reported without their contents.
+fn find_orphans(report: &mut ModelReport, directory: &Path, is_expected: impl Fn(&Path) -> bool) {
+    let mut directories = vec![directory.to_owned()];
+    while let Some(directory) = directories.pop() {
+        let entries = match fs::read_dir(&directory) {
+            Ok(entries) => entries,
+            Err(error) => {
+                report.corrupt(&directory, None, error.into());
+                continue;
+            }
+        };
+        for entry in entries {
+            let path = match entry {
+                Ok(entry) => entry.path(),
+                Err(error) => {
+                    report.corrupt(&directory, None, error.into());
+                    continue;
+                }
+            };
+            if !is_expected(&path) {
+                report.orphaned.push(path);
+            } else if path.is_dir() {
+                directories.push(path);
+            }
+        }
+    }
+    report.orphaned.sort_unstable();
+}
@@ -1,14 +1,26 @@
+use std::str::FromStr;
+
 use crate::{
-    data::{retrieve, NeuronIndex, NeuronViewerObject, NeuroscopeNeuronPage},
+    data::{
+        retrieve, NeuronIndex, NeuronStore, NeuronViewerObject, NeuroscopeLayerPage,
+        NeuroscopeModelPage, NeuroscopeNeuronPage, Text, TokenSearch, TokenSearchType,
+    },
     server,
 };
-use anyhow::{Context, Result};
-use pyo3::prelude::*;
+use anyhow::{ensure, Context, Result};
+use ndarray::{Array1, Array2};
+use numpy::{IntoPyArray, PyArray1, PyArray2};
+use pyo3::{basic::CompareOp, prelude::*};
 use tokio::runtime::Runtime;
 
 #[pyfunction]
-fn start_server() {
-    server::start_server().unwrap();
+#[pyo3(signature = (config_path = None))]
+fn start_server(config_path: Option<&str>) -> Result<()> {
+    let config = match config_path {
+        Some(config_path) => server::Config::from_file(config_path)?,
+        None => server::Config::default(),
+    };
+    server::start_server(config)
 }
 
 #[pyfunction]
@@ -64,14 +76,140 @@ struct PyNeuronViewerObject {
 impl PyNeuronViewerObject {
     #[new]
     fn new(json: &str) -> PyResult<Self> {
-        let json_value = serde_json::from_str(json).context("failed to parse json")?;
         Ok(PyNeuronViewerObject {
-            object: NeuronViewerObject::from_json(&json_value)?,
+            object: NeuronViewerObject::from_json_str(json)?,
+        })
+    }
+
+    fn to_file(&self, path: &str) -> Result<()> {
+        self.object
+            .to_file(path)
+            .with_context(|| format!("Failed to write neuron viewer object to file '{path:?}'."))
+    }
+
+    #[staticmethod]
+    fn from_file(path: &str) -> Result<Self> {
+        Ok(PyNeuronViewerObject {
+            object: NeuronViewerObject::from_file(path).with_context(|| {
+                format!("Failed to read neuron viewer object from file '{path:?}'.")
+            })?,
+        })
+    }
+}
+
+#[pyclass(name = "NeuronIndex")]
+#[derive(Clone, Copy)]
+struct PyNeuronIndex {
+    index: NeuronIndex,
+}
+
+#[pymethods]
+impl PyNeuronIndex {
+    #[new]
+    fn new(layer: u32, neuron: u32) -> Self {
+        PyNeuronIndex {
+            index: NeuronIndex { layer, neuron },
+        }
+    }
+
+    /// Parses a neuron index of the form 'layer_neuron'.
+    #[staticmethod]
+    fn from_str(neuron_index: &str) -> Result<Self> {
+        Ok(PyNeuronIndex {
+            index: NeuronIndex::from_str(neuron_index)?,
         })
     }
 
-    fn to_file(&self, path: &str) {
-        self.object.to_file(path);
+    #[getter]
+    fn layer(&self) -> u32 {
+        self.index.layer
+    }
+
+    #[getter]
+    fn neuron(&self) -> u32 {
+        self.index.neuron
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "NeuronIndex(layer={}, neuron={})",
+            self.index.layer, self.index.neuron
+        )
+    }
+
+    fn __str__(&self) -> String {
+        self.index.to_string()
+    }
+
+    fn __richcmp__(&self, other: &Self, op: CompareOp) -> bool {
+        op.matches(self.index.cmp(&other.index))
+    }
+
+    fn __hash__(&self) -> u64 {
+        ((self.index.layer as u64) << 32) | self.index.neuron as u64
+    }
+}
+
+impl From<NeuronIndex> for PyNeuronIndex {
+    fn from(index: NeuronIndex) -> Self {
+        PyNeuronIndex { index }
+    }
+}
+
+#[pyclass(name = "NeuroscopeText")]
+struct PyNeuroscopeText {
+    text: Text,
+}
+
+#[pymethods]
+impl PyNeuroscopeText {
+    #[getter]
+    fn tokens(&self) -> Vec<String> {
+        self.text.tokens().to_vec()
+    }
+
+    #[getter]
+    fn activations<'py>(&self, py: Python<'py>) -> &'py PyArray1<f32> {
+        PyArray1::from_slice(py, self.text.activations())
+    }
+
+    /// Index of the text in the dataset the activations were recorded on.
+    #[getter]
+    fn data_index(&self) -> u64 {
+        self.text.data_index()
+    }
+
+    #[getter]
+    fn max_activating_token_index(&self) -> u32 {
+        self.text.max_activating_token_index()
+    }
+
+    #[getter]
+    fn max_activation(&self) -> f32 {
+        self.text.max_activation()
+    }
+
+    #[getter]
+    fn min_activation(&self) -> f32 {
+        self.text.min_activation()
+    }
+
+    #[getter]
+    fn max_range(&self) -> f32 {
+        self.text.max_range()
+    }
+
+    #[getter]
+    fn min_range(&self) -> f32 {
+        self.text.min_range()
+    }
+
+    fn __len__(&self) -> usize {
+        self.text.tokens().len()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("{:?}", self.text)
     }
 }
 
@@ -95,6 +233,20 @@ impl PyNeuroscopePage {
         })
     }
 
+    #[getter]
+    fn neuron_index(&self) -> PyNeuronIndex {
+        self.object.neuron_index().into()
+    }
+
+    #[getter]
+    fn texts(&self) -> Vec<PyNeuroscopeText> {
+        self.object
+            .texts()
+            .iter()
+            .map(|text| PyNeuroscopeText { text: text.clone() })
+            .collect()
+    }
+
     fn to_json(&self) -> Result<String> {
         serde_json::to_string(&self.object).context("Failed to serialize neuroscope page.")
     }
@@ -116,6 +268,226 @@ impl PyNeuroscopePage {
                 .with_context(|| format!("Failed to read neuroscope page from file '{path:?}'."))?,
         })
     }
+
+    /// Loads the page of a neuron from a packed layer archive.
+    #[staticmethod]
+    fn from_archive(path: &str, neuron_index: PyNeuronIndex) -> Result<Self> {
+        Ok(PyNeuroscopePage {
+            object: NeuroscopeNeuronPage::from_archive(path, neuron_index.index)?,
+        })
+    }
+}
+
+fn py_important_neurons(important_neurons: &[(NeuronIndex, f32)]) -> Vec<(PyNeuronIndex, f32)> {
+    important_neurons
+        .iter()
+        .map(|&(neuron_index, importance)| (neuron_index.into(), importance))
+        .collect()
+}
+
+#[pyclass(name = "NeuroscopeLayerPage")]
+struct PyNeuroscopeLayerPage {
+    object: NeuroscopeLayerPage,
+}
+
+#[pymethods]
+impl PyNeuroscopeLayerPage {
+    #[getter]
+    fn num_neurons(&self) -> u32 {
+        self.object.num_neurons()
+    }
+
+    /// Neurons of the layer with their activation range, from smallest to largest range.
+    #[getter]
+    fn important_neurons(&self) -> Vec<(PyNeuronIndex, f32)> {
+        py_important_neurons(self.object.important_neurons())
+    }
+
+    fn to_json(&self) -> Result<String> {
+        serde_json::to_string(&self.object).context("Failed to serialize neuroscope layer page.")
+    }
+
+    fn __repr__(&self) -> String {
+        format!("{:?}", self.object)
+    }
+
+    fn to_file(&self, path: &str) -> Result<()> {
+        self.object
+            .to_file(path)
+            .with_context(|| format!("Failed to write neuroscope layer page to file '{path:?}'."))
+    }
+
+    #[staticmethod]
+    fn from_file(path: &str) -> Result<Self> {
+        Ok(PyNeuroscopeLayerPage {
+            object: NeuroscopeLayerPage::from_file(path).with_context(|| {
+                format!("Failed to read neuroscope layer page from file '{path:?}'.")
+            })?,
+        })
+    }
+}
+
+#[pyclass(name = "NeuroscopeModelPage")]
+struct PyNeuroscopeModelPage {
+    object: NeuroscopeModelPage,
+}
+
+#[pymethods]
+impl PyNeuroscopeModelPage {
+    /// Neurons of the model with their importance, from least to most important.
+    #[getter]
+    fn important_neurons(&self) -> Vec<(PyNeuronIndex, f32)> {
+        py_important_neurons(self.object.important_neurons())
+    }
+
+    fn to_json(&self) -> Result<String> {
+        serde_json::to_string(&self.object).context("Failed to serialize neuroscope model page.")
+    }
+
+    fn __repr__(&self) -> String {
+        format!("{:?}", self.object)
+    }
+
+    fn to_file(&self, path: &str) -> Result<()> {
+        self.object
+            .to_file(path)
+            .with_context(|| format!("Failed to write neuroscope model page to file '{path:?}'."))
+    }
+
+    #[staticmethod]
+    fn from_file(path: &str) -> Result<Self> {
+        Ok(PyNeuroscopeModelPage {
+            object: NeuroscopeModelPage::from_file(path).with_context(|| {
+                format!("Failed to read neuroscope model page from file '{path:?}'.")
+            })?,
+        })
+    }
+}
+
+/// Converts neuron indices to an array with a row of layer and neuron index per neuron.
+fn neuron_index_array(neuron_indices: impl ExactSizeIterator<Item = NeuronIndex>) -> Array2<u32> {
+    let mut array = Array2::zeros((neuron_indices.len(), 2));
+    for (mut row, NeuronIndex { layer, neuron }) in array.rows_mut().into_iter().zip(neuron_indices)
+    {
+        row[0] = layer;
+        row[1] = neuron;
+    }
+    array
+}
+
+#[pyclass(name = "NeuronStore")]
+struct PyNeuronStore {
+    store: NeuronStore,
+}
+
+impl PyNeuronStore {
+    fn neuron_index(&self, layer_index: u32, neuron_index: u32) -> Result<NeuronIndex> {
+        let neuron_index = NeuronIndex {
+            layer: layer_index,
+            neuron: neuron_index,
+        };
+        ensure!(
+            self.store.contains(neuron_index),
+            "Neuron {neuron_index} is not in the neuron store, which has {} layers of {} neurons.",
+            self.store.num_layers(),
+            self.store.layer_size()
+        );
+        Ok(neuron_index)
+    }
+}
+
+#[pymethods]
+impl PyNeuronStore {
+    /// Loads the neuron store of a model from the data directory.
+    #[staticmethod]
+    fn load(model: &str) -> Result<Self> {
+        Ok(PyNeuronStore {
+            store: NeuronStore::load(model)?,
+        })
+    }
+
+    /// Neurons that activate on or are important for the token, depending on the search type,
+    /// which is 'activating', 'important' or 'any'. Returns an array of (layer, neuron) rows.
+    #[pyo3(signature = (token, search_type = "any"))]
+    fn search<'py>(
+        &self,
+        py: Python<'py>,
+        token: &str,
+        search_type: &str,
+    ) -> Result<&'py PyArray2<u32>> {
+        let token_search = TokenSearch {
+            token: token.to_owned(),
+            search_types: TokenSearchType::list_from_str(search_type)?,
+        };
+        let results = self.store.search(&[token_search])?;
+        Ok(neuron_index_array(results.into_iter()).into_pyarray(py))
+    }
+
+    /// Neurons matching all token searches of a query of the form
+    /// 'search_type:token,search_type:token', like the search service of the server.
+    fn query<'py>(&self, py: Python<'py>, query: &str) -> Result<&'py PyArray2<u32>> {
+        let token_searches = query
+            .split(',')
+            .map(TokenSearch::from_str)
+            .collect::<Result<Vec<_>>>()?;
+        let results = self.store.search(&token_searches)?;
+        Ok(neuron_index_array(results.into_iter()).into_pyarray(py))
+    }
+
+    /// Neurons with a similarity of at least `threshold` to the neuron, from most to least
+    /// similar. Returns an array of (layer, neuron) rows and an array of similarities.
+    #[pyo3(signature = (layer_index, neuron_index, threshold = 0.4))]
+    fn similar_neurons<'py>(
+        &self,
+        py: Python<'py>,
+        layer_index: u32,
+        neuron_index: u32,
+        threshold: f32,
+    ) -> Result<(&'py PyArray2<u32>, &'py PyArray1<f32>)> {
+        let neuron_index = self.neuron_index(layer_index, neuron_index)?;
+        let similar_neurons = self.store.similar_neurons(neuron_index, threshold)?;
+        let neuron_indices = neuron_index_array(
+            similar_neurons
+                .iter()
+                .map(|&(neuron_index, _)| neuron_index),
+        );
+        let similarities = similar_neurons
+            .iter()
+            .map(|&(_, similarity)| similarity)
+            .collect::<Array1<_>>();
+        Ok((
+            neuron_indices.into_pyarray(py),
+            similarities.into_pyarray(py),
+        ))
+    }
+
+    fn similarity(
+        &self,
+        layer_index1: u32,
+        neuron_index1: u32,
+        layer_index2: u32,
+        neuron_index2: u32,
+    ) -> Result<f32> {
+        Ok(self.store.similarity(
+            self.neuron_index(layer_index1, neuron_index1)?,
+            self.neuron_index(layer_index2, neuron_index2)?,
+        ))
+    }
+
+    /// Similarities between all neurons, indexed by `layer_index * layer_size + neuron_index`.
+    fn similarity_matrix<'py>(&self, py: Python<'py>) -> &'py PyArray2<f32> {
+        self.store.similarity_matrix().into_pyarray(py)
+    }
+
+    #[getter]
+    fn layer_size(&self) -> u32 {
+        self.store.layer_size()
+    }
+
+    #[getter]
+    fn num_layers(&self) -> u32 {
+        self.store.num_layers()
+    }
 }
 
 /// A Python module implemented in Rust.
@@ -126,6 +498,11 @@ fn neuronav(_py: Python, m: &PyModule) -> PyResult<()> {
     m.add_function(wrap_pyfunction!(scrape_model_to_files, m)?)?;
     m.add_function(wrap_pyfunction!(scrape_model_metadata_to_file, m)?)?;
     m.add_class::<PyNeuronViewerObject>()?;
+    m.add_class::<PyNeuronIndex>()?;
+    m.add_class::<PyNeuroscopeText>()?;
     m.add_class::<PyNeuroscopePage>()?;
+    m.add_class::<PyNeuroscopeLayerPage>()?;
+    m.add_class::<PyNeuroscopeModelPage>()?;
+    m.add_class::<PyNeuronStore>()?;
     Ok(())
 }
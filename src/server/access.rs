@@ -0,0 +1,609 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use actix_web::{
+    body::{EitherBody, MessageBody},
+    dev::{ServiceRequest, ServiceResponse},
+    http::{
+        header::{self, X_FORWARDED_FOR},
+        Method, StatusCode,
+    },
+    middleware::Next,
+    web, Error, HttpMessage, HttpRequest, HttpResponse, ResponseError,
+};
+use anyhow::{bail, Result};
+use percent_encoding::percent_decode_str;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::{admin::ADMIN_PATHS, State, RESERVED_SERVICE_NAMES};
+
+/// Header bearing the API key of a request.
+pub const API_KEY_HEADER: &str = "X-API-Key";
+
+/// Number of clients a rate limiter tracks before forgetting those that are back to their full
+/// allowance. If most clients are not, it forgets them again only once it tracks twice as many, so
+/// the cost of forgetting is spread over the requests in between.
+const MAX_TRACKED_CLIENTS: usize = 10_000;
+
+/// Models and services a client may request. A list that is omitted allows everything.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Scope {
+    pub models: Option<Vec<String>>,
+    pub services: Option<Vec<String>>,
+}
+
+fn allows(names: &Option<Vec<String>>, name: &str) -> bool {
+    match names {
+        Some(names) => names.iter().any(|allowed| allowed == name),
+        None => true,
+    }
+}
+
+/// A client may make `requests` requests at once, and as many more every `seconds` seconds.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RateLimit {
+    pub requests: u32,
+    pub seconds: u32,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ApiKey {
+    /// Name of the client holding the key.
+    pub name: String,
+    pub key: String,
+    #[serde(default)]
+    pub scope: Scope,
+    /// Limit of requests with the key. They are not limited if omitted.
+    pub rate_limit: Option<RateLimit>,
+}
+
+/// Which clients may request which pages, and how often.
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AccessConfig {
+    /// Keys clients send in the `X-API-Key` header.
+    pub api_keys: Vec<ApiKey>,
+    /// Scope of requests without an API key.
+    pub anonymous: Scope,
+    /// Limit of requests without a valid API key per client IP address.
+    pub ip_rate_limit: Option<RateLimit>,
+    /// Number of reverse proxies in front of the server, each appending the address of its client
+    /// to the `X-Forwarded-For` header. The client IP address is the entry of the header that the
+    /// outermost proxy added.
+    pub trusted_proxies: u32,
+}
+
+impl AccessConfig {
+    /// Checks that the API keys and their names are unique and that rate limits allow requests.
+    pub fn check(&self) -> Result<()> {
+        let mut names = HashSet::new();
+        let mut keys = HashSet::new();
+        for api_key in &self.api_keys {
+            if api_key.key.is_empty() {
+                bail!("API key '{}' is empty.", api_key.name);
+            }
+            if !names.insert(api_key.name.as_str()) {
+                bail!("API key name '{}' is used more than once.", api_key.name);
+            }
+            if !keys.insert(api_key.key.as_str()) {
+                bail!("API key '{}' is used more than once.", api_key.name);
+            }
+        }
+        let rate_limits = self
+            .api_keys
+            .iter()
+            .filter_map(|api_key| api_key.rate_limit)
+            .chain(self.ip_rate_limit);
+        for RateLimit { requests, seconds } in rate_limits {
+            if requests == 0 || seconds == 0 {
+                bail!("Rate limits must allow at least one request in at least one second.");
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum AccessError {
+    #[error("Invalid API key.")]
+    InvalidKey,
+    #[error("An API key is required to access {0}.")]
+    KeyRequired(String),
+    #[error("The API key does not grant access to {0}.")]
+    Forbidden(String),
+    #[error("Too many requests. Retry in {retry_after} s.")]
+    RateLimited { retry_after: u64 },
+}
+
+impl ResponseError for AccessError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            AccessError::InvalidKey | AccessError::KeyRequired(_) => StatusCode::UNAUTHORIZED,
+            AccessError::Forbidden(_) => StatusCode::FORBIDDEN,
+            AccessError::RateLimited { .. } => StatusCode::TOO_MANY_REQUESTS,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let mut response = HttpResponse::build(self.status_code());
+        if let AccessError::RateLimited { retry_after } = self {
+            response.insert_header((header::RETRY_AFTER, *retry_after));
+        }
+        response.body(self.to_string())
+    }
+}
+
+/// What the client of a request may access. The access middleware adds it to every request.
+#[derive(Clone, Debug)]
+pub struct Grant {
+    /// Name of the API key of the request, if it has one.
+    pub api_key: Option<String>,
+    pub scope: Scope,
+}
+
+impl Grant {
+    /// The grant of the request, or one to access nothing if the middleware did not run.
+    pub fn of(request: &HttpRequest) -> Self {
+        request
+            .extensions()
+            .get::<Grant>()
+            .cloned()
+            .unwrap_or_else(|| {
+                log::error!("No grant for request to '{}'.", request.path());
+                Self::nothing()
+            })
+    }
+
+    fn nothing() -> Self {
+        Self {
+            api_key: None,
+            scope: Scope {
+                models: Some(Vec::new()),
+                services: Some(Vec::new()),
+            },
+        }
+    }
+
+    pub fn allows_model(&self, model_name: &str) -> bool {
+        allows(&self.scope.models, model_name)
+    }
+
+    pub fn allows_service(&self, service_name: &str) -> bool {
+        allows(&self.scope.services, service_name)
+    }
+
+    fn denied(&self, resource: String) -> AccessError {
+        match self.api_key {
+            Some(_) => AccessError::Forbidden(resource),
+            None => AccessError::KeyRequired(resource),
+        }
+    }
+
+    pub fn check_model(&self, model_name: &str) -> Result<(), AccessError> {
+        if self.allows_model(model_name) {
+            Ok(())
+        } else {
+            Err(self.denied(format!("model '{model_name}'")))
+        }
+    }
+
+    pub fn check_service(&self, service_name: &str) -> Result<(), AccessError> {
+        if self.allows_service(service_name) {
+            Ok(())
+        } else {
+            Err(self.denied(format!("service '{service_name}'")))
+        }
+    }
+}
+
+/// Limits clients to a number of requests per period, which they may use up at once. Keeps the
+/// time at which each client has its full allowance back.
+#[derive(Default)]
+struct RateLimiter {
+    clients: Mutex<Clients>,
+}
+
+struct Clients {
+    replenished: HashMap<String, Instant>,
+    /// Number of tracked clients at which those back to their full allowance are forgotten.
+    prune_at: usize,
+}
+
+impl Default for Clients {
+    fn default() -> Self {
+        Self {
+            replenished: HashMap::new(),
+            prune_at: MAX_TRACKED_CLIENTS,
+        }
+    }
+}
+
+impl RateLimiter {
+    /// Counts a request of the client, unless it is over its limit.
+    fn acquire(&self, client: &str, rate_limit: RateLimit) -> Result<(), AccessError> {
+        let period = Duration::from_secs(rate_limit.seconds.into());
+        let interval = period / rate_limit.requests;
+        let now = Instant::now();
+        let mut clients = self.clients.lock().expect("Rate limiter lock poisoned.");
+        if clients.replenished.len() >= clients.prune_at {
+            clients
+                .replenished
+                .retain(|_, replenished| *replenished > now);
+            clients.prune_at = MAX_TRACKED_CLIENTS.max(2 * clients.replenished.len());
+        }
+        let replenished = clients
+            .replenished
+            .get(client)
+            .map_or(now, |&replenished| replenished.max(now))
+            + interval;
+        if replenished > now + period {
+            let retry_after = replenished - period - now;
+            return Err(AccessError::RateLimited {
+                retry_after: retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0),
+            });
+        }
+        clients.replenished.insert(client.to_owned(), replenished);
+        Ok(())
+    }
+}
+
+/// Compares in time independent of where the tokens differ.
+pub fn tokens_match(token: &str, expected: &str) -> bool {
+    token.len() == expected.len()
+        && token
+            .bytes()
+            .zip(expected.bytes())
+            .fold(0, |difference, (a, b)| difference | (a ^ b))
+            == 0
+}
+
+/// Enforces the access config of the server.
+#[derive(Default)]
+pub struct Access {
+    config: AccessConfig,
+    key_limiter: RateLimiter,
+    ip_limiter: RateLimiter,
+}
+
+impl Access {
+    pub fn new(config: AccessConfig) -> Self {
+        Self {
+            config,
+            ..Default::default()
+        }
+    }
+
+    /// Whether what a request may access depends on its API key.
+    pub fn is_restricted(&self) -> bool {
+        let AccessConfig {
+            api_keys,
+            anonymous,
+            ..
+        } = &self.config;
+        !api_keys.is_empty() || anonymous.models.is_some() || anonymous.services.is_some()
+    }
+
+    fn client_ip(&self, request: &ServiceRequest) -> String {
+        let trusted_proxies = self.config.trusted_proxies as usize;
+        if trusted_proxies > 0 {
+            // Clients can send the header themselves, so only the entries appended by the trusted
+            // proxies are used.
+            let forwarded_for = request
+                .headers()
+                .get_all(X_FORWARDED_FOR)
+                .filter_map(|value| value.to_str().ok())
+                .flat_map(|value| value.split(','))
+                .map(str::trim)
+                .collect::<Vec<_>>();
+            if let Some(address) = forwarded_for
+                .len()
+                .checked_sub(trusted_proxies)
+                .map(|index| forwarded_for[index])
+            {
+                return address.to_owned();
+            }
+        }
+        request
+            .peer_addr()
+            .map_or_else(|| "unknown".to_owned(), |address| address.ip().to_string())
+    }
+
+    /// Counts a request without a valid API key against the limit of its IP address.
+    fn acquire_ip(&self, request: &ServiceRequest) -> Result<(), AccessError> {
+        match self.config.ip_rate_limit {
+            Some(rate_limit) => self
+                .ip_limiter
+                .acquire(&self.client_ip(request), rate_limit),
+            None => Ok(()),
+        }
+    }
+
+    /// Identifies the client of the request and counts the request against its rate limit.
+    fn grant(&self, request: &ServiceRequest) -> Result<Grant, AccessError> {
+        let Some(token) = request.headers().get(API_KEY_HEADER) else {
+            self.acquire_ip(request)?;
+            return Ok(Grant {
+                api_key: None,
+                scope: self.config.anonymous.clone(),
+            });
+        };
+        let api_key = token.to_str().ok().and_then(|token| {
+            self.config
+                .api_keys
+                .iter()
+                .find(|api_key| tokens_match(token, &api_key.key))
+        });
+        // Failed keys count against the IP address, so clients cannot guess keys without limit.
+        let Some(api_key) = api_key else {
+            self.acquire_ip(request)?;
+            return Err(AccessError::InvalidKey);
+        };
+        if let Some(rate_limit) = api_key.rate_limit {
+            self.key_limiter.acquire(&api_key.name, rate_limit)?;
+        }
+        Ok(Grant {
+            api_key: Some(api_key.name.clone()),
+            scope: api_key.scope.clone(),
+        })
+    }
+
+    /// Checks the model and service of a page request. Handlers of requests for the pages of
+    /// several services check those services themselves.
+    fn check_path(grant: &Grant, request: &ServiceRequest) -> Result<(), AccessError> {
+        if ADMIN_PATHS.contains(&request.path()) {
+            return Ok(());
+        }
+        let Some(path) = request.path().strip_prefix("/api/") else {
+            return Ok(());
+        };
+        let mut segments = path
+            .split('/')
+            .map(|segment| percent_decode_str(segment).decode_utf8_lossy());
+        let (Some(model_name), Some(service_name)) = (segments.next(), segments.next()) else {
+            return Ok(());
+        };
+        grant.check_model(&model_name)?;
+        let several_services = RESERVED_SERVICE_NAMES.contains(&service_name.as_ref())
+            || (service_name == "batch" && request.method() == Method::POST);
+        if !several_services {
+            grant.check_service(&service_name)?;
+        }
+        Ok(())
+    }
+
+    fn authorize(&self, request: &ServiceRequest) -> Result<Grant, AccessError> {
+        let grant = self.grant(request)?;
+        Self::check_path(&grant, request)?;
+        Ok(grant)
+    }
+}
+
+/// Middleware rejecting requests with invalid API keys, beyond the rate limit of their client or
+/// for pages outside its scope. The [`Grant`] of accepted requests is added to their extensions.
+pub async fn enforce(
+    request: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, Error> {
+    let state = request
+        .app_data::<web::Data<State>>()
+        .expect("State should be registered with the app.")
+        .clone();
+    match state.access().authorize(&request) {
+        Ok(grant) => {
+            request.extensions_mut().insert(grant);
+            Ok(next.call(request).await?.map_into_left_body())
+        }
+        Err(error) => Ok(request.error_response(error).map_into_right_body()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::test::TestRequest;
+
+    use super::*;
+
+    fn access_config() -> AccessConfig {
+        serde_json::from_value(serde_json::json!({
+            "api_keys": [
+                { "name": "team", "key": "team-key" },
+                {
+                    "name": "partner",
+                    "key": "partner-key",
+                    "scope": { "models": ["gelu-1l"], "services": ["neuroscope"] },
+                    "rate_limit": { "requests": 2, "seconds": 60 },
+                },
+            ],
+            "anonymous": { "models": ["solu-1l"] },
+            "ip_rate_limit": { "requests": 3, "seconds": 60 },
+        }))
+        .unwrap()
+    }
+
+    fn request(path: &str, api_key: Option<&str>) -> ServiceRequest {
+        let mut request = TestRequest::get()
+            .uri(path)
+            .peer_addr("10.0.0.1:1234".parse().unwrap());
+        if let Some(api_key) = api_key {
+            request = request.insert_header((API_KEY_HEADER, api_key));
+        }
+        request.to_srv_request()
+    }
+
+    fn status(result: Result<Grant, AccessError>) -> StatusCode {
+        match result {
+            Ok(_) => StatusCode::OK,
+            Err(error) => error.status_code(),
+        }
+    }
+
+    #[test]
+    fn checks_the_scope_of_the_api_key() {
+        let access = Access::new(access_config());
+        let authorize = |path, api_key| status(access.authorize(&request(path, api_key)));
+
+        assert_eq!(
+            authorize("/api/solu-1l/neuroscope/0/1", None),
+            StatusCode::OK
+        );
+        assert_eq!(
+            authorize("/api/gelu-1l/neuroscope/0/1", None),
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            authorize("/api/gelu-1l/neuroscope/0/1", Some("team-key")),
+            StatusCode::OK
+        );
+        assert_eq!(
+            authorize("/api/gelu-1l/neuroscope/0/1", Some("wrong-key")),
+            StatusCode::UNAUTHORIZED
+        );
+
+        let partner = Some("partner-key");
+        assert_eq!(
+            authorize("/api/gelu-1l/neuroscope/0/1", partner),
+            StatusCode::OK
+        );
+        assert_eq!(
+            authorize("/api/gelu-1l/explanation/0/1", partner),
+            StatusCode::FORBIDDEN
+        );
+    }
+
+    #[test]
+    fn leaves_pages_of_several_services_and_admin_endpoints_to_their_handlers() {
+        let mut config = access_config();
+        config.api_keys[1].rate_limit = None;
+        let access = Access::new(config);
+        let authorize = |path, api_key| status(access.authorize(&request(path, api_key)));
+
+        let partner = Some("partner-key");
+        assert_eq!(authorize("/api/gelu-1l/all/0/1", partner), StatusCode::OK);
+        assert_eq!(authorize("/api/gelu-1l/services", partner), StatusCode::OK);
+        assert_eq!(authorize("/api/models", partner), StatusCode::OK);
+        assert_eq!(authorize("/api/admin/reload", partner), StatusCode::OK);
+        // Only the admin endpoints are exempt, not a model named "admin".
+        assert_eq!(
+            authorize("/api/admin/neuroscope", partner),
+            StatusCode::FORBIDDEN
+        );
+        // Path segments are decoded before they are checked.
+        assert_eq!(
+            authorize("/api/gelu-1l/%65xplanation/0/1", partner),
+            StatusCode::FORBIDDEN
+        );
+    }
+
+    #[test]
+    fn rate_limits_api_keys_with_retry_after() {
+        let access = Access::new(access_config());
+        let authorize =
+            || access.authorize(&request("/api/gelu-1l/neuroscope", Some("partner-key")));
+
+        assert!(authorize().is_ok());
+        assert!(authorize().is_ok());
+        let error = authorize().unwrap_err();
+        assert!(
+            matches!(error, AccessError::RateLimited { retry_after } if retry_after > 0 && retry_after <= 30),
+            "{error}"
+        );
+        let response = error.error_response();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert!(response.headers().contains_key(header::RETRY_AFTER));
+
+        // Other clients are not affected.
+        assert!(access
+            .authorize(&request("/api/gelu-1l/neuroscope", Some("team-key")))
+            .is_ok());
+    }
+
+    #[test]
+    fn rate_limits_invalid_keys_by_ip_address() {
+        let access = Access::new(access_config());
+        for _ in 0..3 {
+            assert_eq!(
+                status(access.authorize(&request("/api/models", Some("guess")))),
+                StatusCode::UNAUTHORIZED
+            );
+        }
+        assert_eq!(
+            status(access.authorize(&request("/api/models", Some("guess")))),
+            StatusCode::TOO_MANY_REQUESTS
+        );
+        // Requests without a key share the limit of the IP address.
+        assert_eq!(
+            status(access.authorize(&request("/api/models", None))),
+            StatusCode::TOO_MANY_REQUESTS
+        );
+    }
+
+    #[test]
+    fn takes_client_ip_addresses_from_trusted_proxies() {
+        let client_ip = |trusted_proxies, forwarded_for: &[&str]| {
+            let access = Access::new(AccessConfig {
+                trusted_proxies,
+                ..Default::default()
+            });
+            let mut request = TestRequest::get().peer_addr("10.0.0.1:1234".parse().unwrap());
+            for value in forwarded_for {
+                request = request.append_header((X_FORWARDED_FOR, *value));
+            }
+            access.client_ip(&request.to_srv_request())
+        };
+
+        assert_eq!(client_ip(0, &["1.1.1.1"]), "10.0.0.1");
+        assert_eq!(client_ip(1, &[]), "10.0.0.1");
+        // Entries before those of the trusted proxies may be forged by the client.
+        assert_eq!(client_ip(1, &["6.6.6.6, 1.1.1.1"]), "1.1.1.1");
+        assert_eq!(client_ip(2, &["6.6.6.6, 1.1.1.1", "2.2.2.2"]), "1.1.1.1");
+        assert_eq!(client_ip(3, &["1.1.1.1, 2.2.2.2"]), "10.0.0.1");
+    }
+
+    #[test]
+    fn forgets_clients_back_to_their_full_allowance() {
+        let rate_limiter = RateLimiter::default();
+        let rate_limit = RateLimit {
+            requests: 1,
+            seconds: 60,
+        };
+        for client in 0..MAX_TRACKED_CLIENTS {
+            rate_limiter
+                .acquire(&client.to_string(), rate_limit)
+                .unwrap();
+        }
+        // All tracked clients are still limited, so none are forgotten until twice as many are
+        // tracked.
+        rate_limiter.acquire("new", rate_limit).unwrap();
+        let clients = rate_limiter.clients.lock().unwrap();
+        assert_eq!(clients.replenished.len(), MAX_TRACKED_CLIENTS + 1);
+        assert_eq!(clients.prune_at, 2 * MAX_TRACKED_CLIENTS);
+    }
+
+    #[test]
+    fn grants_nothing_without_the_middleware() {
+        let grant = Grant::of(&TestRequest::get().to_http_request());
+        assert!(!grant.allows_model("solu-1l"));
+        assert!(!grant.allows_service("neuroscope"));
+    }
+
+    #[test]
+    fn rejects_duplicate_keys_and_empty_rate_limits() {
+        assert!(access_config().check().is_ok());
+        let mut duplicate = access_config();
+        duplicate.api_keys[1].key = "team-key".to_owned();
+        assert!(duplicate.check().is_err());
+        let mut empty = access_config();
+        empty.ip_rate_limit = Some(RateLimit {
+            requests: 0,
+            seconds: 1,
+        });
+        assert!(empty.check().is_err());
+    }
+}
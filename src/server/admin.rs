@@ -0,0 +1,108 @@
+use actix_web::{
+    get,
+    http::header::{self, ContentType},
+    post, web, HttpRequest, HttpResponse, Responder,
+};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use super::{access::tokens_match, MemoryReport, State};
+
+/// Paths of the admin endpoints, which check the admin token instead of API key scopes.
+pub const ADMIN_PATHS: [&str; 2] = ["/api/admin/reload", "/api/admin/memory"];
+
+#[derive(Deserialize, IntoParams)]
+pub struct ReloadQuery {
+    /// Name of the model to reload. All models are reloaded if omitted.
+    model: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ReloadSummary {
+    /// The reloaded model, or null if all models were reloaded.
+    model: Option<String>,
+    /// Models whose neuron stores were dropped and will be loaded again on next use.
+    neuron_stores: Vec<String>,
+}
+
+/// Returns the error response for a request that does not bear the admin token of the server.
+pub(super) fn authorization_error(state: &State, request: &HttpRequest) -> Option<HttpResponse> {
+    let Some(admin_token) = state.admin_token() else {
+        return Some(HttpResponse::NotFound().body("Admin endpoints are disabled."));
+    };
+    let token = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    match token {
+        Some(token) if tokens_match(token, admin_token) => None,
+        _ => Some(
+            HttpResponse::Unauthorized()
+                .insert_header((header::WWW_AUTHENTICATE, "Bearer"))
+                .body("Missing or invalid admin token."),
+        ),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/admin/reload",
+    params(ReloadQuery),
+    responses(
+        (status = 200, description = "Neuron stores and the model catalogue were invalidated.", body = ReloadSummary),
+        (status = 401, description = "The admin token is missing or invalid."),
+        (status = 404, description = "No admin token is configured."),
+        (status = 503, description = "The model could not be rescanned.")
+    ),
+    security(("admin_token" = []))
+)]
+#[post("/api/admin/reload")]
+pub async fn reload(
+    state: web::Data<State>,
+    request: HttpRequest,
+    query: web::Query<ReloadQuery>,
+) -> impl Responder {
+    if let Some(response) = authorization_error(&state, &request) {
+        return response;
+    }
+    let ReloadQuery { model } = query.into_inner();
+    let body = state
+        .reload(model.as_deref())
+        .await
+        .and_then(|neuron_stores| {
+            Ok(serde_json::to_string(&ReloadSummary {
+                model,
+                neuron_stores,
+            })?)
+        });
+    match body {
+        Ok(body) => HttpResponse::Ok()
+            .content_type(ContentType::json())
+            .body(body),
+        Err(error) => HttpResponse::ServiceUnavailable().body(format!("{error:#}")),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/admin/memory",
+    responses(
+        (status = 200, description = "Memory used by the loaded neuron stores, neuroscope layer archives and model catalogue.", body = MemoryReport),
+        (status = 401, description = "The admin token is missing or invalid."),
+        (status = 404, description = "No admin token is configured.")
+    ),
+    security(("admin_token" = []))
+)]
+#[get("/api/admin/memory")]
+pub async fn memory(state: web::Data<State>, request: HttpRequest) -> impl Responder {
+    if let Some(response) = authorization_error(&state, &request) {
+        return response;
+    }
+    match serde_json::to_string(&state.memory_report()) {
+        Ok(body) => HttpResponse::Ok()
+            .content_type(ContentType::json())
+            .body(body),
+        Err(error) => HttpResponse::InternalServerError().body(format!("{error}")),
+    }
+}
@@ -0,0 +1,296 @@
+use std::{collections::BTreeMap, iter};
+
+use actix_web::{
+    http::header::ContentType, post, web, HttpRequest, HttpResponse, Responder, ResponseError,
+};
+use futures::{stream, StreamExt};
+use itertools::Either;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::data::NeuronIndex;
+
+use super::{service_page, Grant, Page, PageIndex, Service, State};
+
+const MAX_BATCH_SIZE: usize = 4096;
+const MAX_CONCURRENT_PAGES: usize = 64;
+
+#[derive(Clone, Copy, Debug, Deserialize, ToSchema)]
+#[serde(untagged)]
+pub enum NeuronSelection {
+    Neuron(NeuronIndex),
+    /// All neurons in `start..end` of the given layer.
+    Range {
+        layer: u32,
+        start: u32,
+        end: u32,
+    },
+}
+
+impl NeuronSelection {
+    fn len(&self) -> usize {
+        match *self {
+            NeuronSelection::Neuron(_) => 1,
+            NeuronSelection::Range { start, end, .. } => end.saturating_sub(start) as usize,
+        }
+    }
+
+    fn neuron_indices(self) -> impl Iterator<Item = NeuronIndex> {
+        match self {
+            NeuronSelection::Neuron(neuron_index) => Either::Left(iter::once(neuron_index)),
+            NeuronSelection::Range { layer, start, end } => {
+                Either::Right((start..end).map(move |neuron| NeuronIndex { layer, neuron }))
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, ToSchema)]
+pub struct BatchRequest {
+    neurons: Vec<NeuronSelection>,
+    /// Names of the services to query. All services are queried if omitted.
+    services: Option<Vec<String>>,
+}
+
+#[derive(Clone, Debug, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum BatchItem {
+    Data(Page),
+    Error(String),
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/{model_name}/batch",
+    params(("model_name" = String, Path, description = "Name of the model.")),
+    request_body = BatchRequest,
+    responses(
+        (
+            status = 200,
+            description = "The neuron pages or errors by neuron index and service name.",
+            body = BTreeMap<String, BTreeMap<String, BatchItem>>
+        ),
+        (status = 400, description = "No services or too many pages were requested."),
+        (status = 403, description = "The API key does not grant access to a service."),
+        (status = 404, description = "A service was not found.")
+    )
+)]
+#[post("/api/{model_name}/batch")]
+pub async fn batch(
+    state: web::Data<State>,
+    http_request: HttpRequest,
+    model_name: web::Path<String>,
+    query: web::Query<serde_json::Value>,
+    request: web::Json<BatchRequest>,
+) -> impl Responder {
+    let model_name = model_name.into_inner();
+    let BatchRequest { neurons, services } = request.into_inner();
+    let grant = Grant::of(&http_request);
+
+    let services = match services {
+        Some(service_names) => {
+            if let Err(error) = service_names
+                .iter()
+                .try_for_each(|service_name| grant.check_service(service_name))
+            {
+                return error.error_response();
+            }
+            match service_names
+                .iter()
+                .map(|service_name| {
+                    state
+                        .payload()
+                        .service(service_name)
+                        .ok_or(service_name.as_str())
+                })
+                .collect::<Result<Vec<_>, _>>()
+            {
+                Ok(services) => services,
+                Err(service_name) => {
+                    return HttpResponse::NotFound()
+                        .body(format!("Service '{service_name}' not found."))
+                }
+            }
+        }
+        None => state
+            .payload()
+            .services()
+            .filter(|service| grant.allows_service(service.name()))
+            .collect(),
+    };
+
+    if services.is_empty() {
+        return HttpResponse::BadRequest().body("Batch requests at least one service.");
+    }
+    let num_neurons = neurons.iter().map(NeuronSelection::len).sum::<usize>();
+    if num_neurons > MAX_BATCH_SIZE {
+        return HttpResponse::BadRequest().body(format!(
+            "Batch requests at most {MAX_BATCH_SIZE} neurons, but {num_neurons} were requested."
+        ));
+    }
+    let num_pages = num_neurons * services.len();
+    if num_pages > MAX_BATCH_SIZE {
+        return HttpResponse::BadRequest().body(format!(
+            "Batch requests at most {MAX_BATCH_SIZE} pages, but {num_pages} were requested."
+        ));
+    }
+
+    let requests = neurons
+        .into_iter()
+        .flat_map(NeuronSelection::neuron_indices)
+        .flat_map(|neuron_index| services.iter().map(move |&service| (neuron_index, service)));
+    let pages = stream::iter(requests)
+        .map(|(neuron_index, service): (NeuronIndex, &Service)| {
+            let state = state.as_ref();
+            let query = &*query;
+            let model_name = model_name.as_str();
+            async move {
+                let page = service_page(
+                    state,
+                    query,
+                    service,
+                    model_name,
+                    PageIndex::Neuron(neuron_index.layer, neuron_index.neuron),
+                )
+                .await;
+                (neuron_index, service.name(), page)
+            }
+        })
+        .buffer_unordered(MAX_CONCURRENT_PAGES)
+        .collect::<Vec<_>>()
+        .await;
+
+    let mut results: BTreeMap<String, BTreeMap<&str, BatchItem>> = BTreeMap::new();
+    for (neuron_index, service_name, page) in pages {
+        let item = match page {
+            Ok(page) => BatchItem::Data(page),
+            Err(error) => BatchItem::Error(format!("{error:#}")),
+        };
+        results
+            .entry(neuron_index.to_string())
+            .or_default()
+            .insert(service_name, item);
+    }
+
+    match serde_json::to_string(&results) {
+        Ok(body) => HttpResponse::Ok()
+            .content_type(ContentType::json())
+            .body(body),
+        Err(error) => HttpResponse::InternalServerError().body(format!("{error}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{
+        http::StatusCode,
+        middleware::from_fn,
+        test::{self, TestRequest},
+        App,
+    };
+    use serde_json::{json, Value};
+
+    use super::*;
+    use crate::{
+        data::Payload,
+        server::{access, AccessConfig},
+    };
+
+    /// A service answering every page request with the request itself.
+    fn echo_service(name: &str) -> Service {
+        serde_json::from_value(json!({
+            "name": name,
+            "provider": { "Proxy": { "backend": { "Process": {
+                "command": "sed",
+                "args": ["-u", "s/.*/{\"data\":&}/"],
+            } } } },
+        }))
+        .unwrap()
+    }
+
+    async fn post_batch(body: Value) -> (StatusCode, Value) {
+        let mut payload = Payload::initialize();
+        payload.add_service(echo_service("echo")).unwrap();
+        payload.add_service(echo_service("other")).unwrap();
+        let access = serde_json::from_value::<AccessConfig>(json!({
+            "anonymous": { "services": ["echo", "other", "missing"] },
+        }))
+        .unwrap();
+        let state = web::Data::new(State::new(payload).with_access(access));
+        let app = test::init_service(
+            App::new()
+                .wrap(from_fn(access::enforce))
+                .app_data(state)
+                .service(batch),
+        )
+        .await;
+        let request = TestRequest::post()
+            .uri("/api/toy/batch")
+            .set_json(body)
+            .to_request();
+        let response = test::call_service(&app, request).await;
+        let status = response.status();
+        let body = test::read_body(response).await;
+        let body = serde_json::from_slice(&body)
+            .unwrap_or_else(|_| Value::String(String::from_utf8_lossy(&body).into_owned()));
+        (status, body)
+    }
+
+    #[actix_web::test]
+    async fn returns_the_pages_by_neuron_and_service() {
+        let (status, body) = post_batch(json!({
+            "neurons": [{ "layer": 0, "neuron": 1 }, { "layer": 1, "start": 2, "end": 4 }],
+            "services": ["echo", "other"],
+        }))
+        .await;
+        assert_eq!(status, StatusCode::OK, "{body}");
+        let neurons: Vec<_> = body.as_object().unwrap().keys().cloned().collect();
+        assert_eq!(neurons, ["l0n1", "l1n2", "l1n3"]);
+        let page = &body["l1n3"]["other"]["data"];
+        assert_eq!(
+            (&page["service"], &page["layer"], &page["neuron"]),
+            (&json!("other"), &json!(1), &json!(3))
+        );
+    }
+
+    #[actix_web::test]
+    async fn queries_the_allowed_services_by_default() {
+        let (status, body) = post_batch(json!({ "neurons": [{ "layer": 0, "neuron": 1 }] })).await;
+        assert_eq!(status, StatusCode::OK, "{body}");
+        let services: Vec<_> = body["l0n1"].as_object().unwrap().keys().cloned().collect();
+        assert_eq!(services, ["echo", "other"]);
+    }
+
+    #[actix_web::test]
+    async fn rejects_too_many_pages() {
+        let pages = |end: u32| {
+            json!({
+                "neurons": [{ "layer": 0, "start": 0, "end": end }],
+                "services": ["echo", "other"],
+            })
+        };
+        let (status, body) = post_batch(pages(MAX_BATCH_SIZE as u32 / 2 + 1)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{body}");
+        let (status, body) = post_batch(pages(u32::MAX)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{body}");
+    }
+
+    #[actix_web::test]
+    async fn rejects_an_empty_service_list() {
+        let (status, body) = post_batch(json!({
+            "neurons": [{ "layer": 0, "start": 0, "end": u32::MAX }],
+            "services": [],
+        }))
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{body}");
+    }
+
+    #[actix_web::test]
+    async fn rejects_services_that_are_missing_or_not_granted() {
+        let neurons = json!([{ "layer": 0, "neuron": 1 }]);
+        let (status, _) = post_batch(json!({ "neurons": neurons, "services": ["missing"] })).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (status, _) = post_batch(json!({ "neurons": neurons, "services": ["metadata"] })).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+}
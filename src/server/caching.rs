@@ -0,0 +1,280 @@
+use std::{
+    fs,
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use actix_web::{
+    http::header::{
+        self, CacheControl, CacheDirective, ContentType, EntityTag, Header, IfModifiedSince,
+        IfNoneMatch, LastModified,
+    },
+    HttpRequest, HttpResponse, HttpResponseBuilder,
+};
+use sha2::{Digest, Sha256};
+
+use super::access::API_KEY_HEADER;
+
+/// How long clients may use cached responses, and which caches may keep them.
+#[derive(Clone, Copy)]
+pub struct CachePolicy {
+    pub max_age: u32,
+    /// Whether responses depend on the API key of the request, so shared caches must not keep
+    /// them.
+    pub private: bool,
+}
+
+/// Validators of a response, with which clients can ask whether their cached copy is still valid.
+pub struct Validators {
+    etag: EntityTag,
+    last_modified: Option<SystemTime>,
+}
+
+fn entity_tag(hasher: Sha256) -> EntityTag {
+    let digest = hasher.finalize();
+    let tag = digest[..16]
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect();
+    EntityTag::new_strong(tag)
+}
+
+impl Validators {
+    /// Derives the validators of the response to a request from the modification times and sizes
+    /// of the files it is produced from, so they are known before producing it. Returns `None` if
+    /// there are no files or one of them cannot be read.
+    pub fn from_files(request: &HttpRequest, files: &[PathBuf]) -> Option<Self> {
+        if files.is_empty() {
+            return None;
+        }
+        let mut hasher = Sha256::new();
+        hasher.update(env!("CARGO_PKG_VERSION"));
+        hasher.update(request.uri().to_string());
+        let mut last_modified = UNIX_EPOCH;
+        for path in files {
+            let metadata = fs::metadata(path).ok()?;
+            let modified = metadata.modified().ok()?;
+            hasher.update(path.to_string_lossy().as_bytes());
+            hasher.update(
+                modified
+                    .duration_since(UNIX_EPOCH)
+                    .ok()?
+                    .as_nanos()
+                    .to_le_bytes(),
+            );
+            hasher.update(metadata.len().to_le_bytes());
+            last_modified = last_modified.max(modified);
+        }
+        Some(Self {
+            etag: entity_tag(hasher),
+            last_modified: Some(last_modified),
+        })
+    }
+
+    /// Derives the validators of a response from its body.
+    pub fn from_body(body: &[u8]) -> Self {
+        let mut hasher = Sha256::new();
+        hasher.update(body);
+        Self {
+            etag: entity_tag(hasher),
+            last_modified: None,
+        }
+    }
+
+    /// Whether the copy the client has cached, as told by the conditional headers of the request,
+    /// is still valid.
+    pub fn is_fresh(&self, request: &HttpRequest) -> bool {
+        if request.headers().contains_key(header::IF_NONE_MATCH) {
+            return match IfNoneMatch::parse(request) {
+                Ok(IfNoneMatch::Any) => true,
+                Ok(IfNoneMatch::Items(etags)) => etags.iter().any(|etag| etag.weak_eq(&self.etag)),
+                Err(_) => false,
+            };
+        }
+        match (self.last_modified, IfModifiedSince::parse(request)) {
+            (Some(last_modified), Ok(IfModifiedSince(since))) => {
+                // Dates in headers have a resolution of seconds.
+                let last_modified = last_modified
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0, |duration| duration.as_secs());
+                SystemTime::from(since)
+                    .duration_since(UNIX_EPOCH)
+                    .is_ok_and(|since| since.as_secs() >= last_modified)
+            }
+            _ => false,
+        }
+    }
+
+    fn insert_headers(&self, response: &mut HttpResponseBuilder, policy: CachePolicy) {
+        response.insert_header(header::ETag(self.etag.clone()));
+        if let Some(last_modified) = self.last_modified {
+            response.insert_header(LastModified(last_modified.into()));
+        }
+        let visibility = if policy.private {
+            // The CORS middleware only keeps the first `Vary` header, so this one also lists the
+            // header that compression depends on.
+            response.insert_header((
+                header::VARY,
+                format!("{API_KEY_HEADER}, {}", header::ACCEPT_ENCODING),
+            ));
+            CacheDirective::Private
+        } else {
+            CacheDirective::Public
+        };
+        let cache_control = if policy.max_age == 0 {
+            vec![visibility, CacheDirective::NoCache]
+        } else {
+            vec![visibility, CacheDirective::MaxAge(policy.max_age)]
+        };
+        response.insert_header(CacheControl(cache_control));
+    }
+
+    /// Responds with 304 Not Modified.
+    pub fn not_modified(&self, policy: CachePolicy) -> HttpResponse {
+        let mut response = HttpResponse::NotModified();
+        self.insert_headers(&mut response, policy);
+        response.finish()
+    }
+
+    /// Responds with the JSON body, or with 304 Not Modified if the client has it cached.
+    pub fn json_response(
+        &self,
+        request: &HttpRequest,
+        policy: CachePolicy,
+        body: String,
+    ) -> HttpResponse {
+        if self.is_fresh(request) {
+            return self.not_modified(policy);
+        }
+        let mut response = HttpResponse::Ok();
+        self.insert_headers(&mut response, policy);
+        response.content_type(ContentType::json()).body(body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, process, time::Duration};
+
+    use actix_web::{body::to_bytes, http::StatusCode, test::TestRequest};
+
+    use super::*;
+
+    const PUBLIC: CachePolicy = CachePolicy {
+        max_age: 0,
+        private: false,
+    };
+
+    fn etag(validators: &Validators) -> String {
+        validators.etag.to_string()
+    }
+
+    #[actix_web::test]
+    async fn answers_requests_for_cached_pages_with_not_modified() {
+        let validators = Validators::from_body(b"{}");
+        let fresh_request = TestRequest::get()
+            .insert_header((header::IF_NONE_MATCH, etag(&validators)))
+            .to_http_request();
+        let response = validators.json_response(&fresh_request, PUBLIC, "{}".to_owned());
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+
+        let stale_request = TestRequest::get()
+            .insert_header((header::IF_NONE_MATCH, etag(&Validators::from_body(b"[]"))))
+            .to_http_request();
+        let response = validators.json_response(&stale_request, PUBLIC, "{}".to_owned());
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers().get(header::ETAG).unwrap(),
+            etag(&validators).as_str()
+        );
+        assert_eq!(to_bytes(response.into_body()).await.unwrap(), "{}");
+    }
+
+    #[test]
+    fn compares_entity_tags_weakly() {
+        let validators = Validators::from_body(b"{}");
+        let is_fresh = |if_none_match: String| {
+            validators.is_fresh(
+                &TestRequest::get()
+                    .insert_header((header::IF_NONE_MATCH, if_none_match))
+                    .to_http_request(),
+            )
+        };
+        assert!(is_fresh(format!("W/{}", etag(&validators))));
+        assert!(is_fresh(format!("\"other\", {}", etag(&validators))));
+        assert!(is_fresh("*".to_owned()));
+        assert!(!is_fresh("\"other\"".to_owned()));
+        assert!(!validators.is_fresh(&TestRequest::get().to_http_request()));
+    }
+
+    #[test]
+    fn derives_validators_from_files() {
+        let path = env::temp_dir().join(format!("neuronav-caching-{}.json", process::id()));
+        fs::write(&path, "{}").unwrap();
+        let files = [path.clone()];
+        let request = TestRequest::get()
+            .uri("/api/solu-1l/neuroscope/0/1")
+            .to_http_request();
+        let validators = Validators::from_files(&request, &files).unwrap();
+        fs::write(&path, "{\"changed\":1}").unwrap();
+        let changed = Validators::from_files(&request, &files).unwrap();
+        let other_request = TestRequest::get()
+            .uri("/api/solu-1l/neuroscope/0/2")
+            .to_http_request();
+        let other = Validators::from_files(&other_request, &files).unwrap();
+        let last_modified = changed.last_modified.unwrap();
+        let _ = fs::remove_file(&path);
+
+        assert_ne!(etag(&validators), etag(&changed));
+        assert_ne!(etag(&changed), etag(&other));
+        assert!(Validators::from_files(&request, &files).is_none());
+        assert!(Validators::from_files(&request, &[]).is_none());
+
+        let if_modified_since = |since: SystemTime| {
+            changed.is_fresh(
+                &TestRequest::get()
+                    .insert_header(IfModifiedSince(since.into()))
+                    .to_http_request(),
+            )
+        };
+        assert!(if_modified_since(last_modified + Duration::from_secs(1)));
+        assert!(!if_modified_since(last_modified - Duration::from_secs(1)));
+        // An entity tag takes precedence over the date.
+        let request = TestRequest::get()
+            .insert_header(IfModifiedSince(
+                (last_modified + Duration::from_secs(1)).into(),
+            ))
+            .insert_header((header::IF_NONE_MATCH, "\"other\""))
+            .to_http_request();
+        assert!(!changed.is_fresh(&request));
+    }
+
+    #[test]
+    fn keeps_responses_depending_on_the_api_key_out_of_shared_caches() {
+        let validators = Validators::from_body(b"{}");
+        let cache_control = |policy| {
+            let response = validators.not_modified(policy);
+            let header = |name| {
+                response
+                    .headers()
+                    .get(name)
+                    .map(|value: &header::HeaderValue| value.to_str().unwrap().to_owned())
+            };
+            (header(header::CACHE_CONTROL), header(header::VARY))
+        };
+        assert_eq!(
+            cache_control(PUBLIC),
+            (Some("public, no-cache".to_owned()), None)
+        );
+        assert_eq!(
+            cache_control(CachePolicy {
+                max_age: 60,
+                private: true,
+            }),
+            (
+                Some("private, max-age=60".to_owned()),
+                Some(format!("{API_KEY_HEADER}, accept-encoding"))
+            )
+        );
+    }
+}
@@ -0,0 +1,79 @@
+use std::{fs, path::Path};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::data::Payload;
+
+use super::{AccessConfig, Frontend, Service};
+
+/// Configuration of the server, read from a JSON file. Missing fields take their default values.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub url: String,
+    pub port: u16,
+    /// Services served in addition to the default ones.
+    pub services: Vec<Service>,
+    /// Token expected in the `Authorization: Bearer` header of admin requests. Admin endpoints
+    /// are disabled without one.
+    pub admin_token: Option<String>,
+    /// Models whose neuron stores are loaded right after the server starts.
+    pub preload: Vec<String>,
+    /// Heap memory in MiB that loaded neuron stores may use before the least recently used are
+    /// dropped. Memory mapped compiled stores do not count against it.
+    pub memory_budget_mb: Option<u64>,
+    /// Serves the pages of the frontend next to the API.
+    pub frontend: Option<Frontend>,
+    /// Origins allowed to call the API from a browser, or `*` for any origin.
+    pub cors_origins: Vec<String>,
+    /// Whether responses are compressed with gzip, brotli or zstd when the client accepts it.
+    pub compress: bool,
+    /// Seconds clients may use cached pages before revalidating them. They always revalidate if 0.
+    pub cache_max_age: u32,
+    /// API keys, the models and services they may access, and rate limits.
+    pub access: AccessConfig,
+    /// Whether metrics are served at `/metrics` in the Prometheus text format, to requests bearing
+    /// the admin token.
+    pub metrics: bool,
+}
+
+impl Config {
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read server config '{path:?}'."))?;
+        serde_json::from_str(&text)
+            .with_context(|| format!("Failed to parse server config '{path:?}'."))
+    }
+
+    /// The default payload with the configured services added.
+    pub fn payload(&self) -> Result<Payload> {
+        let mut payload = Payload::default();
+        for service in &self.services {
+            payload
+                .add_service(service.clone())
+                .context("Invalid service in server config.")?;
+        }
+        Ok(payload)
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            url: "127.0.0.1".to_owned(),
+            port: 8080,
+            services: Vec::new(),
+            admin_token: None,
+            preload: Vec::new(),
+            memory_budget_mb: None,
+            frontend: None,
+            cors_origins: Vec::new(),
+            compress: true,
+            cache_max_age: 0,
+            access: AccessConfig::default(),
+            metrics: false,
+        }
+    }
+}
@@ -0,0 +1,388 @@
+use std::{collections::BTreeMap, sync::Arc};
+
+use actix_web::{
+    error::ErrorInternalServerError, get, http::header::CONTENT_TYPE, web, HttpRequest,
+    HttpResponse, Responder, ResponseError,
+};
+use anyhow::{bail, Result};
+use futures::{stream, Stream, StreamExt};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::data::{ModelMetadata, NeuronIndex};
+
+use super::{service_page, Grant, Page, PageIndex, State};
+
+const MAX_BUFFERED_NEURONS: usize = 16;
+
+/// A line of an export, with the pages of a neuron by service name.
+#[derive(Serialize, ToSchema)]
+pub struct ExportLine<'a> {
+    layer: u32,
+    neuron: u32,
+    services: BTreeMap<&'a str, Page>,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum ExportScope {
+    Model,
+    Layer(u32),
+}
+
+/// Streams one JSON line per neuron in the given scope, containing the neuron pages of the given
+/// services. Services without a page for a neuron are left out of its line, and neurons without
+/// any pages are skipped.
+pub fn export_stream(
+    state: Arc<State>,
+    query: serde_json::Value,
+    model_name: &str,
+    scope: ExportScope,
+    service_names: Option<Vec<String>>,
+) -> Result<impl Stream<Item = Result<String>>> {
+    let model_metadata = ModelMetadata::from_file("data", model_name)?;
+    let neuron_indices: Vec<NeuronIndex> = match scope {
+        ExportScope::Model => model_metadata.neuron_indices().collect(),
+        ExportScope::Layer(layer_index) => match model_metadata.layers.get(layer_index as usize) {
+            Some(layer_metadata) => layer_metadata.neuron_indices(layer_index).collect(),
+            None => bail!("Layer {layer_index} does not exist in model '{model_name}'."),
+        },
+    };
+
+    let service_names = match service_names {
+        Some(service_names) => {
+            if let Some(service_name) = service_names
+                .iter()
+                .find(|service_name| state.payload().service(service_name).is_none())
+            {
+                bail!("Service '{service_name}' not found.");
+            }
+            service_names
+        }
+        None => {
+            let mut service_names: Vec<_> = state
+                .payload()
+                .services()
+                .filter(|service| !service.is_metadata())
+                .map(|service| service.name().to_owned())
+                .collect();
+            service_names.sort_unstable();
+            service_names
+        }
+    };
+
+    let model_name: Arc<str> = model_name.into();
+    let service_names: Arc<[String]> = service_names.into();
+    let query = Arc::new(query);
+    let lines =
+        stream::iter(neuron_indices)
+            .map(move |neuron_index| {
+                let state = Arc::clone(&state);
+                let query = Arc::clone(&query);
+                let model_name = Arc::clone(&model_name);
+                let service_names = Arc::clone(&service_names);
+                async move {
+                    neuron_line(&state, &query, &model_name, &service_names, neuron_index).await
+                }
+            })
+            .buffered(MAX_BUFFERED_NEURONS)
+            .filter_map(|line| async move { line.transpose() });
+    Ok(lines)
+}
+
+async fn neuron_line(
+    state: &State,
+    query: &serde_json::Value,
+    model_name: &str,
+    service_names: &[String],
+    neuron_index: NeuronIndex,
+) -> Result<Option<String>> {
+    let NeuronIndex {
+        layer: layer_index,
+        neuron: neuron_index,
+    } = neuron_index;
+    let mut services = BTreeMap::new();
+    for service_name in service_names {
+        let service = state
+            .payload()
+            .service(service_name)
+            .expect("Services should be validated before exporting.");
+        if let Ok(page) = service_page(
+            state,
+            query,
+            service,
+            model_name,
+            PageIndex::Neuron(layer_index, neuron_index),
+        )
+        .await
+        {
+            services.insert(service_name.as_str(), page);
+        }
+    }
+    if services.is_empty() {
+        return Ok(None);
+    }
+
+    let mut line = serde_json::to_string(&ExportLine {
+        layer: layer_index,
+        neuron: neuron_index,
+        services,
+    })?;
+    line.push('\n');
+    Ok(Some(line))
+}
+
+async fn export_response(
+    state: web::Data<State>,
+    request: &HttpRequest,
+    query: web::Query<serde_json::Value>,
+    model_name: String,
+    scope: ExportScope,
+) -> impl Responder {
+    let query = query.into_inner();
+    let grant = Grant::of(request);
+    let service_names = match query["services"].as_str() {
+        Some(service_names) => {
+            let service_names: Vec<_> = service_names.split(',').map(str::to_owned).collect();
+            if let Err(error) = service_names
+                .iter()
+                .try_for_each(|service_name| grant.check_service(service_name))
+            {
+                return error.error_response();
+            }
+            Some(service_names)
+        }
+        // Clients restricted to some services export those of them that exist.
+        None => grant.scope.services.as_ref().map(|service_names| {
+            service_names
+                .iter()
+                .filter(|service_name| {
+                    state
+                        .payload()
+                        .service(service_name)
+                        .is_some_and(|service| !service.is_metadata())
+                })
+                .cloned()
+                .collect()
+        }),
+    };
+    match export_stream(
+        state.into_inner(),
+        query,
+        model_name.as_str(),
+        scope,
+        service_names,
+    ) {
+        Ok(lines) => HttpResponse::Ok()
+            .insert_header((CONTENT_TYPE, "application/x-ndjson"))
+            .streaming(lines.map(|line| {
+                line.map(web::Bytes::from)
+                    .map_err(|error| ErrorInternalServerError(format!("{error}")))
+            })),
+        Err(error) => HttpResponse::NotFound().body(format!("{error}")),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/{model_name}/export",
+    params(("model_name" = String, Path, description = "Name of the model."), ("services" = Option<String>, Query, description = "Comma separated names of the services to export. All services but metadata are exported if omitted.")),
+    responses(
+        (
+            status = 200,
+            description = "One line per neuron with the neuron pages of the selected services.",
+            body = ExportLine,
+            content_type = "application/x-ndjson"
+        ),
+        (status = 403, description = "The API key does not grant access to a service."),
+        (status = 404, description = "The model, layer or a service was not found.")
+    )
+)]
+#[get("/api/{model_name}/export")]
+pub async fn export_model(
+    state: web::Data<State>,
+    request: HttpRequest,
+    model_name: web::Path<String>,
+    query: web::Query<serde_json::Value>,
+) -> impl Responder {
+    export_response(
+        state,
+        &request,
+        query,
+        model_name.into_inner(),
+        ExportScope::Model,
+    )
+    .await
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/{model_name}/export/{layer_index}",
+    params(("model_name" = String, Path, description = "Name of the model."), ("layer_index" = u32, Path, description = "Index of the layer."), ("services" = Option<String>, Query, description = "Comma separated names of the services to export. All services but metadata are exported if omitted.")),
+    responses(
+        (
+            status = 200,
+            description = "One line per neuron with the neuron pages of the selected services.",
+            body = ExportLine,
+            content_type = "application/x-ndjson"
+        ),
+        (status = 403, description = "The API key does not grant access to a service."),
+        (status = 404, description = "The model, layer or a service was not found.")
+    )
+)]
+#[get("/api/{model_name}/export/{layer_index}")]
+pub async fn export_layer(
+    state: web::Data<State>,
+    request: HttpRequest,
+    indices: web::Path<(String, u32)>,
+    query: web::Query<serde_json::Value>,
+) -> impl Responder {
+    let (model_name, layer_index) = indices.into_inner();
+    export_response(
+        state,
+        &request,
+        query,
+        model_name,
+        ExportScope::Layer(layer_index),
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::Path, process};
+
+    use actix_web::{
+        http::StatusCode,
+        middleware::from_fn,
+        test::{self, TestRequest},
+        App,
+    };
+    use serde_json::{json, Value};
+
+    use super::*;
+    use crate::{
+        data::{LayerMetadata, Payload},
+        server::{access, AccessConfig, Service},
+    };
+
+    /// A model in the data directory, which is removed again when dropped.
+    struct TestModel(String);
+
+    impl TestModel {
+        fn new() -> Self {
+            let model_name = format!("export-test-{}", process::id());
+            ModelMetadata {
+                name: model_name.clone(),
+                layers: vec![LayerMetadata { num_neurons: 3 }; 2],
+                activation_function: "gelu".to_owned(),
+                num_total_neurons: 6,
+                num_total_parameters: 0,
+                dataset: "test".to_owned(),
+            }
+            .to_file("data")
+            .unwrap();
+            Self(model_name)
+        }
+    }
+
+    impl Drop for TestModel {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(Path::new("data").join(&self.0));
+            // Only removed if no other data is in it.
+            let _ = fs::remove_dir("data");
+        }
+    }
+
+    /// A service answering page requests with the request itself, except for the first neuron of
+    /// every layer, which has no page.
+    fn echo_service() -> Service {
+        serde_json::from_value(json!({
+            "name": "echo",
+            "provider": { "Proxy": { "backend": { "Process": {
+                "command": "sed",
+                "args": [
+                    "-u",
+                    "-e", "/\"neuron\":0,/{s/.*/{\"error\":\"No page.\",\"status\":404}/;b}",
+                    "-e", "s/.*/{\"data\":&}/",
+                ],
+            } } } },
+        }))
+        .unwrap()
+    }
+
+    async fn get_export(path: &str) -> (StatusCode, Option<String>, String) {
+        let mut payload = Payload::initialize();
+        payload.add_service(echo_service()).unwrap();
+        let access = serde_json::from_value::<AccessConfig>(json!({
+            "anonymous": { "services": ["echo", "missing"] },
+        }))
+        .unwrap();
+        let state = web::Data::new(State::new(payload).with_access(access));
+        let app = test::init_service(
+            App::new()
+                .wrap(from_fn(access::enforce))
+                .app_data(state)
+                .service(export_model)
+                .service(export_layer),
+        )
+        .await;
+        let response = test::call_service(&app, TestRequest::get().uri(path).to_request()).await;
+        let status = response.status();
+        let content_type = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .map(|value| value.to_str().unwrap().to_owned());
+        let body = test::read_body(response).await;
+        (
+            status,
+            content_type,
+            String::from_utf8(body.to_vec()).unwrap(),
+        )
+    }
+
+    fn parse_lines(body: &str) -> Vec<Value> {
+        assert!(body.ends_with('\n'));
+        body.lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    #[actix_web::test]
+    async fn exports_one_line_per_neuron_with_pages() {
+        let model = TestModel::new();
+        let model_name = &model.0;
+
+        let (status, content_type, body) = get_export(&format!("/api/{model_name}/export")).await;
+        assert_eq!(status, StatusCode::OK, "{body}");
+        assert_eq!(content_type.as_deref(), Some("application/x-ndjson"));
+        let lines = parse_lines(&body);
+        let neurons: Vec<_> = lines
+            .iter()
+            .map(|line| (line["layer"].clone(), line["neuron"].clone()))
+            .collect();
+        assert_eq!(
+            neurons,
+            [(0, 1), (0, 2), (1, 1), (1, 2)].map(|(layer, neuron)| (json!(layer), json!(neuron)))
+        );
+        let page = &lines[3]["services"]["echo"];
+        assert_eq!((&page["layer"], &page["neuron"]), (&json!(1), &json!(2)));
+
+        let (status, _, body) = get_export(&format!("/api/{model_name}/export/1")).await;
+        assert_eq!(status, StatusCode::OK, "{body}");
+        assert_eq!(parse_lines(&body).len(), 2);
+
+        let (status, _, body) = get_export(&format!("/api/{model_name}/export/2")).await;
+        assert_eq!(status, StatusCode::NOT_FOUND, "{body}");
+        let (status, _, body) =
+            get_export(&format!("/api/{model_name}/export?services=missing")).await;
+        assert_eq!(status, StatusCode::NOT_FOUND, "{body}");
+    }
+
+    #[actix_web::test]
+    async fn rejects_missing_models_and_services_that_are_not_granted() {
+        let (status, _, body) = get_export("/api/missing-model/export").await;
+        assert_eq!(status, StatusCode::NOT_FOUND, "{body}");
+        let (status, _, body) = get_export("/api/missing-model/export?services=metadata").await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED, "{body}");
+    }
+}
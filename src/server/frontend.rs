@@ -0,0 +1,129 @@
+use std::{
+    borrow::Cow,
+    fs, io,
+    path::{Component, Path, PathBuf},
+};
+
+use actix_web::{get, web, HttpResponse, Responder};
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+
+/// Where the static files of the frontend are served from.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum Frontend {
+    /// Files are read from the directory on every request, so changes show up without a restart.
+    Directory(PathBuf),
+    /// Files embedded into the binary when it is built with the `embedded-frontend` feature.
+    Embedded,
+}
+
+#[cfg(feature = "embedded-frontend")]
+#[derive(rust_embed::RustEmbed)]
+#[folder = "frontend/"]
+struct EmbeddedFiles;
+
+impl Frontend {
+    /// Fails if the files of the frontend are not available.
+    pub fn check(&self) -> Result<()> {
+        match self {
+            Frontend::Directory(path) => {
+                if !path.join("index.html").is_file() {
+                    bail!("Frontend directory '{path:?}' has no 'index.html'.");
+                }
+            }
+            Frontend::Embedded => {
+                if !cfg!(feature = "embedded-frontend") {
+                    bail!("The embedded frontend requires building with the 'embedded-frontend' feature.");
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the file at the path relative to the frontend directory, or `None` if it does not
+    /// exist.
+    fn file(&self, path: &str) -> io::Result<Option<Cow<'static, [u8]>>> {
+        let is_relative = Path::new(path)
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)));
+        if !is_relative {
+            return Ok(None);
+        }
+        match self {
+            Frontend::Directory(directory) => match fs::read(directory.join(path)) {
+                Ok(bytes) => Ok(Some(Cow::Owned(bytes))),
+                Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(None),
+                Err(error) => Err(error),
+            },
+            #[cfg(feature = "embedded-frontend")]
+            Frontend::Embedded => Ok(EmbeddedFiles::get(path).map(|file| file.data)),
+            #[cfg(not(feature = "embedded-frontend"))]
+            Frontend::Embedded => Ok(None),
+        }
+    }
+
+    fn response(&self, path: &str) -> HttpResponse {
+        match self.file(path) {
+            Ok(Some(bytes)) => HttpResponse::Ok()
+                .content_type(mime_guess::from_path(path).first_or_octet_stream())
+                .body(bytes),
+            Ok(None) => HttpResponse::NotFound().body(format!("File '{path}' not found.")),
+            Err(error) => HttpResponse::InternalServerError()
+                .body(format!("Failed to read '{path}': {error}")),
+        }
+    }
+}
+
+#[get("/")]
+async fn index(frontend: web::Data<Frontend>) -> impl Responder {
+    frontend.response("index.html")
+}
+
+#[get("/viz/")]
+async fn viz_index(frontend: web::Data<Frontend>) -> impl Responder {
+    frontend.response("index.html")
+}
+
+#[get("/viz/{model_name}")]
+async fn model_page(frontend: web::Data<Frontend>) -> impl Responder {
+    frontend.response("model.html")
+}
+
+#[get("/viz/{model_name}/{layer_index}")]
+async fn layer_page(frontend: web::Data<Frontend>) -> impl Responder {
+    frontend.response("layer.html")
+}
+
+#[get("/viz/{model_name}/{service}/{layer_index}")]
+async fn service_layer_page(frontend: web::Data<Frontend>) -> impl Responder {
+    frontend.response("layer.html")
+}
+
+#[get("/viz/{model_name}/{service}/{layer_index}/{neuron_index}")]
+async fn neuron_page(frontend: web::Data<Frontend>) -> impl Responder {
+    frontend.response("neuron.html")
+}
+
+#[get("/js/{file_name}")]
+async fn js(frontend: web::Data<Frontend>, file_name: web::Path<String>) -> impl Responder {
+    frontend.response(&format!("js/{file_name}"))
+}
+
+#[get("/css/{file_name}")]
+async fn css(frontend: web::Data<Frontend>, file_name: web::Path<String>) -> impl Responder {
+    frontend.response(&format!("css/{file_name}"))
+}
+
+/// Registers the pages of the frontend, with the same routes as `frontend/server.js`.
+pub fn configure(config: &mut web::ServiceConfig, frontend: Frontend) {
+    config
+        .app_data(web::Data::new(frontend))
+        .service(index)
+        .service(viz_index)
+        .service(model_page)
+        .service(layer_page)
+        .service(service_layer_page)
+        .service(neuron_page)
+        .service(js)
+        .service(css);
+}
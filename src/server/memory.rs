@@ -0,0 +1,38 @@
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use super::{neuroscope_archives::NeuroscopeArchiveMemory, ModelSummary, NeuronStoreMemory};
+
+/// Memory used by the model catalogue.
+#[derive(Clone, Debug, Serialize, ToSchema)]
+pub struct ModelCatalogueMemory {
+    pub models: usize,
+    /// Approximate size of the summaries of the models.
+    pub heap_bytes: u64,
+}
+
+impl ModelCatalogueMemory {
+    pub fn new(models: &[ModelSummary]) -> Self {
+        Self {
+            models: models.len(),
+            heap_bytes: models.iter().map(ModelSummary::heap_size).sum::<usize>() as u64,
+        }
+    }
+}
+
+/// Memory used by what the server loaded from the data directory.
+#[derive(Clone, Debug, Serialize, ToSchema)]
+pub struct MemoryReport {
+    /// Heap memory of the neuron stores and the model catalogue.
+    pub heap_bytes: u64,
+    /// Memory mapped neuron stores and neuroscope layer archives, which the operating system pages
+    /// in and out as needed.
+    pub mapped_bytes: u64,
+    /// Budget for the heap memory of the neuron stores.
+    pub budget_bytes: Option<u64>,
+    /// Most recently used first.
+    pub neuron_stores: Vec<NeuronStoreMemory>,
+    pub neuroscope_archives: Vec<NeuroscopeArchiveMemory>,
+    /// Missing while the models have not been scanned.
+    pub model_catalogue: Option<ModelCatalogueMemory>,
+}
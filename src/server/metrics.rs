@@ -0,0 +1,206 @@
+use std::time::Instant;
+
+use actix_web::{
+    body::MessageBody,
+    dev::{ServiceRequest, ServiceResponse},
+    get,
+    middleware::Next,
+    web, Error, HttpRequest, HttpResponse, Responder,
+};
+use anyhow::Result;
+use prometheus::{
+    exponential_buckets, histogram_opts, opts, Encoder, HistogramVec, IntCounter, IntCounterVec,
+    IntGaugeVec, Registry, TextEncoder,
+};
+
+use super::{admin, MemoryReport, State};
+
+/// Metrics recorded by the neuron stores.
+#[derive(Clone)]
+pub struct NeuronStoreMetrics {
+    /// Requests for a neuron store by whether it was `cached`, `loaded` or failed with an `error`.
+    pub lookups: IntCounterVec,
+    pub load_seconds: HistogramVec,
+    pub evictions: IntCounter,
+    heap_bytes: IntGaugeVec,
+    mapped_bytes: IntGaugeVec,
+}
+
+/// Metrics of the server in the Prometheus text format.
+pub struct Metrics {
+    registry: Registry,
+    requests: IntCounterVec,
+    request_seconds: HistogramVec,
+    page_cache: IntCounterVec,
+    neuron_stores: NeuronStoreMetrics,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self::register().expect("Metrics should have valid and unique names.")
+    }
+
+    fn register() -> Result<Self> {
+        let registry = Registry::new_custom(Some("neuronav".to_owned()), None)?;
+        let requests = IntCounterVec::new(
+            opts!(
+                "http_requests_total",
+                "HTTP requests by route, service and status."
+            ),
+            &["route", "service", "status"],
+        )?;
+        let request_seconds = HistogramVec::new(
+            histogram_opts!(
+                "http_request_duration_seconds",
+                "Time until the response to an HTTP request started, by route, service and status."
+            ),
+            &["route", "service", "status"],
+        )?;
+        let page_cache = IntCounterVec::new(
+            opts!(
+                "page_cache_requests_total",
+                "Page requests by service and whether the client's cached page was still fresh \
+                 (`hit`) or the page was sent (`miss`)."
+            ),
+            &["service", "result"],
+        )?;
+        let neuron_stores = NeuronStoreMetrics {
+            lookups: IntCounterVec::new(
+                opts!(
+                    "neuron_store_lookups_total",
+                    "Requests for neuron stores by whether the store was cached, loaded or failed \
+                     to load."
+                ),
+                &["result"],
+            )?,
+            load_seconds: HistogramVec::new(
+                histogram_opts!(
+                    "neuron_store_load_duration_seconds",
+                    "Time to load neuron stores from disk, by model.",
+                    exponential_buckets(0.01, 4.0, 8)?
+                ),
+                &["model"],
+            )?,
+            evictions: IntCounter::new(
+                "neuron_store_evictions_total",
+                "Neuron stores dropped to stay within the memory budget.",
+            )?,
+            heap_bytes: IntGaugeVec::new(
+                opts!(
+                    "neuron_store_heap_bytes",
+                    "Approximate heap memory used by loaded neuron stores, by model."
+                ),
+                &["model"],
+            )?,
+            mapped_bytes: IntGaugeVec::new(
+                opts!(
+                    "neuron_store_mapped_bytes",
+                    "Size of the memory mapped compiled neuron stores, by model."
+                ),
+                &["model"],
+            )?,
+        };
+        registry.register(Box::new(requests.clone()))?;
+        registry.register(Box::new(request_seconds.clone()))?;
+        registry.register(Box::new(page_cache.clone()))?;
+        registry.register(Box::new(neuron_stores.lookups.clone()))?;
+        registry.register(Box::new(neuron_stores.load_seconds.clone()))?;
+        registry.register(Box::new(neuron_stores.evictions.clone()))?;
+        registry.register(Box::new(neuron_stores.heap_bytes.clone()))?;
+        registry.register(Box::new(neuron_stores.mapped_bytes.clone()))?;
+        Ok(Self {
+            registry,
+            requests,
+            request_seconds,
+            page_cache,
+            neuron_stores,
+        })
+    }
+
+    pub fn neuron_stores(&self) -> &NeuronStoreMetrics {
+        &self.neuron_stores
+    }
+
+    /// Counts a page request of the service, answered from the cache of the client or not.
+    pub fn page_cache(&self, service_name: &str, hit: bool) {
+        let result = if hit { "hit" } else { "miss" };
+        self.page_cache
+            .with_label_values(&[service_name, result])
+            .inc();
+    }
+
+    /// Encodes the metrics, with the sizes of the neuron stores as given by the report.
+    fn encode(&self, memory_report: &MemoryReport) -> Result<Vec<u8>> {
+        let NeuronStoreMetrics {
+            heap_bytes,
+            mapped_bytes,
+            ..
+        } = &self.neuron_stores;
+        heap_bytes.reset();
+        mapped_bytes.reset();
+        for memory in &memory_report.neuron_stores {
+            heap_bytes
+                .with_label_values(&[&memory.model])
+                .set(memory.heap_bytes as i64);
+            mapped_bytes
+                .with_label_values(&[&memory.model])
+                .set(memory.mapped_bytes as i64);
+        }
+        let mut buffer = Vec::new();
+        TextEncoder::new().encode(&self.registry.gather(), &mut buffer)?;
+        Ok(buffer)
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Middleware counting requests and timing them until their response starts. Requests for pages
+/// of a service are labelled with it.
+pub async fn record(
+    request: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let state = request
+        .app_data::<web::Data<State>>()
+        .expect("State should be registered with the app.")
+        .clone();
+    let start = Instant::now();
+    let response = next.call(request).await?;
+    let route = response
+        .request()
+        .match_pattern()
+        .unwrap_or_else(|| "unmatched".to_owned());
+    // Only existing services are labels, so clients cannot create arbitrarily many series.
+    let service_name = response
+        .request()
+        .match_info()
+        .get("service")
+        .filter(|service_name| state.payload().service(service_name).is_some())
+        .unwrap_or_default();
+    let status = response.status();
+    let labels = [route.as_str(), service_name, status.as_str()];
+    let metrics = state.metrics();
+    metrics.requests.with_label_values(&labels).inc();
+    metrics
+        .request_seconds
+        .with_label_values(&labels)
+        .observe(start.elapsed().as_secs_f64());
+    Ok(response)
+}
+
+#[get("/metrics")]
+pub async fn scrape(state: web::Data<State>, request: HttpRequest) -> impl Responder {
+    if let Some(response) = admin::authorization_error(&state, &request) {
+        return response;
+    }
+    match state.metrics().encode(&state.memory_report()) {
+        Ok(body) => HttpResponse::Ok()
+            .content_type(TextEncoder::new().format_type())
+            .body(body),
+        Err(error) => HttpResponse::InternalServerError().body(format!("{error}")),
+    }
+}
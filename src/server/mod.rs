@@ -1,30 +1,55 @@
-use std::{collections::HashMap, ops::Deref, sync::Arc};
+use std::{collections::BTreeMap, ops::Deref, path::PathBuf, sync::Arc, time::Instant};
 
+use actix_cors::Cors;
 use actix_web::{
     get,
-    http::header::ContentType,
+    http::{header, StatusCode},
+    middleware::{from_fn, Compress, Condition},
     rt,
     web::{self},
-    App, HttpResponse, HttpServer, Responder,
+    App, HttpRequest, HttpResponse, HttpServer, Responder,
 };
-use anyhow::Result;
-
-use serde_json::json;
+use anyhow::{Context, Result};
 use tokio::sync::Mutex;
 
-use crate::data::{NeuronStore, Payload};
+use crate::data::{NeuronStore, NeuroscopeLayerArchive, Payload};
+
+use self::{
+    access::Access,
+    caching::{CachePolicy, Validators},
+    metrics::Metrics,
+    neuron_stores::NeuronStores,
+    neuroscope_archives::NeuroscopeArchives,
+};
 
+mod access;
+pub use access::{AccessConfig, AccessError, ApiKey, Grant, RateLimit, Scope};
+mod admin;
+mod batch;
+mod caching;
+mod config;
+pub use config::Config;
+mod export;
+pub use export::{export_stream, ExportScope};
+mod frontend;
+pub use frontend::Frontend;
+mod memory;
+pub use memory::{MemoryReport, ModelCatalogueMemory};
+mod metrics;
+mod models;
+mod neuron_stores;
+mod neuroscope_archives;
+pub use models::ModelSummary;
+pub use neuron_stores::NeuronStoreMemory;
+pub use neuroscope_archives::NeuroscopeArchiveMemory;
+mod openapi;
+mod page;
+pub use page::{schema_ref, EmptyPage, Page, PageIndex, PageLevel, QueryParameter, ServicePage};
 mod service;
-pub use service::Service;
+mod services;
+pub use service::{Service, RESERVED_SERVICE_NAMES};
 mod service_providers;
-pub use service_providers::ServiceProvider;
-
-#[derive(Clone, Copy, Debug)]
-enum PageIndex {
-    Model,
-    Layer(u32),
-    Neuron(u32, u32),
-}
+pub use service_providers::{ProxyError, ServiceProvider};
 
 async fn service_page(
     state: &State,
@@ -32,7 +57,7 @@ async fn service_page(
     service: &Service,
     model_name: &str,
     page_index: PageIndex,
-) -> Result<serde_json::Value> {
+) -> Result<Page> {
     match page_index {
         PageIndex::Model => service.model_page(state, query, model_name).await,
         PageIndex::Layer(layer_index) => {
@@ -48,8 +73,42 @@ async fn service_page(
     }
 }
 
+/// The status to respond with when producing a page failed with the given error.
+fn error_status(error: &anyhow::Error) -> StatusCode {
+    match error.downcast_ref::<ProxyError>() {
+        Some(error) => error.status_code(),
+        None => StatusCode::SERVICE_UNAVAILABLE,
+    }
+}
+
+/// Files the page of the service is read from, together with those of the metadata page it is
+/// served with, or `None` if the service cannot tell.
+fn page_files(
+    state: &State,
+    service: &Service,
+    model_name: &str,
+    page_index: PageIndex,
+) -> Option<Vec<PathBuf>> {
+    let mut files = service
+        .provider()
+        .page_files(service.name(), model_name, page_index);
+    if files.is_empty() {
+        return None;
+    }
+    if !service.is_metadata() {
+        let metadata_service = state.payload().metadata_service();
+        files.extend(metadata_service.provider().page_files(
+            metadata_service.name(),
+            model_name,
+            page_index,
+        ));
+    }
+    Some(files)
+}
+
 async fn response(
     state: web::Data<State>,
+    request: &HttpRequest,
     query: &serde_json::Value,
     service_name: impl AsRef<str>,
     model_name: impl AsRef<str>,
@@ -59,32 +118,49 @@ async fn response(
     let model_name = model_name.as_ref();
 
     if let Some(service) = state.payload().service(service_name) {
-        let metadata_json = service_page(
-            state.as_ref(),
-            query,
-            state.payload().metadata_service(),
-            model_name,
-            page_index,
-        )
-        .await;
-        let service_json = if service.is_metadata() {
-            metadata_json
+        let validators = page_files(&state, service, model_name, page_index)
+            .and_then(|files| Validators::from_files(request, &files));
+        if let Some(validators) = &validators {
+            let hit = validators.is_fresh(request);
+            state.metrics().page_cache(service_name, hit);
+            if hit {
+                return validators.not_modified(state.cache_policy());
+            }
+        }
+        let metadata_service = state.payload().metadata_service();
+        let body = if service.is_metadata() {
+            service_page(state.as_ref(), query, service, model_name, page_index)
+                .await
+                .and_then(|page| Ok(serde_json::to_string(&page)?))
         } else {
-            let service_json =
+            // The metadata is only embedded for clients that may request it on its own.
+            let metadata_page = if Grant::of(request).allows_service(metadata_service.name()) {
+                service_page(
+                    state.as_ref(),
+                    query,
+                    metadata_service,
+                    model_name,
+                    page_index,
+                )
+                .await
+                .ok()
+            } else {
+                None
+            };
+            let service_page =
                 service_page(state.as_ref(), query, service, model_name, page_index).await;
-            let metadata_json = metadata_json.unwrap_or(serde_json::Value::Null);
-            service_json.map(|service_json| {
-                json!({
-                    "metadata": metadata_json,
-                    "data": service_json
-                })
+            service_page.and_then(|page| {
+                Ok(serde_json::to_string(&ServicePage {
+                    metadata: metadata_page,
+                    data: page,
+                })?)
             })
         };
-        match service_json {
-            Ok(page) => HttpResponse::Ok()
-                .content_type(ContentType::json())
-                .body(page.to_string()),
-            Err(error) => HttpResponse::ServiceUnavailable().body(format!("{error}")),
+        match body {
+            Ok(body) => validators
+                .unwrap_or_else(|| Validators::from_body(body.as_bytes()))
+                .json_response(request, state.cache_policy(), body),
+            Err(error) => HttpResponse::build(error_status(&error)).body(format!("{error:#}")),
         }
     } else {
         HttpResponse::NotFound().body(format!("Service '{service_name}' not found.",))
@@ -93,6 +169,7 @@ async fn response(
 
 async fn all_response(
     state: web::Data<State>,
+    request: &HttpRequest,
     query: web::Query<serde_json::Value>,
     model_name: impl AsRef<str>,
     page_index: PageIndex,
@@ -100,29 +177,41 @@ async fn all_response(
     let model_name = model_name.as_ref();
     let query = query.deref();
 
-    let mut value = json!({});
+    let grant = Grant::of(request);
+    let mut pages = BTreeMap::new();
 
-    for service in state.payload().services() {
+    for service in state
+        .payload()
+        .services()
+        .filter(|service| grant.allows_service(service.name()))
+    {
         if let Ok(page) = service_page(state.as_ref(), query, service, model_name, page_index).await
         {
-            value[service.name()] = page;
+            pages.insert(service.name(), page);
         }
     }
 
-    HttpResponse::Ok()
-        .content_type(ContentType::json())
-        .body(value.to_string())
+    match serde_json::to_string(&pages) {
+        Ok(body) => Validators::from_body(body.as_bytes()).json_response(
+            request,
+            state.cache_policy(),
+            body,
+        ),
+        Err(error) => HttpResponse::InternalServerError().body(format!("{error}")),
+    }
 }
 
 #[get("/api/{model_name}/{service}")]
 pub async fn model(
     state: web::Data<State>,
+    request: HttpRequest,
     indices: web::Path<(String, String)>,
     query: web::Query<serde_json::Value>,
 ) -> impl Responder {
     let (model_name, service_name) = indices.into_inner();
     response(
         state,
+        &request,
         query.deref(),
         service_name,
         model_name,
@@ -134,12 +223,14 @@ pub async fn model(
 #[get("/api/{model_name}/{service}/{layer_index}")]
 pub async fn layer(
     state: web::Data<State>,
+    request: HttpRequest,
     indices: web::Path<(String, String, u32)>,
     query: web::Query<serde_json::Value>,
 ) -> impl Responder {
     let (model_name, service_name, layer_index) = indices.into_inner();
     response(
         state,
+        &request,
         query.deref(),
         service_name,
         model_name,
@@ -151,12 +242,14 @@ pub async fn layer(
 #[get("/api/{model_name}/{service}/{layer_index}/{neuron_index}")]
 pub async fn neuron(
     state: web::Data<State>,
+    request: HttpRequest,
     indices: web::Path<(String, String, u32, u32)>,
     query: web::Query<serde_json::Value>,
 ) -> impl Responder {
     let (model_name, service_name, layer_index, neuron_index) = indices.into_inner();
     response(
         state,
+        &request,
         query.deref(),
         service_name,
         model_name,
@@ -165,35 +258,64 @@ pub async fn neuron(
     .await
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/{model_name}/all",
+    params(("model_name" = String, Path, description = "Name of the model.")),
+    responses((status = 200, description = "The model pages of all services that have one, by service name.", body = BTreeMap<String, Page>))
+)]
 #[get("/api/{model_name}/all")]
 async fn all_model(
     state: web::Data<State>,
+    request: HttpRequest,
     indices: web::Path<String>,
     query: web::Query<serde_json::Value>,
 ) -> impl Responder {
     let model_name = indices.into_inner();
-    all_response(state, query, model_name, PageIndex::Model).await
+    all_response(state, &request, query, model_name, PageIndex::Model).await
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/{model_name}/all/{layer_index}",
+    params(("model_name" = String, Path, description = "Name of the model."), ("layer_index" = u32, Path, description = "Index of the layer.")),
+    responses((status = 200, description = "The layer pages of all services that have one, by service name.", body = BTreeMap<String, Page>))
+)]
 #[get("/api/{model_name}/all/{layer_index}")]
 async fn all_layer(
     state: web::Data<State>,
+    request: HttpRequest,
     indices: web::Path<(String, u32)>,
     query: web::Query<serde_json::Value>,
 ) -> impl Responder {
     let (model_name, layer_index) = indices.into_inner();
-    all_response(state, query, model_name, PageIndex::Layer(layer_index)).await
+    all_response(
+        state,
+        &request,
+        query,
+        model_name,
+        PageIndex::Layer(layer_index),
+    )
+    .await
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/{model_name}/all/{layer_index}/{neuron_index}",
+    params(("model_name" = String, Path, description = "Name of the model."), ("layer_index" = u32, Path, description = "Index of the layer."), ("neuron_index" = u32, Path, description = "Index of the neuron in its layer.")),
+    responses((status = 200, description = "The neuron pages of all services that have one, by service name.", body = BTreeMap<String, Page>))
+)]
 #[get("/api/{model_name}/all/{layer_index}/{neuron_index}")]
 async fn all_neuron(
     state: web::Data<State>,
+    request: HttpRequest,
     indices: web::Path<(String, u32, u32)>,
     query: web::Query<serde_json::Value>,
 ) -> impl Responder {
     let (model_name, layer_index, neuron_index) = indices.into_inner();
     all_response(
         state,
+        &request,
         query,
         model_name,
         PageIndex::Neuron(layer_index, neuron_index),
@@ -202,30 +324,208 @@ async fn all_neuron(
 }
 
 pub struct State {
-    neuron_stores: Arc<Mutex<HashMap<String, NeuronStore>>>,
+    neuron_stores: NeuronStores,
+    neuroscope_archives: NeuroscopeArchives,
+    models: Mutex<Option<Arc<Vec<ModelSummary>>>>,
+    admin_token: Option<String>,
+    cache_max_age: u32,
+    access: Access,
+    metrics: Metrics,
     payload: Payload,
 }
 
 impl State {
     pub fn new(payload: Payload) -> Self {
+        let metrics = Metrics::new();
         Self {
-            neuron_stores: Arc::new(Mutex::new(HashMap::new())),
+            neuron_stores: NeuronStores::new(None, metrics.neuron_stores().clone()),
+            neuroscope_archives: NeuroscopeArchives::default(),
+            models: Mutex::new(None),
+            admin_token: None,
+            cache_max_age: 0,
+            access: Access::default(),
+            metrics,
             payload,
         }
     }
 
+    /// Enables the admin endpoints for requests bearing the given token.
+    pub fn with_admin_token(mut self, admin_token: Option<String>) -> Self {
+        self.admin_token = admin_token;
+        self
+    }
+
+    /// Limits the memory used by neuron stores, evicting the least recently used ones beyond it.
+    pub fn with_memory_budget(mut self, budget_bytes: Option<u64>) -> Self {
+        self.neuron_stores = NeuronStores::new(budget_bytes, self.metrics.neuron_stores().clone());
+        self
+    }
+
+    /// Lets clients use cached pages for the given number of seconds before revalidating them.
+    pub fn with_cache_max_age(mut self, cache_max_age: u32) -> Self {
+        self.cache_max_age = cache_max_age;
+        self
+    }
+
+    /// Restricts requests to the API keys, scopes and rate limits of the config.
+    pub fn with_access(mut self, access: AccessConfig) -> Self {
+        self.access = Access::new(access);
+        self
+    }
+
     pub fn payload(&self) -> &Payload {
         &self.payload
     }
 
-    pub async fn neuron_store(&self, model_name: &str) -> Result<NeuronStore> {
-        let mut neuron_stores = self.neuron_stores.lock().await;
-        if !neuron_stores.contains_key(model_name) {
-            log::info!("Neuron store doesn't exist for model '{model_name}', loading from disk");
-            neuron_stores.insert(model_name.to_string(), NeuronStore::load(model_name)?);
+    pub fn admin_token(&self) -> Option<&str> {
+        self.admin_token.as_deref()
+    }
+
+    /// Responses are private to the client if they depend on its API key.
+    pub fn cache_policy(&self) -> CachePolicy {
+        CachePolicy {
+            max_age: self.cache_max_age,
+            private: self.access.is_restricted(),
+        }
+    }
+
+    pub fn access(&self) -> &Access {
+        &self.access
+    }
+
+    pub fn metrics(&self) -> &Metrics {
+        &self.metrics
+    }
+
+    /// The models in the data directory. They are scanned on first use.
+    pub async fn models(&self) -> Result<Arc<Vec<ModelSummary>>> {
+        let mut models = self.models.lock().await;
+        if let Some(models) = models.as_ref() {
+            return Ok(Arc::clone(models));
         }
-        assert!(neuron_stores.contains_key(model_name));
-        Ok(neuron_stores.get(model_name).unwrap().clone())
+        log::info!("Scanning data directory for models");
+        let scanned = Arc::new(
+            self.scan(|payload| models::scan_models("data", payload))
+                .await?,
+        );
+        *models = Some(Arc::clone(&scanned));
+        Ok(scanned)
+    }
+
+    /// Runs a scan of the data directory on a thread where blocking is fine.
+    async fn scan<T: Send + 'static>(
+        &self,
+        scan: impl FnOnce(&Payload) -> Result<T> + Send + 'static,
+    ) -> Result<T> {
+        let payload = self.payload.clone();
+        tokio::task::spawn_blocking(move || scan(&payload))
+            .await
+            .context("Scanning the data directory panicked.")?
+    }
+
+    /// The neuron store of the model, loaded from disk on first use without blocking requests for
+    /// other models.
+    pub async fn neuron_store(&self, model_name: &str) -> Result<Arc<NeuronStore>> {
+        self.neuron_stores.get(model_name).await
+    }
+
+    /// The neuroscope archive of the layer, opened on first use, or `None` if the layer is not
+    /// packed.
+    pub fn neuroscope_archive(
+        &self,
+        model_name: &str,
+        layer_index: u32,
+    ) -> Result<Option<Arc<NeuroscopeLayerArchive>>> {
+        self.neuroscope_archives.get(model_name, layer_index)
+    }
+
+    pub fn memory_report(&self) -> MemoryReport {
+        let neuron_stores = self.neuron_stores.memory();
+        let neuroscope_archives = self.neuroscope_archives.memory();
+        // The catalogue is left out while it is being scanned rather than waiting for the scan.
+        let model_catalogue = self.models.try_lock().ok().and_then(|models| {
+            models
+                .as_deref()
+                .map(|models| ModelCatalogueMemory::new(models))
+        });
+        let catalogue_bytes = model_catalogue
+            .as_ref()
+            .map_or(0, |model_catalogue| model_catalogue.heap_bytes);
+        MemoryReport {
+            heap_bytes: neuron_stores
+                .iter()
+                .map(|memory| memory.heap_bytes)
+                .sum::<u64>()
+                + catalogue_bytes,
+            mapped_bytes: neuron_stores
+                .iter()
+                .map(|memory| memory.mapped_bytes)
+                .chain(neuroscope_archives.iter().map(|memory| memory.mapped_bytes))
+                .sum(),
+            budget_bytes: self.neuron_stores.budget_bytes(),
+            neuron_stores,
+            neuroscope_archives,
+            model_catalogue,
+        }
+    }
+
+    /// Loads the neuron stores of the given models one after the other, logging progress.
+    pub async fn preload(&self, model_names: &[String]) {
+        let start = Instant::now();
+        for (index, model_name) in model_names.iter().enumerate() {
+            log::info!(
+                "Preloading model '{model_name}' ({}/{})",
+                index + 1,
+                model_names.len()
+            );
+            if let Err(error) = self.neuron_store(model_name).await {
+                log::error!("Failed to preload model '{model_name}': {error:#}");
+            }
+        }
+        log::info!(
+            "Preloaded {} models in {:.1} s",
+            model_names.len(),
+            start.elapsed().as_secs_f64()
+        );
+    }
+
+    /// Drops everything loaded from the data directory for the given model, or for all models if
+    /// none is given, so it is loaded again on next use. Requests in flight keep using what they
+    /// already loaded. Returns the names of the models whose neuron stores were dropped.
+    pub async fn reload(&self, model_name: Option<&str>) -> Result<Vec<String>> {
+        let reloaded = self.neuron_stores.remove(model_name);
+        self.neuroscope_archives.remove(model_name);
+
+        // The model is scanned before locking the models, so listing them is not held up by it.
+        let summary = match model_name {
+            Some(model_name) => {
+                let owned_model_name = model_name.to_owned();
+                self.scan(move |payload| models::scan_model("data", payload, &owned_model_name))
+                    .await?
+            }
+            None => None,
+        };
+        let mut models = self.models.lock().await;
+        match (model_name, models.as_ref()) {
+            (Some(model_name), Some(scanned)) => {
+                let mut scanned = scanned.as_ref().clone();
+                scanned.retain(|summary| summary.metadata.name != model_name);
+                if let Some(summary) = summary {
+                    scanned.push(summary);
+                    scanned.sort_unstable_by(|a, b| a.metadata.name.cmp(&b.metadata.name));
+                }
+                *models = Some(Arc::new(scanned));
+            }
+            (None, _) => *models = None,
+            (_, None) => {}
+        }
+        log::info!(
+            "Reloaded {}",
+            model_name.map_or("all models".to_owned(), |model_name| format!(
+                "model '{model_name}'"
+            ))
+        );
+        Ok(reloaded)
     }
 }
 
@@ -236,23 +536,95 @@ impl Default for State {
     }
 }
 
-pub fn start_server() -> std::io::Result<()> {
-    let url = "127.0.0.1";
-    let port = 8080;
+/// Allows the given origins, or any origin for `*`, to call the API from a browser.
+fn cors(origins: &[String]) -> Cors {
+    let cors = Cors::default()
+        .allow_any_method()
+        .allow_any_header()
+        .expose_headers([header::ETAG, header::LAST_MODIFIED])
+        .max_age(3600);
+    if origins.iter().any(|origin| origin == "*") {
+        cors.allow_any_origin()
+    } else {
+        origins
+            .iter()
+            .fold(cors, |cors, origin| cors.allowed_origin(origin))
+    }
+}
+
+pub fn start_server(config: Config) -> Result<()> {
+    config.access.check()?;
+    let memory_budget_bytes = config
+        .memory_budget_mb
+        .map(|budget| {
+            budget
+                .checked_mul(1 << 20)
+                .with_context(|| format!("Memory budget of {budget} MiB is too large."))
+        })
+        .transpose()?;
+    let state = web::Data::new(
+        State::new(config.payload()?)
+            .with_admin_token(config.admin_token.clone())
+            .with_memory_budget(memory_budget_bytes)
+            .with_cache_max_age(config.cache_max_age)
+            .with_access(config.access.clone()),
+    );
+    if let Some(frontend) = &config.frontend {
+        frontend.check()?;
+    }
+    let Config {
+        url,
+        port,
+        preload,
+        frontend,
+        cors_origins,
+        compress,
+        metrics: serve_metrics,
+        ..
+    } = config;
     println!("Serving neuronav on http://{url}:{port}/");
-    let state = web::Data::new(State::default());
-    rt::System::new().block_on(
-        HttpServer::new(move || {
+    let preload_state = state.clone();
+    rt::System::new().block_on(async move {
+        let server = HttpServer::new(move || {
             App::new()
+                .wrap(from_fn(access::enforce))
+                .wrap(from_fn(metrics::record))
+                .wrap(Condition::new(compress, Compress::default()))
+                .wrap(Condition::new(
+                    !cors_origins.is_empty(),
+                    cors(&cors_origins),
+                ))
                 .app_data(state.clone())
+                .service(openapi::openapi_json)
+                .service(admin::reload)
+                .service(admin::memory)
+                .service(models::all_models)
+                .service(services::all_services)
+                .service(services::model_services)
                 .service(all_model)
                 .service(all_layer)
                 .service(all_neuron)
+                .service(batch::batch)
+                .service(export::export_model)
+                .service(export::export_layer)
                 .service(model)
                 .service(layer)
                 .service(neuron)
+                .configure(|config| {
+                    if serve_metrics {
+                        config.service(metrics::scrape);
+                    }
+                    if let Some(frontend) = &frontend {
+                        frontend::configure(config, frontend.clone());
+                    }
+                })
         })
-        .bind((url, port))?
-        .run(),
-    )
+        .bind((url.as_str(), port))?
+        .run();
+        if !preload.is_empty() {
+            rt::spawn(async move { preload_state.preload(&preload).await });
+        }
+        server.await
+    })?;
+    Ok(())
 }
@@ -0,0 +1,138 @@
+use std::{collections::BTreeMap, ffi::OsStr, fs, mem, path::Path};
+
+use actix_web::{get, http::header::ContentType, web, HttpRequest, HttpResponse, Responder};
+use anyhow::{bail, Context, Result};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::data::{LayerMetadata, ModelMetadata, Payload};
+
+use super::{Grant, State};
+
+#[derive(Clone, Debug, Serialize, ToSchema)]
+pub struct ModelSummary {
+    pub metadata: ModelMetadata,
+    /// Number of neurons with a neuron page by service name, for the services that can tell and
+    /// the client may access.
+    pub coverage: BTreeMap<String, u32>,
+}
+
+impl ModelSummary {
+    fn new(payload: &Payload, metadata: ModelMetadata) -> Self {
+        let coverage = payload
+            .services()
+            .filter_map(|service| {
+                service
+                    .provider()
+                    .neuron_coverage(service.name(), &metadata)
+                    .map(|num_neurons| (service.name().to_owned(), num_neurons))
+            })
+            .collect();
+        Self { metadata, coverage }
+    }
+
+    /// The summary with the coverage of only the services the grant allows.
+    fn for_grant(&self, grant: &Grant) -> Self {
+        Self {
+            metadata: self.metadata.clone(),
+            coverage: self
+                .coverage
+                .iter()
+                .filter(|(service_name, _)| grant.allows_service(service_name))
+                .map(|(service_name, &num_neurons)| (service_name.clone(), num_neurons))
+                .collect(),
+        }
+    }
+
+    /// Approximate number of bytes the summary occupies on the heap.
+    pub fn heap_size(&self) -> usize {
+        let ModelMetadata {
+            name,
+            layers,
+            activation_function,
+            dataset,
+            ..
+        } = &self.metadata;
+        let coverage_size: usize = self
+            .coverage
+            .keys()
+            .map(|service_name| mem::size_of::<(String, u32)>() + service_name.len())
+            .sum();
+        mem::size_of::<Self>()
+            + name.len()
+            + layers.len() * mem::size_of::<LayerMetadata>()
+            + activation_function.len()
+            + dataset.len()
+            + coverage_size
+    }
+}
+
+/// Returns the model with the given name in the data directory, or `None` if it has no model
+/// metadata.
+pub fn scan_model<P: AsRef<Path>>(
+    data_path: P,
+    payload: &Payload,
+    model_name: &str,
+) -> Result<Option<ModelSummary>> {
+    let data_path = data_path.as_ref();
+    if Path::new(model_name).file_name() != Some(OsStr::new(model_name)) {
+        bail!("Invalid model name '{model_name}'.");
+    }
+    if !data_path.join(model_name).join("metadata.json").is_file() {
+        return Ok(None);
+    }
+    let metadata = ModelMetadata::from_file(data_path, model_name)?;
+    Ok(Some(ModelSummary::new(payload, metadata)))
+}
+
+/// Returns the models in the data directory, sorted by name. Directories without model metadata
+/// are skipped.
+pub fn scan_models<P: AsRef<Path>>(data_path: P, payload: &Payload) -> Result<Vec<ModelSummary>> {
+    let data_path = data_path.as_ref();
+    let mut models = Vec::new();
+    for entry in fs::read_dir(data_path)
+        .with_context(|| format!("Failed to read data directory '{data_path:?}'."))?
+    {
+        let model_path = entry?.path();
+        let Some(model_name) = model_path
+            .file_name()
+            .and_then(|file_name| file_name.to_str())
+        else {
+            continue;
+        };
+        match scan_model(data_path, payload, model_name) {
+            Ok(Some(model)) => models.push(model),
+            Ok(None) => {}
+            Err(error) => log::warn!("Skipping model '{model_name}': {error:#}"),
+        }
+    }
+    models.sort_unstable_by(|a, b| a.metadata.name.cmp(&b.metadata.name));
+    Ok(models)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/models",
+    responses(
+        (status = 200, description = "The models with data on the server that the client may access.", body = Vec<ModelSummary>),
+        (status = 503, description = "The data directory could not be read.")
+    )
+)]
+#[get("/api/models")]
+pub async fn all_models(state: web::Data<State>, request: HttpRequest) -> impl Responder {
+    let grant = Grant::of(&request);
+    let body = state.models().await.and_then(|models| {
+        let models: Vec<_> = models
+            .iter()
+            .filter(|summary| grant.allows_model(&summary.metadata.name))
+            .map(|summary| summary.for_grant(&grant))
+            .collect();
+        Ok(serde_json::to_string(&models)?)
+    });
+    match body {
+        Ok(body) => HttpResponse::Ok()
+            .content_type(ContentType::json())
+            .body(body),
+        Err(error) => HttpResponse::ServiceUnavailable().body(format!("{error:#}")),
+    }
+}
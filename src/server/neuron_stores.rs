@@ -0,0 +1,213 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use tokio::sync::OnceCell;
+use utoipa::ToSchema;
+
+use crate::data::NeuronStore;
+
+use super::metrics::NeuronStoreMetrics;
+
+/// A neuron store that is loaded on first use. Requests for the same model wait for one load.
+#[derive(Default)]
+struct Entry {
+    neuron_store: OnceCell<Arc<NeuronStore>>,
+    /// Milliseconds since the creation of the stores at which the entry was last used.
+    last_used: AtomicU64,
+    load_time: Mutex<Option<Duration>>,
+}
+
+/// Memory used by a loaded neuron store.
+#[derive(Clone, Debug, Serialize, ToSchema)]
+pub struct NeuronStoreMemory {
+    pub model: String,
+    /// Approximate size of the store on the heap, which is all of it for stores compiled from JSON
+    /// when they were loaded.
+    pub heap_bytes: u64,
+    /// Size of the memory mapped compiled store.
+    pub mapped_bytes: u64,
+    pub load_seconds: Option<f64>,
+    pub idle_seconds: f64,
+}
+
+/// The neuron stores of the models, loaded on first use. If a memory budget is set, the least
+/// recently used stores are dropped when loading another one makes their heap memory exceed it.
+/// Memory mapped stores do not count against the budget, as the operating system pages them out
+/// by itself.
+pub struct NeuronStores {
+    entries: Mutex<HashMap<String, Arc<Entry>>>,
+    budget_bytes: Option<u64>,
+    created: Instant,
+    metrics: NeuronStoreMetrics,
+}
+
+impl NeuronStores {
+    pub fn new(budget_bytes: Option<u64>, metrics: NeuronStoreMetrics) -> Self {
+        Self {
+            entries: Mutex::new(HashMap::new()),
+            budget_bytes,
+            created: Instant::now(),
+            metrics,
+        }
+    }
+
+    fn entries(&self) -> std::sync::MutexGuard<'_, HashMap<String, Arc<Entry>>> {
+        self.entries.lock().expect("Neuron store lock poisoned.")
+    }
+
+    fn now_ms(&self) -> u64 {
+        self.created.elapsed().as_millis() as u64
+    }
+
+    /// The neuron store of the model, loaded from disk on first use without blocking requests for
+    /// other models.
+    pub async fn get(&self, model_name: &str) -> Result<Arc<NeuronStore>> {
+        let entry = Arc::clone(self.entries().entry(model_name.to_owned()).or_default());
+        entry.last_used.store(self.now_ms(), Ordering::Relaxed);
+        let mut loaded = false;
+        let neuron_store = entry
+            .neuron_store
+            .get_or_try_init(|| async {
+                log::info!(
+                    "Neuron store doesn't exist for model '{model_name}', loading from disk"
+                );
+                let start = Instant::now();
+                let owned_model_name = model_name.to_owned();
+                let neuron_store =
+                    tokio::task::spawn_blocking(move || NeuronStore::load(&owned_model_name))
+                        .await
+                        .context("Loading neuron store panicked.")??;
+                let load_time = start.elapsed();
+                self.metrics
+                    .load_seconds
+                    .with_label_values(&[model_name])
+                    .observe(load_time.as_secs_f64());
+                *entry.load_time.lock().expect("Load time lock poisoned.") = Some(load_time);
+                log::info!(
+                    "Loaded neuron store for model '{model_name}' in {:.1} s ({} MiB heap, {} MiB \
+                     mapped)",
+                    load_time.as_secs_f64(),
+                    neuron_store.heap_size() >> 20,
+                    neuron_store.mapped_size() >> 20
+                );
+                loaded = true;
+                Ok::<_, anyhow::Error>(Arc::new(neuron_store))
+            })
+            .await;
+        let neuron_store = match neuron_store {
+            Ok(neuron_store) => Arc::clone(neuron_store),
+            Err(error) => {
+                self.metrics.lookups.with_label_values(&["error"]).inc();
+                // Entries of models without a store are not kept around.
+                let mut entries = self.entries();
+                if entries.get(model_name).is_some_and(|other| {
+                    Arc::ptr_eq(other, &entry) && !entry.neuron_store.initialized()
+                }) {
+                    entries.remove(model_name);
+                }
+                return Err(error);
+            }
+        };
+        let result = if loaded { "loaded" } else { "cached" };
+        self.metrics.lookups.with_label_values(&[result]).inc();
+        if loaded {
+            self.evict(model_name);
+        }
+        Ok(neuron_store)
+    }
+
+    /// Drops the least recently used stores other than that of `keep` until the loaded stores fit
+    /// in the budget. Requests in flight keep the stores they hold.
+    fn evict(&self, keep: &str) {
+        let Some(budget_bytes) = self.budget_bytes else {
+            return;
+        };
+        let mut entries = self.entries();
+        let mut loaded: Vec<_> = entries
+            .iter()
+            .filter_map(|(model_name, entry)| {
+                let bytes = entry.neuron_store.get()?.heap_size() as u64;
+                Some((
+                    model_name.clone(),
+                    entry.last_used.load(Ordering::Relaxed),
+                    bytes,
+                ))
+            })
+            .collect();
+        let mut total_bytes: u64 = loaded.iter().map(|&(_, _, bytes)| bytes).sum();
+        loaded.sort_unstable_by_key(|&(_, last_used, _)| last_used);
+        for (model_name, _, bytes) in loaded {
+            if total_bytes <= budget_bytes {
+                break;
+            }
+            if model_name == keep {
+                continue;
+            }
+            log::info!(
+                "Evicting neuron store for model '{model_name}' to stay within memory budget"
+            );
+            entries.remove(&model_name);
+            self.metrics.evictions.inc();
+            total_bytes -= bytes;
+        }
+        if total_bytes > budget_bytes {
+            log::warn!(
+                "Loaded neuron stores use {} MiB of heap, more than the memory budget of {} MiB",
+                total_bytes >> 20,
+                budget_bytes >> 20
+            );
+        }
+    }
+
+    /// Drops the store of the given model, or of all models if none is given. Returns the names of
+    /// the models whose stores were loaded.
+    pub fn remove(&self, model_name: Option<&str>) -> Vec<String> {
+        let mut entries = self.entries();
+        let removed: Vec<_> = match model_name {
+            Some(model_name) => entries.remove_entry(model_name).into_iter().collect(),
+            None => entries.drain().collect(),
+        };
+        let mut removed: Vec<_> = removed
+            .into_iter()
+            .filter(|(_, entry)| entry.neuron_store.initialized())
+            .map(|(model_name, _)| model_name)
+            .collect();
+        removed.sort_unstable();
+        removed
+    }
+
+    pub fn budget_bytes(&self) -> Option<u64> {
+        self.budget_bytes
+    }
+
+    /// Memory used by the loaded stores, most recently used first.
+    pub fn memory(&self) -> Vec<NeuronStoreMemory> {
+        let now_ms = self.now_ms();
+        let mut neuron_stores: Vec<_> = self
+            .entries()
+            .iter()
+            .filter_map(|(model_name, entry)| {
+                let neuron_store = entry.neuron_store.get()?;
+                let load_time = *entry.load_time.lock().expect("Load time lock poisoned.");
+                let idle_ms = now_ms.saturating_sub(entry.last_used.load(Ordering::Relaxed));
+                Some(NeuronStoreMemory {
+                    model: model_name.clone(),
+                    heap_bytes: neuron_store.heap_size() as u64,
+                    mapped_bytes: neuron_store.mapped_size() as u64,
+                    load_seconds: load_time.map(|load_time| load_time.as_secs_f64()),
+                    idle_seconds: idle_ms as f64 / 1000.0,
+                })
+            })
+            .collect();
+        neuron_stores.sort_unstable_by(|a, b| a.idle_seconds.total_cmp(&b.idle_seconds));
+        neuron_stores
+    }
+}
@@ -0,0 +1,81 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use anyhow::Result;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::data::NeuroscopeLayerArchive;
+
+/// Memory mapped by an open neuroscope layer archive.
+#[derive(Clone, Debug, Serialize, ToSchema)]
+pub struct NeuroscopeArchiveMemory {
+    pub model: String,
+    pub layer: u32,
+    pub mapped_bytes: u64,
+}
+
+type Archives = HashMap<(String, u32), Arc<NeuroscopeLayerArchive>>;
+
+/// The neuroscope layer archives of the models by model name and layer index, opened on first
+/// use and kept mapped until they are removed.
+#[derive(Default)]
+pub struct NeuroscopeArchives {
+    archives: Mutex<Archives>,
+}
+
+impl NeuroscopeArchives {
+    fn archives(&self) -> std::sync::MutexGuard<'_, Archives> {
+        self.archives
+            .lock()
+            .expect("Neuroscope archive lock poisoned.")
+    }
+
+    /// The archive of the layer, or `None` if the layer is not packed.
+    pub fn get(
+        &self,
+        model_name: &str,
+        layer_index: u32,
+    ) -> Result<Option<Arc<NeuroscopeLayerArchive>>> {
+        let key = (model_name.to_owned(), layer_index);
+        if let Some(archive) = self.archives().get(&key) {
+            return Ok(Some(Arc::clone(archive)));
+        }
+        let archive_path = NeuroscopeLayerArchive::path("data", model_name, layer_index);
+        if !archive_path.exists() {
+            return Ok(None);
+        }
+        let archive = Arc::new(NeuroscopeLayerArchive::open(archive_path)?);
+        Ok(Some(Arc::clone(
+            self.archives().entry(key).or_insert(archive),
+        )))
+    }
+
+    /// Drops the archives of the given model, or of all models if none is given. Requests in
+    /// flight keep the archives they hold.
+    pub fn remove(&self, model_name: Option<&str>) {
+        let mut archives = self.archives();
+        match model_name {
+            Some(model_name) => archives.retain(|(name, _), _| name != model_name),
+            None => archives.clear(),
+        }
+    }
+
+    pub fn memory(&self) -> Vec<NeuroscopeArchiveMemory> {
+        let mut memory: Vec<_> = self
+            .archives()
+            .iter()
+            .map(
+                |((model_name, layer_index), archive)| NeuroscopeArchiveMemory {
+                    model: model_name.clone(),
+                    layer: *layer_index,
+                    mapped_bytes: archive.mapped_size() as u64,
+                },
+            )
+            .collect();
+        memory.sort_unstable_by(|a, b| (&a.model, a.layer).cmp(&(&b.model, b.layer)));
+        memory
+    }
+}
@@ -0,0 +1,260 @@
+use actix_web::{get, http::header::ContentType, web, HttpResponse, Responder};
+use utoipa::{
+    openapi::{
+        path::{HttpMethod, Operation, OperationBuilder, Parameter, ParameterBuilder, ParameterIn},
+        schema::{ObjectBuilder, OneOfBuilder, Type},
+        security::{
+            ApiKey, ApiKeyValue, HttpAuthScheme, HttpBuilder, SecurityRequirement, SecurityScheme,
+        },
+        ContentBuilder, HeaderBuilder, OpenApi as OpenApiDocument, RefOr, Required,
+        ResponseBuilder, Schema,
+    },
+    Modify, OpenApi, PartialSchema,
+};
+
+use crate::data::Payload;
+
+use super::{
+    access::API_KEY_HEADER, admin, batch, export, models, page, services, PageLevel,
+    QueryParameter, Service, ServiceProvider, State,
+};
+
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "neuronav",
+        description = "Pages of the services of neuronav. Every service has its own paths, of which \
+                       only the levels the service provides pages for are listed."
+    ),
+    paths(
+        super::all_model,
+        super::all_layer,
+        super::all_neuron,
+        batch::batch,
+        export::export_model,
+        export::export_layer,
+        models::all_models,
+        services::all_services,
+        services::model_services,
+        openapi_json,
+        admin::reload,
+        admin::memory
+    ),
+    components(schemas(page::Page, page::ServicePage)),
+    modifiers(&Security)
+)]
+struct ApiDoc;
+
+struct Security;
+
+impl Modify for Security {
+    fn modify(&self, openapi: &mut OpenApiDocument) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "admin_token",
+            SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).build()),
+        );
+        components.add_security_scheme(
+            "api_key",
+            SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::new(API_KEY_HEADER))),
+        );
+        // Requests without an API key are allowed unless the server restricts them.
+        openapi.security = Some(vec![
+            SecurityRequirement::default(),
+            SecurityRequirement::new("api_key", Vec::<String>::new()),
+        ]);
+    }
+}
+
+fn path_parameter(name: &str, description: &str, schema: RefOr<Schema>) -> Parameter {
+    ParameterBuilder::new()
+        .name(name)
+        .parameter_in(ParameterIn::Path)
+        .required(Required::True)
+        .description(Some(description))
+        .schema(Some(schema))
+        .build()
+}
+
+fn query_parameter(query_parameter: &QueryParameter) -> Parameter {
+    let required = if query_parameter.required {
+        Required::True
+    } else {
+        Required::False
+    };
+    ParameterBuilder::new()
+        .name(query_parameter.name)
+        .parameter_in(ParameterIn::Query)
+        .required(required)
+        .description(Some(query_parameter.description))
+        .schema(Some(String::schema()))
+        .build()
+}
+
+fn nullable(schema: RefOr<Schema>) -> RefOr<Schema> {
+    OneOfBuilder::new()
+        .item(schema)
+        .item(ObjectBuilder::new().schema_type(Type::Null))
+        .into()
+}
+
+fn page_operation(
+    payload: &Payload,
+    service: &Service,
+    page_level: PageLevel,
+) -> Option<Operation> {
+    let page_schema = service.provider().page_schema(page_level)?;
+    let schema = if service.is_metadata() {
+        page_schema
+    } else {
+        let metadata_schema = payload
+            .metadata_service()
+            .provider()
+            .page_schema(page_level)
+            .unwrap_or_else(|| ObjectBuilder::new().into());
+        ObjectBuilder::new()
+            .property("metadata", nullable(metadata_schema))
+            .required("metadata")
+            .property("data", page_schema)
+            .required("data")
+            .into()
+    };
+
+    let mut parameters = vec![path_parameter(
+        "model_name",
+        "Name of the model.",
+        String::schema(),
+    )];
+    if page_level != PageLevel::Model {
+        parameters.push(path_parameter(
+            "layer_index",
+            "Index of the layer.",
+            u32::schema(),
+        ));
+    }
+    if page_level == PageLevel::Neuron {
+        parameters.push(path_parameter(
+            "neuron_index",
+            "Index of the neuron in its layer.",
+            u32::schema(),
+        ));
+    }
+    parameters.extend(
+        service
+            .provider()
+            .query_parameters(page_level)
+            .iter()
+            .map(query_parameter),
+    );
+
+    let mut operation = OperationBuilder::new()
+        .summary(Some(format!(
+            "The {} page of service '{}'.",
+            page_level.to_str(),
+            service.name()
+        )))
+        .tag(service.name())
+        .parameters(Some(parameters))
+        .response(
+            "200",
+            ResponseBuilder::new().description("The page.").content(
+                ContentType::json().to_string(),
+                ContentBuilder::new().schema(Some(schema)).build(),
+            ),
+        )
+        .response(
+            "304",
+            ResponseBuilder::new()
+                .description("The page did not change since the client cached it."),
+        )
+        .response(
+            "401",
+            ResponseBuilder::new().description(
+                "The API key is invalid, or one is required to access the model or service.",
+            ),
+        )
+        .response(
+            "403",
+            ResponseBuilder::new()
+                .description("The API key does not grant access to the model or service."),
+        )
+        .response(
+            "429",
+            ResponseBuilder::new()
+                .description("Too many requests were made with the API key or IP address.")
+                .header(
+                    "Retry-After",
+                    HeaderBuilder::new()
+                        .schema(u64::schema())
+                        .description(Some("Seconds until the request may be retried."))
+                        .build(),
+                ),
+        )
+        .response(
+            "503",
+            ResponseBuilder::new().description("The page could not be produced."),
+        );
+    // Only proxies forward the status of their backend.
+    if let ServiceProvider::Proxy(_) = service.provider() {
+        operation = operation
+            .response(
+                "404",
+                ResponseBuilder::new().description("The backend has no such page."),
+            )
+            .response(
+                "502",
+                ResponseBuilder::new()
+                    .description("The backend failed or responded with an error."),
+            )
+            .response(
+                "504",
+                ResponseBuilder::new().description("The backend did not respond in time."),
+            );
+    }
+    Some(operation.build())
+}
+
+fn page_path(service: &Service, page_level: PageLevel) -> String {
+    let service_name = service.name();
+    match page_level {
+        PageLevel::Model => format!("/api/{{model_name}}/{service_name}"),
+        PageLevel::Layer => format!("/api/{{model_name}}/{service_name}/{{layer_index}}"),
+        PageLevel::Neuron => {
+            format!("/api/{{model_name}}/{service_name}/{{layer_index}}/{{neuron_index}}")
+        }
+    }
+}
+
+/// Returns the OpenAPI document of the API, with the pages of the services in the payload.
+pub fn openapi_document(payload: &Payload) -> OpenApiDocument {
+    let mut document = ApiDoc::openapi();
+    let mut services: Vec<_> = payload.services().collect();
+    services.sort_unstable_by_key(|service| service.name());
+    for service in services {
+        for page_level in PageLevel::ALL {
+            if let Some(operation) = page_operation(payload, service, page_level) {
+                document.paths.add_path_operation(
+                    page_path(service, page_level),
+                    vec![HttpMethod::Get],
+                    operation,
+                );
+            }
+        }
+    }
+    document
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/openapi.json",
+    responses((status = 200, description = "This document.", content_type = "application/json"))
+)]
+#[get("/api/openapi.json")]
+pub async fn openapi_json(state: web::Data<State>) -> impl Responder {
+    match openapi_document(state.payload()).to_json() {
+        Ok(document) => HttpResponse::Ok()
+            .content_type(ContentType::json())
+            .body(document),
+        Err(error) => HttpResponse::InternalServerError().body(format!("{error}")),
+    }
+}
@@ -0,0 +1,83 @@
+use serde::{Deserialize, Serialize};
+use utoipa::{
+    openapi::{Ref, RefOr, Schema},
+    ToSchema,
+};
+
+use crate::data::{
+    ExplanationLayerPage, ExplanationNeuronPage, LayerMetadata, ModelMetadata, Neuron2GraphPage,
+    NeuronIndex, NeuroscopeLayerPage, NeuroscopeModelPage, NeuroscopeNeuronPage,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum PageLevel {
+    Model,
+    Layer,
+    Neuron,
+}
+
+impl PageLevel {
+    pub const ALL: [PageLevel; 3] = [PageLevel::Model, PageLevel::Layer, PageLevel::Neuron];
+
+    pub fn to_str(self) -> &'static str {
+        match self {
+            PageLevel::Model => "model",
+            PageLevel::Layer => "layer",
+            PageLevel::Neuron => "neuron",
+        }
+    }
+}
+
+/// The model, layer or neuron a page is about.
+#[derive(Clone, Copy, Debug)]
+pub enum PageIndex {
+    Model,
+    Layer(u32),
+    Neuron(u32, u32),
+}
+
+/// A query parameter accepted by the pages of a service.
+#[derive(Clone, Copy, Debug, Serialize, ToSchema)]
+pub struct QueryParameter {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub required: bool,
+}
+
+/// A reference to the schema of `T` in the components of the OpenAPI document.
+pub fn schema_ref<T: ToSchema>() -> RefOr<Schema> {
+    Ref::from_schema_name(T::name()).into()
+}
+
+/// A page without any content.
+#[derive(Clone, Debug, Default, Serialize, ToSchema)]
+pub struct EmptyPage {}
+
+/// A page returned by a service provider.
+#[derive(Clone, Debug, Serialize, ToSchema)]
+#[serde(untagged)]
+pub enum Page {
+    Empty(EmptyPage),
+    ModelMetadata(ModelMetadata),
+    LayerMetadata(LayerMetadata),
+    NeuroscopeModel(NeuroscopeModelPage),
+    NeuroscopeLayer(NeuroscopeLayerPage),
+    NeuroscopeNeuron(NeuroscopeNeuronPage),
+    Neuron2Graph(Neuron2GraphPage),
+    /// Neurons matching a search.
+    NeuronIndices(Vec<NeuronIndex>),
+    ExplanationLayer(ExplanationLayerPage),
+    ExplanationNeuron(ExplanationNeuronPage),
+    /// Any JSON value, for services whose pages have no fixed schema.
+    #[schema(value_type = Object)]
+    Json(serde_json::Value),
+}
+
+/// The page of a service other than metadata, together with the metadata page of the same model,
+/// layer or neuron. The metadata is null if it could not be found.
+#[derive(Clone, Debug, Serialize, ToSchema)]
+pub struct ServicePage {
+    pub metadata: Option<Page>,
+    pub data: Page,
+}
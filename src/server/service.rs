@@ -1,8 +1,10 @@
-
-use anyhow::Result;
+use anyhow::{bail, Result};
 use serde::{Deserialize, Serialize};
 
-use super::{ServiceProvider, State};
+use super::{Page, ServiceProvider, State};
+
+/// Names that are part of the routes of the API, so they cannot be used by services.
+pub const RESERVED_SERVICE_NAMES: [&str; 3] = ["all", "export", "services"];
 
 #[derive(Clone, Serialize, Deserialize)]
 pub struct Service {
@@ -11,9 +13,18 @@ pub struct Service {
 }
 
 impl Service {
-    pub fn new(name: String, provider: ServiceProvider) -> Self {
-        assert_ne!(name, "all", "Service name cannot be 'all'.");
-        Self { name, provider }
+    pub fn new(name: String, provider: ServiceProvider) -> Result<Self> {
+        Self::check_name(&name)?;
+        Ok(Self { name, provider })
+    }
+
+    /// Checks that the name is not part of the routes of the API. Services deserialized from a
+    /// config are checked when they are added to the payload.
+    pub fn check_name(name: &str) -> Result<()> {
+        if RESERVED_SERVICE_NAMES.contains(&name) {
+            bail!("Service name '{name}' is reserved.");
+        }
+        Ok(())
     }
 
     pub fn name(&self) -> &str {
@@ -25,12 +36,16 @@ impl Service {
         self.provider.is_metadata()
     }
 
+    pub fn provider(&self) -> &ServiceProvider {
+        &self.provider
+    }
+
     pub async fn model_page(
         &self,
         state: &State,
         query: &serde_json::Value,
         model_name: &str,
-    ) -> Result<serde_json::Value> {
+    ) -> Result<Page> {
         self.provider
             .model_page(self.name(), state, query, model_name)
             .await
@@ -42,7 +57,7 @@ impl Service {
         query: &serde_json::Value,
         model_name: &str,
         layer_index: u32,
-    ) -> Result<serde_json::Value> {
+    ) -> Result<Page> {
         self.provider
             .layer_page(self.name(), state, query, model_name, layer_index)
             .await
@@ -55,7 +70,7 @@ impl Service {
         model_name: &str,
         layer_index: u32,
         neuron_index: u32,
-    ) -> Result<serde_json::Value> {
+    ) -> Result<Page> {
         self.provider
             .neuron_page(
                 self.name(),
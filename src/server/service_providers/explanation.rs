@@ -0,0 +1,99 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use utoipa::openapi::{RefOr, Schema};
+
+use crate::{
+    data::{ExplanationLayerPage, ExplanationNeuronPage, ModelMetadata, NeuronIndex},
+    server::{schema_ref, Page, PageIndex, PageLevel, State},
+};
+
+use super::service_provider::ServiceProviderTrait;
+
+/// Serves explanations imported from OpenAI's automated interpretability project.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Explanation;
+
+#[async_trait]
+impl ServiceProviderTrait for Explanation {
+    async fn layer_page(
+        &self,
+        _service_name: &str,
+        _state: &State,
+        _query: &serde_json::Value,
+        model_name: &str,
+        layer_index: u32,
+    ) -> Result<Page> {
+        let path = ExplanationLayerPage::path("data", model_name, layer_index);
+        ExplanationLayerPage::from_file(path).map(Page::ExplanationLayer)
+    }
+
+    async fn neuron_page(
+        &self,
+        _service_name: &str,
+        _state: &State,
+        _query: &serde_json::Value,
+        model_name: &str,
+        layer_index: u32,
+        neuron_index: u32,
+    ) -> Result<Page> {
+        let path = ExplanationNeuronPage::path(
+            "data",
+            model_name,
+            NeuronIndex {
+                layer: layer_index,
+                neuron: neuron_index,
+            },
+        );
+        ExplanationNeuronPage::from_file(path).map(Page::ExplanationNeuron)
+    }
+
+    fn has_data(&self, _service_name: &str, model_name: &str) -> bool {
+        Path::new("data")
+            .join(model_name)
+            .join("explanation")
+            .is_dir()
+    }
+
+    fn neuron_coverage(&self, _service_name: &str, model_metadata: &ModelMetadata) -> Option<u32> {
+        let num_neurons = model_metadata
+            .neuron_indices()
+            .filter(|&neuron_index| {
+                ExplanationNeuronPage::path("data", &model_metadata.name, neuron_index).is_file()
+            })
+            .count();
+        Some(num_neurons as u32)
+    }
+
+    fn page_files(
+        &self,
+        _service_name: &str,
+        model_name: &str,
+        page_index: PageIndex,
+    ) -> Vec<PathBuf> {
+        match page_index {
+            PageIndex::Model => vec![],
+            PageIndex::Layer(layer_index) => {
+                vec![ExplanationLayerPage::path("data", model_name, layer_index)]
+            }
+            PageIndex::Neuron(layer_index, neuron_index) => vec![ExplanationNeuronPage::path(
+                "data",
+                model_name,
+                NeuronIndex {
+                    layer: layer_index,
+                    neuron: neuron_index,
+                },
+            )],
+        }
+    }
+
+    fn page_schema(&self, page_level: PageLevel) -> Option<RefOr<Schema>> {
+        match page_level {
+            PageLevel::Model => None,
+            PageLevel::Layer => Some(schema_ref::<ExplanationLayerPage>()),
+            PageLevel::Neuron => Some(schema_ref::<ExplanationNeuronPage>()),
+        }
+    }
+}
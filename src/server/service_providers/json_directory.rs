@@ -0,0 +1,298 @@
+use std::{
+    fs,
+    path::{Component, Path, PathBuf},
+};
+
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use utoipa::openapi::{schema::ObjectBuilder, RefOr, Schema};
+
+use crate::{
+    data::{ModelMetadata, NeuronIndex},
+    server::{Page, PageIndex, PageLevel, State},
+};
+
+use super::service_provider::ServiceProviderTrait;
+
+/// Serves JSON files from the data directory as pages. The paths of the files are given by
+/// templates relative to the data directory, in which `{model}`, `{service}`, `{layer}` and
+/// `{neuron}` are replaced by the model name, service name, layer index and neuron index. Levels
+/// without a template have no pages.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct JsonDirectory {
+    #[serde(default = "JsonDirectory::default_model_page")]
+    model_page: Option<String>,
+    #[serde(default = "JsonDirectory::default_layer_page")]
+    layer_page: Option<String>,
+    #[serde(default = "JsonDirectory::default_neuron_page")]
+    neuron_page: Option<String>,
+}
+
+impl JsonDirectory {
+    fn default_model_page() -> Option<String> {
+        Some("{model}/{service}/model.json".to_owned())
+    }
+
+    fn default_layer_page() -> Option<String> {
+        Some("{model}/{service}/l{layer}.json".to_owned())
+    }
+
+    fn default_neuron_page() -> Option<String> {
+        Some("{model}/{service}/l{layer}n{neuron}.json".to_owned())
+    }
+
+    fn template(&self, page_level: PageLevel) -> Option<&str> {
+        match page_level {
+            PageLevel::Model => self.model_page.as_deref(),
+            PageLevel::Layer => self.layer_page.as_deref(),
+            PageLevel::Neuron => self.neuron_page.as_deref(),
+        }
+    }
+
+    fn page_path(
+        &self,
+        service_name: &str,
+        model_name: &str,
+        page_level: PageLevel,
+        layer_index: Option<u32>,
+        neuron_index: Option<u32>,
+    ) -> Result<PathBuf> {
+        let Some(template) = self.template(page_level) else {
+            bail!(
+                "No {} page exists for service '{service_name}'.",
+                page_level.to_str()
+            );
+        };
+        if !is_single_component(model_name) {
+            bail!("Invalid model name '{model_name}'.");
+        }
+        let mut path = template
+            .replace("{model}", model_name)
+            .replace("{service}", service_name);
+        if let Some(layer_index) = layer_index {
+            path = path.replace("{layer}", &layer_index.to_string());
+        }
+        if let Some(neuron_index) = neuron_index {
+            path = path.replace("{neuron}", &neuron_index.to_string());
+        }
+        Ok(Path::new("data").join(path))
+    }
+
+    fn read_page(&self, path: &Path) -> Result<Page> {
+        let text =
+            fs::read_to_string(path).with_context(|| format!("Failed to read file '{path:?}'."))?;
+        let value = serde_json::from_str(&text)
+            .with_context(|| format!("Failed to parse JSON file '{path:?}'."))?;
+        Ok(Page::Json(value))
+    }
+}
+
+fn is_single_component(name: &str) -> bool {
+    let mut components = Path::new(name).components();
+    matches!(
+        (components.next(), components.next()),
+        (Some(Component::Normal(_)), None)
+    )
+}
+
+#[async_trait]
+impl ServiceProviderTrait for JsonDirectory {
+    async fn model_page(
+        &self,
+        service_name: &str,
+        _state: &State,
+        _query: &serde_json::Value,
+        model_name: &str,
+    ) -> Result<Page> {
+        let path = self.page_path(service_name, model_name, PageLevel::Model, None, None)?;
+        self.read_page(&path)
+    }
+
+    async fn layer_page(
+        &self,
+        service_name: &str,
+        _state: &State,
+        _query: &serde_json::Value,
+        model_name: &str,
+        layer_index: u32,
+    ) -> Result<Page> {
+        let path = self.page_path(
+            service_name,
+            model_name,
+            PageLevel::Layer,
+            Some(layer_index),
+            None,
+        )?;
+        self.read_page(&path)
+    }
+
+    async fn neuron_page(
+        &self,
+        service_name: &str,
+        _state: &State,
+        _query: &serde_json::Value,
+        model_name: &str,
+        layer_index: u32,
+        neuron_index: u32,
+    ) -> Result<Page> {
+        let path = self.page_path(
+            service_name,
+            model_name,
+            PageLevel::Neuron,
+            Some(layer_index),
+            Some(neuron_index),
+        )?;
+        self.read_page(&path)
+    }
+
+    fn has_data(&self, service_name: &str, model_name: &str) -> bool {
+        PageLevel::ALL.into_iter().any(|page_level| {
+            self.page_path(service_name, model_name, page_level, None, None)
+                .is_ok_and(|path| {
+                    // The directory up to the first placeholder that is not filled in.
+                    let path = path.to_string_lossy();
+                    let prefix = &path[..path.find('{').unwrap_or(path.len())];
+                    Path::new(prefix)
+                        .parent()
+                        .is_some_and(|directory| directory.is_dir())
+                })
+        })
+    }
+
+    fn neuron_coverage(&self, service_name: &str, model_metadata: &ModelMetadata) -> Option<u32> {
+        self.neuron_page.as_ref()?;
+        let num_neurons = model_metadata
+            .neuron_indices()
+            .filter(|&NeuronIndex { layer, neuron }| {
+                self.page_path(
+                    service_name,
+                    &model_metadata.name,
+                    PageLevel::Neuron,
+                    Some(layer),
+                    Some(neuron),
+                )
+                .is_ok_and(|path| path.is_file())
+            })
+            .count();
+        Some(num_neurons as u32)
+    }
+
+    fn page_files(
+        &self,
+        service_name: &str,
+        model_name: &str,
+        page_index: PageIndex,
+    ) -> Vec<PathBuf> {
+        let path = match page_index {
+            PageIndex::Model => {
+                self.page_path(service_name, model_name, PageLevel::Model, None, None)
+            }
+            PageIndex::Layer(layer_index) => self.page_path(
+                service_name,
+                model_name,
+                PageLevel::Layer,
+                Some(layer_index),
+                None,
+            ),
+            PageIndex::Neuron(layer_index, neuron_index) => self.page_path(
+                service_name,
+                model_name,
+                PageLevel::Neuron,
+                Some(layer_index),
+                Some(neuron_index),
+            ),
+        };
+        path.into_iter().collect()
+    }
+
+    fn page_schema(&self, page_level: PageLevel) -> Option<RefOr<Schema>> {
+        self.template(page_level).map(|_| {
+            ObjectBuilder::new()
+                .description(Some("Any JSON value."))
+                .into()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, process};
+
+    use super::*;
+
+    fn json_directory(value: serde_json::Value) -> JsonDirectory {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn fills_in_the_templates() {
+        let default = json_directory(serde_json::json!({}));
+        let page_path = |json_directory: &JsonDirectory, page_level, layer, neuron| {
+            json_directory
+                .page_path("custom", "solu-1l", page_level, layer, neuron)
+                .unwrap()
+        };
+        assert_eq!(
+            page_path(&default, PageLevel::Model, None, None),
+            Path::new("data/solu-1l/custom/model.json")
+        );
+        assert_eq!(
+            page_path(&default, PageLevel::Layer, Some(2), None),
+            Path::new("data/solu-1l/custom/l2.json")
+        );
+        assert_eq!(
+            page_path(&default, PageLevel::Neuron, Some(2), Some(31)),
+            Path::new("data/solu-1l/custom/l2n31.json")
+        );
+
+        let custom = json_directory(serde_json::json!({
+            "model_page": null,
+            "neuron_page": "{service}/{model}/{layer}/{neuron}.json",
+        }));
+        assert_eq!(
+            page_path(&custom, PageLevel::Neuron, Some(0), Some(7)),
+            Path::new("data/custom/solu-1l/0/7.json")
+        );
+        assert!(custom
+            .page_path("custom", "solu-1l", PageLevel::Model, None, None)
+            .is_err());
+        assert!(custom.page_schema(PageLevel::Model).is_none());
+        assert!(custom.page_schema(PageLevel::Layer).is_some());
+    }
+
+    #[test]
+    fn rejects_model_names_that_leave_the_model_directory() {
+        let default = json_directory(serde_json::json!({}));
+        for model_name in ["", ".", "..", "../secrets", "a/b", "/etc"] {
+            assert!(
+                default
+                    .page_path("custom", model_name, PageLevel::Model, None, None)
+                    .is_err(),
+                "{model_name}"
+            );
+            assert!(default
+                .page_files("custom", model_name, PageIndex::Model)
+                .is_empty());
+        }
+    }
+
+    #[test]
+    fn reads_pages_as_json() {
+        let path = env::temp_dir().join(format!("neuronav-json-directory-{}.json", process::id()));
+        let default = json_directory(serde_json::json!({}));
+        fs::write(&path, r#"{"values": [1, 2]}"#).unwrap();
+        let page = default.read_page(&path);
+        fs::write(&path, "{").unwrap();
+        let invalid = default.read_page(&path);
+        let _ = fs::remove_file(&path);
+        let missing = default.read_page(&path);
+
+        assert!(matches!(
+            page.unwrap(),
+            Page::Json(value) if value == serde_json::json!({"values": [1, 2]})
+        ));
+        assert!(invalid.is_err());
+        assert!(missing.is_err());
+    }
+}
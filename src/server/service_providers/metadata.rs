@@ -1,11 +1,17 @@
-use std::{fs, path::Path};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
 
 use anyhow::{Context, Result};
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
-use serde_json::json;
+use utoipa::openapi::{RefOr, Schema};
 
-use crate::{data::ModelMetadata, server::State};
+use crate::{
+    data::{LayerMetadata, ModelMetadata},
+    server::{schema_ref, EmptyPage, Page, PageIndex, PageLevel, State},
+};
 
 use super::ServiceProviderTrait;
 
@@ -20,11 +26,11 @@ impl ServiceProviderTrait for Metadata {
         _state: &State,
         _query: &serde_json::Value,
         model_name: &str,
-    ) -> Result<serde_json::Value> {
+    ) -> Result<Page> {
         let path: std::path::PathBuf = Path::new("data").join(model_name).join("metadata.json");
         let text = fs::read_to_string(path)?;
         let metadata = serde_json::from_str(&text)?;
-        Ok(metadata)
+        Ok(Page::ModelMetadata(metadata))
     }
 
     async fn layer_page(
@@ -34,16 +40,15 @@ impl ServiceProviderTrait for Metadata {
         _query: &serde_json::Value,
         model_name: &str,
         layer_index: u32,
-    ) -> Result<serde_json::Value> {
+    ) -> Result<Page> {
         let path = Path::new("data").join(model_name).join("metadata.json");
         let text = fs::read_to_string(path)?;
         let model_metadata: ModelMetadata = serde_json::from_str(&text)?;
-        let layer_metadata = &model_metadata
+        let layer_metadata = model_metadata
             .layers
             .get(layer_index as usize)
             .context("Layer index out of bounds.")?;
-        let metadata = serde_json::to_value(layer_metadata)?;
-        Ok(metadata)
+        Ok(Page::LayerMetadata(layer_metadata.clone()))
     }
 
     async fn neuron_page(
@@ -54,7 +59,31 @@ impl ServiceProviderTrait for Metadata {
         _model_name: &str,
         _layer_index: u32,
         _neuron_index: u32,
-    ) -> Result<serde_json::Value> {
-        Ok(json!({}))
+    ) -> Result<Page> {
+        Ok(Page::Empty(EmptyPage {}))
+    }
+
+    fn has_data(&self, _service_name: &str, model_name: &str) -> bool {
+        Path::new("data")
+            .join(model_name)
+            .join("metadata.json")
+            .is_file()
+    }
+
+    fn page_files(
+        &self,
+        _service_name: &str,
+        model_name: &str,
+        _page_index: PageIndex,
+    ) -> Vec<PathBuf> {
+        vec![Path::new("data").join(model_name).join("metadata.json")]
+    }
+
+    fn page_schema(&self, page_level: PageLevel) -> Option<RefOr<Schema>> {
+        Some(match page_level {
+            PageLevel::Model => schema_ref::<ModelMetadata>(),
+            PageLevel::Layer => schema_ref::<LayerMetadata>(),
+            PageLevel::Neuron => schema_ref::<EmptyPage>(),
+        })
     }
 }
@@ -1,5 +1,9 @@
+mod explanation;
+mod json_directory;
 mod metadata;
 mod neuroscope;
+mod proxy;
+pub use proxy::ProxyError;
 
 mod neuron2graph;
 mod neuron2graph_search;
@@ -1,18 +1,30 @@
-use std::{fs, path::Path};
+use std::path::{Path, PathBuf};
 
 use anyhow::{Context, Result};
 use async_trait::async_trait;
 
 use serde::{Deserialize, Serialize};
-use serde_json::json;
+use utoipa::openapi::{RefOr, Schema};
 
-use crate::{data::NeuronIndex, server::State};
+use crate::{
+    data::{ModelMetadata, Neuron2GraphPage, NeuronIndex, NeuronStore, SimilarNeuron},
+    server::{schema_ref, Page, PageIndex, PageLevel, State},
+};
 
 use super::service_provider::ServiceProviderTrait;
 
 #[derive(Clone, Serialize, Deserialize)]
 pub struct Neuron2Graph;
 
+fn graph_path(model_name: &str, layer_index: u32, neuron_index: u32) -> PathBuf {
+    Path::new("data")
+        .join(model_name)
+        .join("neuron2graph")
+        .join(format!("layer_{layer_index}",))
+        .join(format!("{layer_index}_{neuron_index}"))
+        .join("graph")
+}
+
 #[async_trait]
 impl ServiceProviderTrait for Neuron2Graph {
     async fn neuron_page(
@@ -23,15 +35,9 @@ impl ServiceProviderTrait for Neuron2Graph {
         model: &str,
         layer_index: u32,
         neuron_index: u32,
-    ) -> Result<serde_json::Value> {
-        let path = Path::new("data")
-            .join(model)
-            .join("neuron2graph")
-            .join(format!("layer_{layer_index}",))
-            .join(format!("{layer_index}_{neuron_index}"))
-            .join("graph");
-        let graph = fs::read_to_string(path).map(|page| json!(page)).with_context(|| format!("Failed to read neuron2graph page for neuron {neuron_index} in layer {layer_index} of model '{model}'."))?;
-        let similar_neurons = state
+    ) -> Result<Page> {
+        let graph = Neuron2GraphPage::read_graph(graph_path(model, layer_index, neuron_index)).with_context(|| format!("Failed to read neuron2graph page for neuron {neuron_index} in layer {layer_index} of model '{model}'."))?;
+        let similar = state
             .neuron_store(model)
             .await?
             .similar_neurons(
@@ -42,24 +48,47 @@ impl ServiceProviderTrait for Neuron2Graph {
                 0.4,
             )?
             .into_iter()
-            .map(
-                |(
-                    NeuronIndex {
-                        layer: layer_index,
-                        neuron: neuron_index,
-                    },
-                    similarity,
-                )| {
-                    json!({
-                        "layer": layer_index,
-                        "neuron": neuron_index,
-                        "similarity": similarity,
-                    })
-                },
-            )
+            .map(|(neuron_index, similarity)| SimilarNeuron::new(neuron_index, similarity))
             .collect::<Vec<_>>();
-        Ok(json!({
-        "graph": graph,
-        "similar": similar_neurons,}))
+        Ok(Page::Neuron2Graph(Neuron2GraphPage { graph, similar }))
+    }
+
+    fn has_data(&self, _service_name: &str, model_name: &str) -> bool {
+        Path::new("data")
+            .join(model_name)
+            .join("neuron2graph")
+            .is_dir()
+    }
+
+    fn neuron_coverage(&self, _service_name: &str, model_metadata: &ModelMetadata) -> Option<u32> {
+        let num_neurons = model_metadata
+            .neuron_indices()
+            .filter(|&NeuronIndex { layer, neuron }| {
+                graph_path(&model_metadata.name, layer, neuron).is_file()
+            })
+            .count();
+        Some(num_neurons as u32)
+    }
+
+    fn page_files(
+        &self,
+        _service_name: &str,
+        model_name: &str,
+        page_index: PageIndex,
+    ) -> Vec<PathBuf> {
+        match page_index {
+            PageIndex::Neuron(layer_index, neuron_index) => vec![
+                graph_path(model_name, layer_index, neuron_index),
+                NeuronStore::source_path("data", model_name),
+            ],
+            _ => vec![],
+        }
+    }
+
+    fn page_schema(&self, page_level: PageLevel) -> Option<RefOr<Schema>> {
+        match page_level {
+            PageLevel::Neuron => Some(schema_ref::<Neuron2GraphPage>()),
+            _ => None,
+        }
     }
 }
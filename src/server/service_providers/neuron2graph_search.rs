@@ -1,11 +1,17 @@
-use std::{collections::HashSet, str::FromStr};
+use std::{path::PathBuf, str::FromStr};
 
 use anyhow::{Context, Result};
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
-use serde_json::json;
+use utoipa::{
+    openapi::{RefOr, Schema},
+    PartialSchema,
+};
 
-use crate::{data::TokenSearch, server::State};
+use crate::{
+    data::{NeuronIndex, NeuronStore, TokenSearch},
+    server::{Page, PageIndex, PageLevel, QueryParameter, State},
+};
 
 use super::service_provider::ServiceProviderTrait;
 
@@ -20,7 +26,7 @@ impl ServiceProviderTrait for Neuron2GraphSearch {
         state: &State,
         query: &serde_json::Value,
         model_name: &str,
-    ) -> Result<serde_json::Value> {
+    ) -> Result<Page> {
         let query = query["query"]
             .as_str()
             .context("Query should contain an entry 'query' with a string value.")?;
@@ -29,28 +35,42 @@ impl ServiceProviderTrait for Neuron2GraphSearch {
             .split(',')
             .map(TokenSearch::from_str)
             .collect::<Result<Vec<_>>>()?;
-        let results = token_searches
-            .into_iter()
-            .map(|token_search| {
-                let TokenSearch {
-                    token,
-                    search_types,
-                } = token_search;
-                search_types
-                    .into_iter()
-                    .flat_map(|search_type| {
-                        neuron_store
-                            .get(search_type, token.as_str())
-                            .cloned()
-                            .unwrap_or_default()
-                    })
-                    .collect::<HashSet<_>>()
-            })
-            .reduce(|a, b| a.intersection(&b).copied().collect::<HashSet<_>>())
-            .with_context(|| "At least one token search should be provided.")?
-            .into_iter()
-            .collect::<Vec<_>>();
-
-        Ok(json!(results))
+        let results = neuron_store.search(&token_searches)?;
+
+        Ok(Page::NeuronIndices(results))
+    }
+
+    fn has_data(&self, _service_name: &str, model_name: &str) -> bool {
+        NeuronStore::source_path("data", model_name).is_file()
+    }
+
+    fn page_files(
+        &self,
+        _service_name: &str,
+        model_name: &str,
+        page_index: PageIndex,
+    ) -> Vec<PathBuf> {
+        match page_index {
+            PageIndex::Model => vec![NeuronStore::source_path("data", model_name)],
+            _ => vec![],
+        }
+    }
+
+    fn page_schema(&self, page_level: PageLevel) -> Option<RefOr<Schema>> {
+        match page_level {
+            PageLevel::Model => Some(Vec::<NeuronIndex>::schema()),
+            _ => None,
+        }
+    }
+
+    fn query_parameters(&self, page_level: PageLevel) -> Vec<QueryParameter> {
+        match page_level {
+            PageLevel::Model => vec![QueryParameter {
+                name: "query",
+                description: "Comma separated token searches of the form 'search_type:token', where the search type is 'activating', 'important' or 'any'. Neurons matching all searches are returned.",
+                required: true,
+            }],
+            _ => vec![],
+        }
     }
 }
@@ -1,13 +1,16 @@
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use anyhow::Result;
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
-use serde_json::json;
+use utoipa::openapi::{RefOr, Schema};
 
 use crate::{
-    data::{NeuroscopeLayerPage, NeuroscopeModelPage, NeuroscopeNeuronPage},
-    server::State,
+    data::{
+        retrieve, ModelMetadata, NeuronIndex, NeuroscopeLayerArchive, NeuroscopeLayerPage,
+        NeuroscopeModelPage, NeuroscopeNeuronPage,
+    },
+    server::{schema_ref, Page, PageIndex, PageLevel, State},
 };
 
 use super::service_provider::ServiceProviderTrait;
@@ -15,6 +18,39 @@ use super::service_provider::ServiceProviderTrait;
 #[derive(Clone, Serialize, Deserialize)]
 pub struct Neuroscope;
 
+fn model_page_path(model_name: &str) -> PathBuf {
+    Path::new("data")
+        .join(model_name)
+        .join("neuroscope")
+        .join("model.postcard")
+}
+
+fn layer_page_path(model_name: &str, layer_index: u32) -> PathBuf {
+    Path::new("data")
+        .join(model_name)
+        .join("neuroscope")
+        .join(format!("l{layer_index}.postcard",))
+}
+
+fn neuron_page_path(model_name: &str, layer_index: u32, neuron_index: u32) -> PathBuf {
+    Path::new("data")
+        .join(model_name)
+        .join("neuroscope")
+        .join(format!("l{layer_index}n{neuron_index}.postcard",))
+}
+
+/// Number of neurons with a page in the archive.
+fn archive_coverage(archive_path: &Path) -> Result<u32> {
+    let archive = NeuroscopeLayerArchive::open(archive_path)?;
+    let mut num_neurons = 0;
+    for neuron_index in 0..archive.num_neurons() {
+        if archive.record(neuron_index)?.is_some() {
+            num_neurons += 1;
+        }
+    }
+    Ok(num_neurons)
+}
+
 #[async_trait]
 impl ServiceProviderTrait for Neuroscope {
     async fn model_page(
@@ -23,12 +59,8 @@ impl ServiceProviderTrait for Neuroscope {
         _state: &State,
         _query: &serde_json::Value,
         model_name: &str,
-    ) -> Result<serde_json::Value> {
-        let path = Path::new("data")
-            .join(model_name)
-            .join("neuroscope")
-            .join("model.postcard");
-        NeuroscopeModelPage::from_file(path).map(|page| json!(page))
+    ) -> Result<Page> {
+        NeuroscopeModelPage::from_file(model_page_path(model_name)).map(Page::NeuroscopeModel)
     }
 
     async fn layer_page(
@@ -38,27 +70,95 @@ impl ServiceProviderTrait for Neuroscope {
         _query: &serde_json::Value,
         model_name: &str,
         layer_index: u32,
-    ) -> Result<serde_json::Value> {
-        let path = Path::new("data")
-            .join(model_name)
-            .join("neuroscope")
-            .join(format!("l{layer_index}.postcard",));
-        NeuroscopeLayerPage::from_file(path).map(|page| json!(page))
+    ) -> Result<Page> {
+        NeuroscopeLayerPage::from_file(layer_page_path(model_name, layer_index))
+            .map(Page::NeuroscopeLayer)
     }
 
     async fn neuron_page(
         &self,
         _service_name: &str,
-        _state: &State,
+        state: &State,
         _query: &serde_json::Value,
         model_name: &str,
         layer_index: u32,
         neuron_index: u32,
-    ) -> Result<serde_json::Value> {
-        let path = Path::new("data")
+    ) -> Result<Page> {
+        let page = if let Some(archive) = state.neuroscope_archive(model_name, layer_index)? {
+            archive.page(NeuronIndex {
+                layer: layer_index,
+                neuron: neuron_index,
+            })?
+        } else {
+            NeuroscopeNeuronPage::from_file(neuron_page_path(
+                model_name,
+                layer_index,
+                neuron_index,
+            ))?
+        };
+        Ok(Page::NeuroscopeNeuron(page))
+    }
+
+    fn has_data(&self, _service_name: &str, model_name: &str) -> bool {
+        Path::new("data")
             .join(model_name)
             .join("neuroscope")
-            .join(format!("l{layer_index}n{neuron_index}.postcard",));
-        NeuroscopeNeuronPage::from_file(path).map(|page| json!(page))
+            .is_dir()
+    }
+
+    fn neuron_coverage(&self, _service_name: &str, model_metadata: &ModelMetadata) -> Option<u32> {
+        let model_name = model_metadata.name.as_str();
+        let mut num_neurons = 0;
+        for (layer_index, layer_metadata) in model_metadata.layers.iter().enumerate() {
+            let layer_index = layer_index as u32;
+            let archive_path = NeuroscopeLayerArchive::path("data", model_name, layer_index);
+            if archive_path.exists() {
+                // A broken archive should not hide the coverage of the other layers.
+                match archive_coverage(&archive_path) {
+                    Ok(layer_neurons) => num_neurons += layer_neurons,
+                    Err(error) => log::warn!(
+                        "Failed to count the neurons in archive '{archive_path:?}': {error:#}"
+                    ),
+                }
+            } else {
+                num_neurons += layer_metadata
+                    .neuron_indices(layer_index)
+                    .filter(|&neuron_index| {
+                        retrieve::neuroscope::neuron_data_path("data", model_name, neuron_index)
+                            .is_file()
+                    })
+                    .count() as u32;
+            }
+        }
+        Some(num_neurons)
+    }
+
+    fn page_files(
+        &self,
+        _service_name: &str,
+        model_name: &str,
+        page_index: PageIndex,
+    ) -> Vec<PathBuf> {
+        let path = match page_index {
+            PageIndex::Model => model_page_path(model_name),
+            PageIndex::Layer(layer_index) => layer_page_path(model_name, layer_index),
+            PageIndex::Neuron(layer_index, neuron_index) => {
+                let archive_path = NeuroscopeLayerArchive::path("data", model_name, layer_index);
+                if archive_path.exists() {
+                    archive_path
+                } else {
+                    neuron_page_path(model_name, layer_index, neuron_index)
+                }
+            }
+        };
+        vec![path]
+    }
+
+    fn page_schema(&self, page_level: PageLevel) -> Option<RefOr<Schema>> {
+        Some(match page_level {
+            PageLevel::Model => schema_ref::<NeuroscopeModelPage>(),
+            PageLevel::Layer => schema_ref::<NeuroscopeLayerPage>(),
+            PageLevel::Neuron => schema_ref::<NeuroscopeNeuronPage>(),
+        })
     }
 }
@@ -0,0 +1,482 @@
+use std::{process::Stdio, sync::Arc, time::Duration};
+
+use actix_web::http::StatusCode;
+use anyhow::{anyhow, bail, Context, Result};
+use async_trait::async_trait;
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use thiserror::Error;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    process::{Child, ChildStdin, ChildStdout, Command},
+    sync::Mutex,
+    time::{self, Instant},
+};
+use utoipa::openapi::{schema::ObjectBuilder, RefOr, Schema};
+
+use crate::server::{Page, PageLevel, State};
+
+use super::service_provider::ServiceProviderTrait;
+
+#[derive(Debug, Error)]
+pub enum ProxyError {
+    #[error("Backend of service '{service}' timed out after {timeout:?}.")]
+    Timeout { service: String, timeout: Duration },
+    #[error("Backend of service '{service}' responded with status {status}: {message}")]
+    Status {
+        service: String,
+        status: u16,
+        message: String,
+    },
+    #[error("Backend of service '{service}' failed: {message}")]
+    Backend { service: String, message: String },
+}
+
+impl ProxyError {
+    /// The status to respond with when a page request fails with this error.
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            ProxyError::Timeout { .. } => StatusCode::GATEWAY_TIMEOUT,
+            ProxyError::Status { status, .. } if *status == 404 => StatusCode::NOT_FOUND,
+            ProxyError::Status { .. } | ProxyError::Backend { .. } => StatusCode::BAD_GATEWAY,
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub enum ProxyBackend {
+    /// Pages are requested from `{url}/{model}`, `{url}/{model}/{layer}` and
+    /// `{url}/{model}/{layer}/{neuron}` with the query of the original request.
+    Http { url: String },
+    /// A process started on first use. Every page request is written to its standard input as a
+    /// line of JSON with the fields `service`, `model`, `layer`, `neuron` and `query`, and answered
+    /// with a line of JSON on its standard output, either `{"data": page}` or
+    /// `{"error": message}` with an optional HTTP `status`.
+    Process {
+        command: String,
+        #[serde(default)]
+        args: Vec<String>,
+    },
+}
+
+struct ProcessHandle {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ProcessResponse {
+    Data { data: serde_json::Value },
+    Error { error: String, status: Option<u16> },
+}
+
+/// Forwards page requests to a backend that computes the pages.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Proxy {
+    backend: ProxyBackend,
+    /// The levels the backend has pages for.
+    #[serde(default = "Proxy::default_page_levels")]
+    page_levels: Vec<PageLevel>,
+    #[serde(default = "Proxy::default_timeout_ms")]
+    timeout_ms: u64,
+    #[serde(skip)]
+    http: reqwest::Client,
+    /// The running process of a process backend, shared by all clones of the provider.
+    #[serde(skip)]
+    process: Arc<Mutex<Option<ProcessHandle>>>,
+}
+
+impl Proxy {
+    fn default_page_levels() -> Vec<PageLevel> {
+        PageLevel::ALL.to_vec()
+    }
+
+    fn default_timeout_ms() -> u64 {
+        10_000
+    }
+
+    fn timeout(&self) -> Duration {
+        Duration::from_millis(self.timeout_ms)
+    }
+
+    async fn page(
+        &self,
+        service_name: &str,
+        query: &serde_json::Value,
+        model_name: &str,
+        page_level: PageLevel,
+        layer_index: Option<u32>,
+        neuron_index: Option<u32>,
+    ) -> Result<Page> {
+        if !self.page_levels.contains(&page_level) {
+            bail!(
+                "No {} page exists for service '{service_name}'.",
+                page_level.to_str()
+            );
+        }
+        let page = match &self.backend {
+            ProxyBackend::Http { url: backend_url } => {
+                let mut url = Url::parse(backend_url)
+                    .with_context(|| format!("Invalid backend URL '{backend_url}'."))?;
+                url.path_segments_mut()
+                    .map_err(|_| anyhow!("Backend URL '{backend_url}' cannot have a path."))?
+                    .pop_if_empty()
+                    .push(model_name)
+                    .extend(layer_index.map(|index| index.to_string()))
+                    .extend(neuron_index.map(|index| index.to_string()));
+                self.http_page(service_name, url, query).await?
+            }
+            ProxyBackend::Process { command, args } => {
+                let request = json!({
+                    "service": service_name,
+                    "model": model_name,
+                    "layer": layer_index,
+                    "neuron": neuron_index,
+                    "query": query,
+                });
+                self.process_page(service_name, command, args, &request)
+                    .await?
+            }
+        };
+        Ok(Page::Json(page))
+    }
+
+    async fn http_page(
+        &self,
+        service_name: &str,
+        url: Url,
+        query: &serde_json::Value,
+    ) -> Result<serde_json::Value, ProxyError> {
+        let backend_error = |message: String| ProxyError::Backend {
+            service: service_name.to_owned(),
+            message,
+        };
+        let response = self
+            .http
+            .get(url.clone())
+            .query(query)
+            .timeout(self.timeout())
+            .send()
+            .await
+            .map_err(|error| {
+                if error.is_timeout() {
+                    ProxyError::Timeout {
+                        service: service_name.to_owned(),
+                        timeout: self.timeout(),
+                    }
+                } else {
+                    backend_error(format!("Request to '{url}' failed: {error}"))
+                }
+            })?;
+        let status = response.status();
+        let text = response
+            .text()
+            .await
+            .map_err(|error| backend_error(format!("Failed to read response: {error}")))?;
+        if !status.is_success() {
+            return Err(ProxyError::Status {
+                service: service_name.to_owned(),
+                status: status.as_u16(),
+                message: text,
+            });
+        }
+        serde_json::from_str(&text)
+            .map_err(|error| backend_error(format!("Response is not valid JSON: {error}")))
+    }
+
+    async fn process_page(
+        &self,
+        service_name: &str,
+        command: &str,
+        args: &[String],
+        request: &serde_json::Value,
+    ) -> Result<serde_json::Value, ProxyError> {
+        let backend_error = |message: String| ProxyError::Backend {
+            service: service_name.to_owned(),
+            message,
+        };
+        let timeout_error = || ProxyError::Timeout {
+            service: service_name.to_owned(),
+            timeout: self.timeout(),
+        };
+        // Requests are answered in order, so only one can be in flight at a time. The timeout
+        // includes waiting for the requests ahead.
+        let deadline = Instant::now() + self.timeout();
+        let mut process = time::timeout_at(deadline, self.process.lock())
+            .await
+            .map_err(|_| timeout_error())?;
+        if process.is_none() {
+            log::info!("Starting backend process '{command}' of service '{service_name}'");
+            *process =
+                Some(spawn_process(command, args).map_err(|error| {
+                    backend_error(format!("Failed to start '{command}': {error}"))
+                })?);
+        }
+        let handle = process.as_mut().expect("Process was started above.");
+
+        let mut line = request.to_string();
+        line.push('\n');
+        let exchange = async {
+            handle.stdin.write_all(line.as_bytes()).await?;
+            handle.stdin.flush().await?;
+            let mut response = String::new();
+            handle.stdout.read_line(&mut response).await?;
+            Ok::<_, std::io::Error>(response)
+        };
+        let response = match time::timeout_at(deadline, exchange).await {
+            Ok(Ok(response)) if !response.is_empty() => Ok(response),
+            Ok(Ok(_)) => Err(backend_error("Process exited.".to_owned())),
+            Ok(Err(error)) => Err(backend_error(format!("Failed to communicate: {error}"))),
+            Err(_) => Err(timeout_error()),
+        };
+        let response = match response {
+            Ok(response) => response,
+            Err(error) => {
+                // The process cannot be trusted to answer the next request in order, so it is
+                // restarted on the next request.
+                if let Some(mut handle) = process.take() {
+                    let _ = handle.child.start_kill();
+                }
+                return Err(error);
+            }
+        };
+
+        match serde_json::from_str(&response) {
+            Ok(ProcessResponse::Data { data }) => Ok(data),
+            Ok(ProcessResponse::Error { error, status }) => Err(ProxyError::Status {
+                service: service_name.to_owned(),
+                status: status.unwrap_or(502),
+                message: error,
+            }),
+            Err(error) => Err(backend_error(format!(
+                "Response is not valid JSON: {error}"
+            ))),
+        }
+    }
+}
+
+fn spawn_process(command: &str, args: &[String]) -> std::io::Result<ProcessHandle> {
+    let mut child = Command::new(command)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()?;
+    let stdin = child.stdin.take().expect("Standard input is piped.");
+    let stdout = BufReader::new(child.stdout.take().expect("Standard output is piped."));
+    Ok(ProcessHandle {
+        child,
+        stdin,
+        stdout,
+    })
+}
+
+#[async_trait]
+impl ServiceProviderTrait for Proxy {
+    async fn model_page(
+        &self,
+        service_name: &str,
+        _state: &State,
+        query: &serde_json::Value,
+        model_name: &str,
+    ) -> Result<Page> {
+        self.page(
+            service_name,
+            query,
+            model_name,
+            PageLevel::Model,
+            None,
+            None,
+        )
+        .await
+    }
+
+    async fn layer_page(
+        &self,
+        service_name: &str,
+        _state: &State,
+        query: &serde_json::Value,
+        model_name: &str,
+        layer_index: u32,
+    ) -> Result<Page> {
+        self.page(
+            service_name,
+            query,
+            model_name,
+            PageLevel::Layer,
+            Some(layer_index),
+            None,
+        )
+        .await
+    }
+
+    async fn neuron_page(
+        &self,
+        service_name: &str,
+        _state: &State,
+        query: &serde_json::Value,
+        model_name: &str,
+        layer_index: u32,
+        neuron_index: u32,
+    ) -> Result<Page> {
+        self.page(
+            service_name,
+            query,
+            model_name,
+            PageLevel::Neuron,
+            Some(layer_index),
+            Some(neuron_index),
+        )
+        .await
+    }
+
+    /// Backends compute their pages on demand, so data is assumed to exist for every model.
+    fn has_data(&self, _service_name: &str, _model_name: &str) -> bool {
+        true
+    }
+
+    fn page_schema(&self, page_level: PageLevel) -> Option<RefOr<Schema>> {
+        self.page_levels.contains(&page_level).then(|| {
+            ObjectBuilder::new()
+                .description(Some("Any JSON value."))
+                .into()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::future;
+
+    use super::*;
+    use crate::server::error_status;
+
+    fn proxy(backend: serde_json::Value, timeout_ms: u64) -> Proxy {
+        serde_json::from_value(json!({
+            "backend": backend,
+            "page_levels": ["neuron"],
+            "timeout_ms": timeout_ms,
+        }))
+        .unwrap()
+    }
+
+    /// A proxy to a shell script answering every request line.
+    fn script_proxy(answer: &str, timeout_ms: u64) -> Proxy {
+        let script = format!("while read -r line; do {answer}; done");
+        proxy(
+            json!({ "Process": { "command": "sh", "args": ["-c", script] } }),
+            timeout_ms,
+        )
+    }
+
+    async fn status(proxy: &Proxy, page_level: PageLevel) -> StatusCode {
+        let page = proxy
+            .page("test", &json!({}), "model", page_level, Some(0), Some(1))
+            .await;
+        match page {
+            Ok(_) => StatusCode::OK,
+            Err(error) => error_status(&error),
+        }
+    }
+
+    #[actix_web::test]
+    async fn forwards_requests_to_processes() {
+        let proxy = proxy(
+            json!({ "Process": { "command": "sed", "args": ["-u", "s/.*/{\"data\":&}/"] } }),
+            10_000,
+        );
+        let page = proxy
+            .page(
+                "test",
+                &json!({ "k": "v" }),
+                "model",
+                PageLevel::Neuron,
+                Some(2),
+                Some(3),
+            )
+            .await
+            .unwrap();
+        let Page::Json(request) = page else {
+            panic!("Proxies return JSON pages.");
+        };
+        assert_eq!(
+            request,
+            json!({
+                "service": "test",
+                "model": "model",
+                "layer": 2,
+                "neuron": 3,
+                "query": { "k": "v" },
+            })
+        );
+    }
+
+    #[actix_web::test]
+    async fn maps_backend_errors_to_statuses() {
+        let cases = [
+            (
+                r#"echo '{"error": "No page.", "status": 404}'"#,
+                StatusCode::NOT_FOUND,
+            ),
+            (r#"echo '{"error": "Failed."}'"#, StatusCode::BAD_GATEWAY),
+            ("echo 'not json'", StatusCode::BAD_GATEWAY),
+            ("exit", StatusCode::BAD_GATEWAY),
+            ("sleep 5", StatusCode::GATEWAY_TIMEOUT),
+        ];
+        for (answer, expected) in cases {
+            let proxy = script_proxy(answer, 200);
+            assert_eq!(
+                status(&proxy, PageLevel::Neuron).await,
+                expected,
+                "{answer}"
+            );
+        }
+
+        let missing = proxy(
+            json!({ "Process": { "command": "/nonexistent/backend" } }),
+            200,
+        );
+        assert_eq!(
+            status(&missing, PageLevel::Neuron).await,
+            StatusCode::BAD_GATEWAY
+        );
+        let refused = proxy(json!({ "Http": { "url": "http://127.0.0.1:1" } }), 200);
+        assert_eq!(
+            status(&refused, PageLevel::Neuron).await,
+            StatusCode::BAD_GATEWAY
+        );
+        // Levels without pages are not the backend's fault.
+        assert_eq!(
+            status(&refused, PageLevel::Model).await,
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+    }
+
+    #[actix_web::test]
+    async fn restarts_processes_that_failed() {
+        // The first process answers only after the timeout, so it is replaced by another.
+        let marker = std::env::temp_dir().join(format!("neuronav-proxy-{}", std::process::id()));
+        let marker = marker.to_string_lossy();
+        let proxy = script_proxy(
+            &format!(
+                r#"if [ -e '{marker}' ]; then echo '{{"data": {{}}}}'; else touch '{marker}'; sleep 5; fi"#
+            ),
+            500,
+        );
+        let first = status(&proxy, PageLevel::Neuron).await;
+        let second = status(&proxy, PageLevel::Neuron).await;
+        let _ = std::fs::remove_file(&*marker);
+        assert_eq!(first, StatusCode::GATEWAY_TIMEOUT);
+        assert_eq!(second, StatusCode::OK);
+    }
+
+    #[actix_web::test]
+    async fn counts_waiting_for_other_requests_against_the_timeout() {
+        let proxy = script_proxy(r#"sleep 1; echo '{"data": {}}'"#, 1500);
+        let statuses = future::join_all((0..2).map(|_| status(&proxy, PageLevel::Neuron))).await;
+        assert_eq!(statuses, [StatusCode::OK, StatusCode::GATEWAY_TIMEOUT]);
+    }
+}
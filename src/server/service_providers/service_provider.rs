@@ -1,16 +1,20 @@
-use std::{future::Future, pin::Pin};
-
+use std::{future::Future, path::PathBuf, pin::Pin};
 
 use anyhow::{bail, Result};
 use async_trait::async_trait;
 use delegate::delegate;
 use serde::{Deserialize, Serialize};
+use utoipa::openapi::{RefOr, Schema};
 
 use super::{
-    metadata::Metadata, neuron2graph::Neuron2Graph, neuron2graph_search::Neuron2GraphSearch,
-    neuroscope::Neuroscope,
+    explanation::Explanation, json_directory::JsonDirectory, metadata::Metadata,
+    neuron2graph::Neuron2Graph, neuron2graph_search::Neuron2GraphSearch, neuroscope::Neuroscope,
+    proxy::Proxy,
+};
+use crate::{
+    data::ModelMetadata,
+    server::{Page, PageIndex, PageLevel, QueryParameter, State},
 };
-use crate::server::State;
 
 #[allow(unused_variables)]
 #[async_trait]
@@ -21,7 +25,7 @@ pub trait ServiceProviderTrait: Clone + Serialize + Deserialize<'static> + Send
         state: &State,
         query: &serde_json::Value,
         model_name: &str,
-    ) -> Result<serde_json::Value> {
+    ) -> Result<Page> {
         bail!("No model page exists for service '{}'.", service_name);
     }
     async fn layer_page(
@@ -31,7 +35,7 @@ pub trait ServiceProviderTrait: Clone + Serialize + Deserialize<'static> + Send
         query: &serde_json::Value,
         model_name: &str,
         layer_index: u32,
-    ) -> Result<serde_json::Value> {
+    ) -> Result<Page> {
         bail!("No layer page exists for service '{}'.", service_name);
     }
     async fn neuron_page(
@@ -42,9 +46,33 @@ pub trait ServiceProviderTrait: Clone + Serialize + Deserialize<'static> + Send
         model_name: &str,
         layer_index: u32,
         neuron_index: u32,
-    ) -> Result<serde_json::Value> {
+    ) -> Result<Page> {
         bail!("No neuron page exists for service '{}'.", service_name);
     }
+    /// Schema of the pages at the given level, or `None` if the service has no such pages.
+    fn page_schema(&self, page_level: PageLevel) -> Option<RefOr<Schema>> {
+        None
+    }
+    fn query_parameters(&self, page_level: PageLevel) -> Vec<QueryParameter> {
+        vec![]
+    }
+    /// Whether data for the given model exists for this provider.
+    fn has_data(&self, service_name: &str, model_name: &str) -> bool;
+    /// Number of neurons of the model with a neuron page, or `None` if the provider cannot tell
+    /// without producing the pages.
+    fn neuron_coverage(&self, service_name: &str, model_metadata: &ModelMetadata) -> Option<u32> {
+        None
+    }
+    /// Files the page is read from, used to tell whether it changed without producing it. Empty if
+    /// the provider cannot tell.
+    fn page_files(
+        &self,
+        service_name: &str,
+        model_name: &str,
+        page_index: PageIndex,
+    ) -> Vec<PathBuf> {
+        vec![]
+    }
 }
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -53,6 +81,9 @@ pub enum ServiceProvider {
     Neuroscope,
     Neuron2Graph,
     Neuron2GraphSearch,
+    Explanation,
+    JsonDirectory(JsonDirectory),
+    Proxy(Proxy),
 }
 
 impl ServiceProvider {
@@ -60,12 +91,36 @@ impl ServiceProvider {
         matches!(self, ServiceProvider::Metadata)
     }
 
+    /// Name of the provider as used in payload configurations.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ServiceProvider::Metadata => "Metadata",
+            ServiceProvider::Neuroscope => "Neuroscope",
+            ServiceProvider::Neuron2Graph => "Neuron2Graph",
+            ServiceProvider::Neuron2GraphSearch => "Neuron2GraphSearch",
+            ServiceProvider::Explanation => "Explanation",
+            ServiceProvider::JsonDirectory(_) => "JsonDirectory",
+            ServiceProvider::Proxy(_) => "Proxy",
+        }
+    }
+
+    /// The levels the provider has pages for.
+    pub fn page_levels(&self) -> Vec<PageLevel> {
+        PageLevel::ALL
+            .into_iter()
+            .filter(|&page_level| self.page_schema(page_level).is_some())
+            .collect()
+    }
+
     delegate! {
         to match self {
             ServiceProvider::Metadata => Metadata,
             ServiceProvider::Neuroscope => Neuroscope,
             ServiceProvider::Neuron2Graph => Neuron2Graph,
             ServiceProvider::Neuron2GraphSearch => Neuron2GraphSearch,
+            ServiceProvider::Explanation => Explanation,
+            ServiceProvider::JsonDirectory(json_directory) => json_directory,
+            ServiceProvider::Proxy(proxy) => proxy,
         } {
             pub fn model_page<'a>(
                 &'a self,
@@ -73,7 +128,7 @@ impl ServiceProvider {
                 state: &'a State,
                 query: &'a serde_json::Value,
                 model_name: &'a str,
-            ) -> Pin<Box<dyn Future<Output = Result<serde_json::Value>> + Send + 'a>>;
+            ) -> Pin<Box<dyn Future<Output = Result<Page>> + Send + 'a>>;
 
             pub fn layer_page<'a>(
                 &'a self,
@@ -82,7 +137,7 @@ impl ServiceProvider {
                 query: &'a serde_json::Value,
                 model_name: &'a str,
                 layer_index: u32,
-            ) -> Pin<Box<dyn Future<Output = Result<serde_json::Value>> + Send + 'a >>;
+            ) -> Pin<Box<dyn Future<Output = Result<Page>> + Send + 'a >>;
 
             pub fn neuron_page<'a>(
                 &'a self,
@@ -92,7 +147,26 @@ impl ServiceProvider {
                 model_name: &'a str,
                 layer_index: u32,
                 neuron_index: u32,
-            ) -> Pin<Box<dyn Future<Output = Result<serde_json::Value>> + Send + 'a >>;
+            ) -> Pin<Box<dyn Future<Output = Result<Page>> + Send + 'a >>;
+
+            pub fn page_schema(&self, page_level: PageLevel) -> Option<RefOr<Schema>>;
+
+            pub fn query_parameters(&self, page_level: PageLevel) -> Vec<QueryParameter>;
+
+            pub fn has_data(&self, service_name: &str, model_name: &str) -> bool;
+
+            pub fn neuron_coverage(
+                &self,
+                service_name: &str,
+                model_metadata: &ModelMetadata,
+            ) -> Option<u32>;
+
+            pub fn page_files(
+                &self,
+                service_name: &str,
+                model_name: &str,
+                page_index: PageIndex,
+            ) -> Vec<PathBuf>;
         }
     }
 }
@@ -0,0 +1,89 @@
+use actix_web::{get, http::header::ContentType, web, HttpRequest, HttpResponse, Responder};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use super::{Grant, PageLevel, QueryParameter, Service, State};
+
+#[derive(Clone, Debug, Serialize, ToSchema)]
+pub struct PageDescription {
+    level: PageLevel,
+    query_parameters: Vec<QueryParameter>,
+}
+
+#[derive(Clone, Debug, Serialize, ToSchema)]
+pub struct ServiceDescription {
+    name: String,
+    /// Kind of the provider serving the pages of the service.
+    provider: &'static str,
+    /// The levels the service has pages for.
+    pages: Vec<PageDescription>,
+    /// Whether data exists for the model. Only present when requested for a model.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    has_data: Option<bool>,
+}
+
+impl ServiceDescription {
+    fn new(service: &Service, model_name: Option<&str>) -> Self {
+        let provider = service.provider();
+        Self {
+            name: service.name().to_owned(),
+            provider: provider.kind(),
+            pages: provider
+                .page_levels()
+                .into_iter()
+                .map(|level| PageDescription {
+                    level,
+                    query_parameters: provider.query_parameters(level),
+                })
+                .collect(),
+            has_data: model_name.map(|model_name| provider.has_data(service.name(), model_name)),
+        }
+    }
+}
+
+fn services_response(state: &State, grant: &Grant, model_name: Option<&str>) -> HttpResponse {
+    let mut descriptions: Vec<_> = state
+        .payload()
+        .services()
+        .filter(|service| grant.allows_service(service.name()))
+        .map(|service| ServiceDescription::new(service, model_name))
+        .collect();
+    descriptions.sort_unstable_by(|a, b| a.name.cmp(&b.name));
+    match serde_json::to_string(&descriptions) {
+        Ok(body) => HttpResponse::Ok()
+            .content_type(ContentType::json())
+            .body(body),
+        Err(error) => HttpResponse::InternalServerError().body(format!("{error}")),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/services",
+    responses((status = 200, description = "The services of the server that the client may access.", body = Vec<ServiceDescription>))
+)]
+#[get("/api/services")]
+pub async fn all_services(state: web::Data<State>, request: HttpRequest) -> impl Responder {
+    services_response(&state, &Grant::of(&request), None)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/{model_name}/services",
+    params(("model_name" = String, Path, description = "Name of the model.")),
+    responses(
+        (
+            status = 200,
+            description = "The services of the server, with whether data exists for the model.",
+            body = Vec<ServiceDescription>
+        )
+    )
+)]
+#[get("/api/{model_name}/services")]
+pub async fn model_services(
+    state: web::Data<State>,
+    request: HttpRequest,
+    model_name: web::Path<String>,
+) -> impl Responder {
+    services_response(&state, &Grant::of(&request), Some(model_name.as_str()))
+}